@@ -4,6 +4,7 @@
   const statusCoordsEl = document.getElementById('status-coords');
   const chatLog = document.getElementById('chat-log');
   const chatInput = document.getElementById('chat-input');
+  const chatChannel = document.getElementById('chat-channel');
   const nameInput = document.getElementById('name-input');
   const nameSave = document.getElementById('name-save');
   const startupEl = document.getElementById('startup');
@@ -28,6 +29,13 @@
   const fullscreenButton = document.getElementById('fullscreen-toggle');
   const musicToggle = document.getElementById('music-toggle');
   const sfxToggle = document.getElementById('sfx-toggle');
+  const pvpToggle = document.getElementById('pvp-toggle');
+  const partyPanel = document.getElementById('party');
+  const partyTitle = document.getElementById('party-title');
+  const partyList = document.getElementById('party-list');
+  const guildPanel = document.getElementById('guild');
+  const guildTitle = document.getElementById('guild-title');
+  const guildList = document.getElementById('guild-list');
   const joystickEl = document.getElementById('touch-joystick');
   const joystickHandle = joystickEl ? joystickEl.querySelector('.stick-handle') : null;
   const actionButtons = Array.from(document.querySelectorAll('.action-btn'));
@@ -55,6 +63,11 @@
       statusConnecting: 'Connecting...',
       statusConnected: 'Connected. Exploring...',
       statusDisconnected: 'Disconnected. Reconnecting...',
+      statusKicked: 'Disconnected by an administrator.',
+      kickedReason: 'Disconnected by an administrator: {reason}',
+      banned: 'You are banned from this server.',
+      bannedReason: 'Reason: {reason}',
+      bannedUntil: 'The ban ends {time}.',
       statusSessionFailed: 'Failed to start session.',
       panelInventory: 'Inventory',
       panelBuild: 'Build',
@@ -104,14 +117,37 @@
       helpTouch: 'Touch: drag screen or joystick to move · Tap Attack/Gather/Interact · Tap chat to type',
       inventoryEmpty: 'Empty',
       inventoryEat: 'Click to eat',
+      inventoryPlant: 'Click to plant where you stand',
       musicToggle: 'Toggle music',
       sfxToggle: 'Toggle sound effects',
+      pvpToggle: 'PvP',
+      pvpToggleAria: 'Toggle PvP',
+      partyTitle: 'Party',
+      partyLeader: 'Leader',
+      partyInvite: '{name} invited you to a party. Type /accept to join.',
+      partyCommandUsage: 'Party commands: /invite name, /accept, /leave, /kick name, /loot damage|round_robin|random',
+      guildTitle: 'Guild',
+      guildOffline: 'offline',
+      chatChannels: { local: 'Local', global: 'Global', party: 'Party', guild: 'Guild' },
+      chatChannelAria: 'Chat channel',
+      chatWhisperTo: 'To {name}: {text}',
+      chatWhisperFrom: '{name} whispers: {text}',
+      guildRanks: { leader: 'Leader', officer: 'Officer', member: 'Member' },
+      guildInvite: '{name} invited you to the guild {guild}. Type /guild accept to join.',
+      guildCommandUsage: 'Guild commands: /guild create TAG name, /guild invite name, /guild accept, /guild leave, /guild kick name, /guild rank name leader|officer|member, /guild allow|deny officer|member invite|kick|promote|demolish',
+      buildOptionGuildClaim: 'Give to guild',
+      buildStatusTarget: 'Click a structure.',
       hpLabel: 'HP',
     },
     de: {
       statusConnecting: 'Verbinde...',
       statusConnected: 'Verbunden. Auf Erkundung...',
       statusDisconnected: 'Getrennt. Verbinde neu...',
+      statusKicked: 'Von einem Administrator getrennt.',
+      kickedReason: 'Von einem Administrator getrennt: {reason}',
+      banned: 'Du bist von diesem Server gebannt.',
+      bannedReason: 'Grund: {reason}',
+      bannedUntil: 'Der Bann endet am {time}.',
       statusSessionFailed: 'Sitzung konnte nicht gestartet werden.',
       panelInventory: 'Inventar',
       panelBuild: 'Bauen',
@@ -161,8 +197,26 @@
       helpTouch: 'Touch: Bildschirm oder Joystick ziehen zum Laufen · Angriff/Sammeln/Interagieren tippen · Chat zum Tippen antippen',
       inventoryEmpty: 'Leer',
       inventoryEat: 'Klicken zum Essen',
+      inventoryPlant: 'Klicken zum Pflanzen an deiner Position',
       musicToggle: 'Musik umschalten',
       sfxToggle: 'Soundeffekte umschalten',
+      pvpToggle: 'PvP',
+      pvpToggleAria: 'PvP umschalten',
+      partyTitle: 'Gruppe',
+      partyLeader: 'Leitung',
+      partyInvite: '{name} hat dich in eine Gruppe eingeladen. Tippe /accept zum Beitreten.',
+      partyCommandUsage: 'Gruppenbefehle: /invite Name, /accept, /leave, /kick Name, /loot damage|round_robin|random',
+      guildTitle: 'Gilde',
+      guildOffline: 'offline',
+      chatChannels: { local: 'Lokal', global: 'Global', party: 'Gruppe', guild: 'Gilde' },
+      chatChannelAria: 'Chatkanal',
+      chatWhisperTo: 'An {name}: {text}',
+      chatWhisperFrom: '{name} flüstert: {text}',
+      guildRanks: { leader: 'Leitung', officer: 'Offizier', member: 'Mitglied' },
+      guildInvite: '{name} hat dich in die Gilde {guild} eingeladen. Tippe /guild accept zum Beitreten.',
+      guildCommandUsage: 'Gildenbefehle: /guild create KÜRZEL Name, /guild invite Name, /guild accept, /guild leave, /guild kick Name, /guild rank Name leader|officer|member, /guild allow|deny officer|member invite|kick|promote|demolish',
+      buildOptionGuildClaim: 'An Gilde übergeben',
+      buildStatusTarget: 'Gebäude anklicken.',
       hpLabel: 'HP',
     },
   };
@@ -179,12 +233,17 @@
   let startupEntered = false;
   let initialName = null;
 
-  const MAX_HEARTS = 10;
+  let MAX_HEARTS = 10;
   let tileSize = 32;
   let chunkSize = 32;
   let playerId = null;
+  let partyState = null;
+  let guildState = null;
+  let disconnectNotice = null;
+  let localAdmin = false;
   let playerState = null;
   let localInBoat = false;
+  let localPvp = false;
   let worldSeed = 0;
   const PLAYER_ANCHOR = { x: 0.5, y: 0.9 };
   const RESOURCE_ANCHOR = { x: 0.5, y: 1.0 };
@@ -210,6 +269,22 @@
   overlayLayer.sortableChildren = true;
   world.addChild(tileLayer, structureLayer, entityLayer, projectileLayer, overlayLayer);
   app.stage.addChild(world);
+  const nightOverlay = new PIXI.Graphics();
+  nightOverlay.eventMode = 'none';
+  app.stage.addChild(nightOverlay);
+  let worldClock = null;
+  let campfireLit = true;
+  let nightOverlaySize = { width: 0, height: 0 };
+  const weatherOverlay = new PIXI.Graphics();
+  weatherOverlay.eventMode = 'none';
+  app.stage.addChild(weatherOverlay);
+  const chunkWeather = new Map();
+  let weatherOverlayState = { weather: null, width: 0, height: 0 };
+  const weatherTints = {
+    rain: { color: 0x3a5a7a, alpha: 0.18 },
+    storm: { color: 0x1c2433, alpha: 0.32 },
+    fog: { color: 0xd8dee6, alpha: 0.38 },
+  };
 
   const tileAssetUrls = [
     'assets/tiles/grass.svg',
@@ -232,6 +307,9 @@
     'assets/entities/palm-1.svg',
     'assets/entities/palm-2.svg',
     'assets/entities/palm-3.svg',
+    'assets/entities/wheat-1.svg',
+    'assets/entities/wheat-2.svg',
+    'assets/entities/wheat-3.svg',
     'assets/entities/rock.svg',
     'assets/entities/rock-small.svg',
     'assets/entities/rock-medium.svg',
@@ -245,6 +323,7 @@
     'assets/entities/boat.svg',
     'assets/entities/tent.svg',
     'assets/entities/campfire.svg',
+    'assets/entities/campfire-out.svg',
     'assets/entities/player.svg',
     'assets/entities/player-back.svg',
     'assets/entities/player-side.svg',
@@ -255,6 +334,9 @@
     'assets/entities/slime.svg',
     'assets/entities/boar.svg',
     'assets/entities/rabbit.svg',
+    'assets/entities/wolf.svg',
+    'assets/entities/goblin-archer.svg',
+    'assets/entities/forest-troll.svg',
     'assets/entities/arrow.svg',
   ];
   await PIXI.Assets.load([...tileAssetUrls, ...entityAssetUrls]);
@@ -270,6 +352,7 @@
   const playerEntities = new Map();
   const monsterEntities = new Map();
   const projectileSprites = new Map();
+  const groundItemSprites = new Map();
   const boatEntities = new Map();
   const npcSprites = new Map();
   const landmarkSprites = new Map();
@@ -286,6 +369,8 @@
         return textures[`pine_tree${level}`] || textures.pine_tree1;
       case 'palm_tree':
         return textures[`palm_tree${level}`] || textures.palm_tree1;
+      case 'wheat':
+        return textures[`wheat${level}`] || textures.wheat1;
       case 'rock': {
         if (level >= 3) return textures.rockLarge || textures.rock;
         if (level === 2) return textures.rockMedium || textures.rock;
@@ -299,7 +384,7 @@
   const loadedChunks = new Set();
   const pendingChunks = new Set();
   const CHUNK_REQUEST_RADIUS = 2;
-  const CHUNK_KEEP_RADIUS = 3;
+  let CHUNK_KEEP_RADIUS = 3;
 
   const keys = new Set();
   const touchState = {
@@ -319,6 +404,12 @@
     dirY: 0,
     pointerId: null,
   };
+  const aimState = {
+    valid: false,
+    x: 0,
+    y: 0,
+  };
+  const AIM_TARGET_RADIUS = 0.8;
   const gamepadState = {
     active: false,
     connected: false,
@@ -333,13 +424,13 @@
   const GAMEPAD_DEADZONE = 0.18;
   const INTERP_MS = 120;
   const INPUT_SEND_INTERVAL_MS = 90;
-  const PLAYER_SPEED = 3.4;
+  let PLAYER_SPEED = 3.4;
   const CORRECTION_DISTANCE = 2;
   const MAX_CHAT_LINES = 60;
   const TYPING_IDLE_MS = 1800;
-  const MAX_NAME_CHARS = 20;
-  const GATHER_RANGE = 1.1;
-  const INTERACT_RANGE = 1.2;
+  let MAX_NAME_CHARS = 20;
+  let GATHER_RANGE = 1.1;
+  let INTERACT_RANGE = 1.2;
   const randomNameAdjectives = [
     'Bright',
     'Duskwind',
@@ -615,6 +706,158 @@
     );
   }
 
+  function renderParty() {
+    if (!partyPanel || !partyList) return;
+    partyPanel.classList.toggle('hidden', !partyState);
+    while (partyList.firstChild) {
+      partyList.removeChild(partyList.firstChild);
+    }
+    if (!partyState) return;
+    const self = playerId ? playerEntities.get(playerId) : null;
+    partyState.members.forEach((member) => {
+      const row = document.createElement('div');
+      row.className = 'party-member';
+      if (member.id === partyState.leader) {
+        row.classList.add('leader');
+        row.title = t('partyLeader');
+      }
+      let text = `${member.name} · ${t('hpLabel')} ${member.hp}`;
+      if (self && member.id !== playerId) {
+        const dx = member.x - self.x;
+        const dy = member.y - self.y;
+        text += ` · ${Math.round(Math.hypot(dx, dy))}m ${compassDirection(dx, dy)}`;
+      }
+      row.textContent = text;
+      partyList.appendChild(row);
+    });
+  }
+
+  function renderGuild() {
+    if (!guildPanel || !guildList) return;
+    guildPanel.classList.toggle('hidden', !guildState);
+    while (guildList.firstChild) {
+      guildList.removeChild(guildList.firstChild);
+    }
+    if (!guildState) return;
+    if (guildTitle) {
+      guildTitle.textContent = `[${guildState.tag}] ${guildState.name}`;
+    }
+    const ranks = t('guildRanks');
+    guildState.members.forEach((member) => {
+      const row = document.createElement('div');
+      row.className = 'guild-member';
+      row.classList.toggle('offline', !member.online);
+      let text = `${member.name} · ${ranks[member.rank] || member.rank}`;
+      if (!member.online) {
+        text += ` · ${t('guildOffline')}`;
+      }
+      row.textContent = text;
+      guildList.appendChild(row);
+    });
+  }
+
+  function guildCommand(text) {
+    const match = text.match(/^\/guild(?:\s+(.*))?$/i);
+    if (!match) return null;
+    const rest = (match[1] || '').trim();
+    const [sub = '', ...args] = rest.split(/\s+/);
+    const arg = args.join(' ');
+    switch (sub.toLowerCase()) {
+      case 'create':
+        return args.length >= 2
+          ? { type: 'guild_create', tag: args[0], name: args.slice(1).join(' ') }
+          : false;
+      case 'invite':
+        return arg ? { type: 'guild_invite', name: arg } : false;
+      case 'accept':
+        return { type: 'guild_accept' };
+      case 'leave':
+        return { type: 'guild_leave' };
+      case 'kick':
+        return arg ? { type: 'guild_kick', name: arg } : false;
+      case 'rank': {
+        const rank = (args[args.length - 1] || '').toLowerCase();
+        const name = args.slice(0, -1).join(' ');
+        return name && ['leader', 'officer', 'member'].includes(rank)
+          ? { type: 'guild_rank', name, rank }
+          : false;
+      }
+      case 'allow':
+      case 'deny': {
+        const rank = (args[0] || '').toLowerCase();
+        const permission = (args[1] || '').toLowerCase();
+        return ['officer', 'member'].includes(rank) &&
+          ['invite', 'kick', 'promote', 'demolish'].includes(permission)
+          ? { type: 'guild_permission', rank, permission, allowed: sub.toLowerCase() === 'allow' }
+          : false;
+      }
+      default:
+        return false;
+    }
+  }
+
+  function formatChat(msg) {
+    if (msg.emote) {
+      return `* ${msg.from} ${msg.text}`;
+    }
+    switch (msg.channel) {
+      case 'whisper':
+        return msg.to
+          ? t('chatWhisperTo').replace('{name}', msg.to).replace('{text}', msg.text)
+          : t('chatWhisperFrom').replace('{name}', msg.from).replace('{text}', msg.text);
+      case 'global':
+      case 'party':
+      case 'guild':
+        return `[${t('chatChannels')[msg.channel]}] ${msg.from}: ${msg.text}`;
+      default:
+        return `${msg.from}: ${msg.text}`;
+    }
+  }
+
+  function compassDirection(dx, dy) {
+    const names = ['E', 'SE', 'S', 'SW', 'W', 'NW', 'N', 'NE'];
+    const index = Math.round(Math.atan2(dy, dx) / (Math.PI / 4));
+    return names[(index + 8) % 8];
+  }
+
+  function partyCommand(text) {
+    const match = text.match(/^\/(\w+)\s*(.*)$/);
+    if (!match) return null;
+    const arg = match[2].trim();
+    switch (match[1].toLowerCase()) {
+      case 'invite':
+        return arg ? { type: 'party_invite', name: arg } : false;
+      case 'accept':
+        return { type: 'party_accept' };
+      case 'leave':
+        return { type: 'party_leave' };
+      case 'kick': {
+        if (!arg) return false;
+        const partyMember = partyState && partyState.members.some(
+          (member) => member.name.toLowerCase() === arg.toLowerCase()
+        );
+        // Admins kick from the server unless they name someone in their party.
+        if (localAdmin && !partyMember) return null;
+        return { type: 'party_kick', name: arg };
+      }
+      case 'loot':
+        return ['damage', 'round_robin', 'random'].includes(arg)
+          ? { type: 'party_loot', mode: arg }
+          : false;
+      default:
+        return null;
+    }
+  }
+
+  function updatePvpToggle() {
+    setToggleButtonState(
+      pvpToggle,
+      localPvp,
+      `${t('pvpToggleAria')} (On)`,
+      `${t('pvpToggleAria')} (Off)`
+    );
+  }
+
   function setToggleButtonState(button, enabled, labelOn, labelOff) {
     if (!button) return;
     button.classList.toggle('active', enabled);
@@ -848,6 +1091,13 @@
       sfxToggle.setAttribute('aria-label', label);
       sfxToggle.setAttribute('title', label);
     }
+    if (partyTitle) {
+      partyTitle.textContent = t('partyTitle');
+    }
+    if (pvpToggle) {
+      pvpToggle.textContent = t('pvpToggle');
+      updatePvpToggle();
+    }
     if (fullscreenButton) {
       updateFullscreenButton();
     }
@@ -875,6 +1125,13 @@
     if (chatInput) {
       chatInput.placeholder = t('chatPlaceholder');
     }
+    if (chatChannel) {
+      chatChannel.setAttribute('aria-label', t('chatChannelAria'));
+      const channels = t('chatChannels');
+      Array.from(chatChannel.options).forEach((option) => {
+        option.textContent = channels[option.value] || option.value;
+      });
+    }
     if (buildStatus) {
       buildStatus.textContent = t('buildStatusSelect');
     }
@@ -890,6 +1147,7 @@
       road: t('buildOptionRoad'),
       boat: t('buildOptionBoat'),
       demolish: t('buildOptionDemolish'),
+      guild_claim: t('buildOptionGuildClaim'),
     };
     buildButtons.forEach((button) => {
       const label = buildLabels[button.dataset.build];
@@ -1121,6 +1379,16 @@
         row.addEventListener('click', () => {
           sendMessage({ type: 'use_item', id: item.id });
         });
+      } else if (item.plants) {
+        row.classList.add('is-usable');
+        row.setAttribute('role', 'button');
+        row.setAttribute('tabindex', '0');
+        row.title = t('inventoryPlant');
+        row.addEventListener('click', () => {
+          const tile = getPlayerTile();
+          if (!tile) return;
+          sendMessage({ type: 'plant', id: item.id, x: tile.x, y: tile.y });
+        });
       }
       inventoryList.appendChild(row);
     });
@@ -1136,6 +1404,10 @@
     buildStatus.textContent = text;
   }
 
+  function isTargetMode(kind) {
+    return kind === 'demolish' || kind === 'guild_claim';
+  }
+
   function isCraftKind(kind) {
     return craftKinds.has(kind);
   }
@@ -1178,6 +1450,9 @@
     } else if (buildMode === 'demolish') {
       setBuildStatus(t('buildStatusDemolish'));
       clearBuildPreview();
+    } else if (isTargetMode(buildMode)) {
+      setBuildStatus(t('buildStatusTarget'));
+      clearBuildPreview();
     } else if (isCraftKind(buildMode)) {
       setBuildStatus(t('buildStatusCraftRequested'));
       clearBuildPreview();
@@ -1188,7 +1463,7 @@
   }
 
   function ensureBuildPreview(kind) {
-    if (!kind || kind === 'demolish' || isCraftKind(kind)) return;
+    if (!kind || isTargetMode(kind) || isCraftKind(kind)) return;
     ensureTextures();
     const textureKey = baseStructureKind(kind).replace(/_(h|v)$/, '');
     const texture = textures[textureKey];
@@ -1226,7 +1501,7 @@
   }
 
   function updateBuildPreview(tile) {
-    if (!tile || !buildMode || buildMode === 'demolish' || isCraftKind(buildMode)) {
+    if (!tile || !buildMode || isTargetMode(buildMode) || isCraftKind(buildMode)) {
       clearBuildPreview();
       return;
     }
@@ -1276,7 +1551,7 @@
   }
 
   function handlePointerPreview(event) {
-    if (!buildMode || buildMode === 'demolish') return;
+    if (!buildMode || isTargetMode(buildMode)) return;
     lastPointerTile = screenToTile(event);
     updateBuildPreview(lastPointerTile);
   }
@@ -1313,6 +1588,10 @@
       setBuildStatus(t('buildStatusDemolishRequested'));
       return true;
     }
+    if (isTargetMode(buildMode)) {
+      sendMessage({ type: buildMode, x: tile.x, y: tile.y });
+      return true;
+    }
     sendMessage({ type: 'build', kind: buildMode, x: tile.x, y: tile.y });
     setBuildStatus(t('buildStatusPlacementRequested'));
     return true;
@@ -2054,6 +2333,12 @@
       }
       structureSprites.set(key, entry);
     }
+    if (entry.kind !== structure.kind) {
+      const texture = textures[baseKind.replace(/_(h|v)$/, '')];
+      if (texture) {
+        entry.sprite.texture = texture;
+      }
+    }
     entry.id = structure.id;
     entry.kind = structure.kind;
     entry.tileX = structure.x;
@@ -2157,7 +2442,12 @@
         entity.boatId = player.boat_id ?? null;
         updateEntityTarget(entity, player.x, player.y, now);
       }
-      ensurePlayerLabel(entity, player.name);
+      ensurePlayerLabel(entity, player.guild ? `[${player.guild}] ${player.name}` : player.name);
+      entity.label.tint = player.pvp ? 0xff7a6b : 0xffffff;
+      if (player.id === playerId && localPvp !== Boolean(player.pvp)) {
+        localPvp = Boolean(player.pvp);
+        updatePvpToggle();
+      }
       entity.hp = player.hp;
     });
 
@@ -2182,6 +2472,9 @@
       if (!entity) {
         const sprite = new PIXI.Sprite(texture);
         sprite.anchor.set(0.5, 0.9);
+        if (monster.boss) {
+          sprite.scale.set(1.8);
+        }
         entityLayer.addChild(sprite);
         entity = createEntityState(sprite, monster.x, monster.y, now, { kind: monster.kind });
         monsterEntities.set(monster.id, entity);
@@ -2253,7 +2546,7 @@
     projectiles.forEach((proj) => {
       seen.add(proj.id);
       if (!projectileSprites.has(proj.id)) {
-        const sprite = new PIXI.Sprite(textures.arrow);
+        const sprite = new PIXI.Sprite(textures[proj.kind] || textures.arrow);
         sprite.anchor.set(0.5, 0.5);
         projectileLayer.addChild(sprite);
         projectileSprites.set(proj.id, sprite);
@@ -2297,6 +2590,50 @@
     });
   }
 
+  function syncGroundItems(items) {
+    items.forEach((item) => {
+      if (groundItemSprites.has(item.id)) return;
+      const sprite = new PIXI.Sprite(textures[item.item] || textures.arrow);
+      sprite.anchor.set(0.5, 0.5);
+      sprite.rotation = (item.id % 8) * (Math.PI / 4);
+      sprite.alpha = 0.85;
+      sprite.x = item.x * tileSize;
+      sprite.y = item.y * tileSize;
+      structureLayer.addChild(sprite);
+      sprite.zIndex = sprite.y;
+      groundItemSprites.set(item.id, sprite);
+    });
+  }
+
+  function removeGroundItems(ids) {
+    if (!ids) return;
+    ids.forEach((id) => {
+      const sprite = groundItemSprites.get(id);
+      if (!sprite) return;
+      if (sprite.parent) {
+        sprite.parent.removeChild(sprite);
+      }
+      sprite.destroy();
+      groundItemSprites.delete(id);
+    });
+  }
+
+  function showProjectileImpact(x, y) {
+    const puff = new PIXI.Graphics();
+    puff.beginFill(0xf2e6c9, 0.7);
+    puff.drawCircle(0, 0, tileSize * 0.18);
+    puff.endFill();
+    puff.x = x * tileSize;
+    puff.y = y * tileSize;
+    projectileLayer.addChild(puff);
+    setTimeout(() => {
+      if (puff.parent) {
+        puff.parent.removeChild(puff);
+      }
+      puff.destroy();
+    }, 180);
+  }
+
   function removeProjectiles(ids) {
     if (!ids) return;
     ids.forEach((id) => {
@@ -2322,6 +2659,12 @@
     npcSprites.set(npc.id, { sprite, x: npc.x, y: npc.y });
   }
 
+  function replaceNpcs(npcs) {
+    npcSprites.forEach((entry) => entry.sprite.destroy());
+    npcSprites.clear();
+    npcs.forEach((npc) => addNpc(npc));
+  }
+
   function addLandmark(id, textureKey, tileX, tileY, anchor = PLAYER_ANCHOR) {
     if (landmarkSprites.has(id)) return;
     const texture = textures[textureKey];
@@ -2340,10 +2683,60 @@
     if (!world) return;
     const baseX = Math.round(world.spawn_x);
     const baseY = Math.round(world.spawn_y);
-    addLandmark('campfire', 'campfire', baseX, baseY, PLAYER_ANCHOR);
+    addLandmark('campfire', campfireLit ? 'campfire' : 'campfire_out', baseX, baseY, PLAYER_ANCHOR);
     addLandmark('tent', 'tent', baseX + 1, baseY, PLAYER_ANCHOR);
   }
 
+  function setCampfireLit(lit) {
+    campfireLit = lit;
+    const sprite = landmarkSprites.get('campfire');
+    const texture = textures[lit ? 'campfire' : 'campfire_out'];
+    if (sprite && texture) sprite.texture = texture;
+  }
+
+  function currentTimeOfDay(now) {
+    if (!worldClock) return 0.5;
+    const elapsed = (now - worldClock.receivedAt) / worldClock.dayLengthMs;
+    return (((worldClock.time + elapsed) % 1) + 1) % 1;
+  }
+
+  function updateNightOverlay(now) {
+    const width = app.renderer.width;
+    const height = app.renderer.height;
+    if (width !== nightOverlaySize.width || height !== nightOverlaySize.height) {
+      nightOverlay.clear();
+      nightOverlay.beginFill(0x0a1030, 1);
+      nightOverlay.drawRect(0, 0, width, height);
+      nightOverlay.endFill();
+      nightOverlaySize = { width, height };
+    }
+    const sun = Math.cos((currentTimeOfDay(now) - 0.5) * Math.PI * 2);
+    const darkness = Math.min(1, Math.max(0, (0.35 - sun) / 1.1));
+    nightOverlay.alpha = darkness * 0.55;
+  }
+
+  function updateWeatherOverlay() {
+    const tile = getPlayerTile();
+    const weather = tile ? chunkWeather.get(chunkKeyForTile(tile.x, tile.y)) || 'clear' : 'clear';
+    const width = app.renderer.width;
+    const height = app.renderer.height;
+    if (
+      weather === weatherOverlayState.weather &&
+      width === weatherOverlayState.width &&
+      height === weatherOverlayState.height
+    ) {
+      return;
+    }
+    weatherOverlay.clear();
+    const tint = weatherTints[weather];
+    if (tint) {
+      weatherOverlay.beginFill(tint.color, tint.alpha);
+      weatherOverlay.drawRect(0, 0, width, height);
+      weatherOverlay.endFill();
+    }
+    weatherOverlayState = { weather, width, height };
+  }
+
   function updateCamera() {
     const playerEntity = playerEntities.get(playerId);
     if (!playerEntity) return;
@@ -2422,6 +2815,9 @@
     textures.palm_tree1 = PIXI.Texture.from('assets/entities/palm-1.svg');
     textures.palm_tree2 = PIXI.Texture.from('assets/entities/palm-2.svg');
     textures.palm_tree3 = PIXI.Texture.from('assets/entities/palm-3.svg');
+    textures.wheat1 = PIXI.Texture.from('assets/entities/wheat-1.svg');
+    textures.wheat2 = PIXI.Texture.from('assets/entities/wheat-2.svg');
+    textures.wheat3 = PIXI.Texture.from('assets/entities/wheat-3.svg');
     textures.rock = PIXI.Texture.from('assets/entities/rock.svg');
     textures.rockSmall = PIXI.Texture.from('assets/entities/rock-small.svg');
     textures.rockMedium = PIXI.Texture.from('assets/entities/rock-medium.svg');
@@ -2435,6 +2831,7 @@
     textures.boat = PIXI.Texture.from('assets/entities/boat.svg');
     textures.tent = PIXI.Texture.from('assets/entities/tent.svg');
     textures.campfire = PIXI.Texture.from('assets/entities/campfire.svg');
+    textures.campfire_out = PIXI.Texture.from('assets/entities/campfire-out.svg');
     textures.playerFront = PIXI.Texture.from('assets/entities/player.svg');
     textures.playerBack = PIXI.Texture.from('assets/entities/player-back.svg');
     textures.playerSide = PIXI.Texture.from('assets/entities/player-side.svg');
@@ -2445,6 +2842,9 @@
     textures.slime = PIXI.Texture.from('assets/entities/slime.svg');
     textures.boar = PIXI.Texture.from('assets/entities/boar.svg');
     textures.rabbit = PIXI.Texture.from('assets/entities/rabbit.svg');
+    textures.wolf = PIXI.Texture.from('assets/entities/wolf.svg');
+    textures.goblin_archer = PIXI.Texture.from('assets/entities/goblin-archer.svg');
+    textures.forest_troll = PIXI.Texture.from('assets/entities/forest-troll.svg');
     textures.arrow = PIXI.Texture.from('assets/entities/arrow.svg');
 
     return textures;
@@ -2862,6 +3262,7 @@
         case 'welcome': {
           playerId = msg.player.id;
           playerState = msg.player;
+          localAdmin = Boolean(msg.player.admin);
           localInBoat = Boolean(msg.player.in_boat);
           localPrediction = { x: msg.player.x, y: msg.player.y };
           pendingInputs.length = 0;
@@ -2872,6 +3273,14 @@
           tileSize = msg.world.tile_size;
           chunkSize = msg.world.chunk_size;
           worldSeed = msg.world.seed;
+          if (msg.config) {
+            PLAYER_SPEED = msg.config.player_speed;
+            MAX_HEARTS = msg.config.max_hp;
+            CHUNK_KEEP_RADIUS = msg.config.chunk_keep_radius;
+            MAX_NAME_CHARS = msg.config.max_name_chars;
+            GATHER_RANGE = msg.config.gather_range;
+            INTERACT_RANGE = msg.config.interact_range;
+          }
           ensureTextures();
           addCampfireAndTent(msg.world);
           refreshNameStyle();
@@ -2898,6 +3307,19 @@
         }
         case 'chunk_data': {
           drawChunk(msg);
+          if (msg.weather) {
+            chunkWeather.set(chunkKey(msg.chunk_x, msg.chunk_y), msg.weather);
+          }
+          break;
+        }
+        case 'weather': {
+          (msg.chunks || []).forEach((entry) => {
+            chunkWeather.set(chunkKey(entry.chunk_x, entry.chunk_y), entry.weather);
+          });
+          break;
+        }
+        case 'campfire': {
+          setCampfireLit(Boolean(msg.lit));
           break;
         }
         case 'state': {
@@ -2912,6 +3334,7 @@
           syncMonsters(msg.monsters || [], false);
           syncProjectiles(msg.projectiles || [], false);
           syncBoats(msg.boats || [], false);
+          syncGroundItems(msg.ground_items || []);
           break;
         }
         case 'entities_remove': {
@@ -2919,6 +3342,12 @@
           removeMonsters(msg.monsters || []);
           removeProjectiles(msg.projectiles || []);
           removeBoats(msg.boats || []);
+          removeGroundItems(msg.ground_items || []);
+          break;
+        }
+        case 'projectile_impact': {
+          removeProjectiles([msg.id]);
+          showProjectileImpact(msg.x, msg.y);
           break;
         }
         case 'resource_update': {
@@ -2950,14 +3379,36 @@
           renderInventory(msg.items);
           break;
         }
+        case 'npcs': {
+          replaceNpcs(msg.npcs);
+          break;
+        }
         case 'chat': {
-          addChat(`${msg.from}: ${msg.text}`);
+          addChat(formatChat(msg), msg.emote ? `${msg.channel} emote` : msg.channel);
           break;
         }
         case 'system': {
           addChat(msg.text, 'system');
           break;
         }
+        case 'party_invite': {
+          addChat(t('partyInvite').replace('{name}', msg.from), 'system');
+          break;
+        }
+        case 'party': {
+          partyState = msg.party || null;
+          renderParty();
+          break;
+        }
+        case 'guild_invite': {
+          addChat(t('guildInvite').replace('{name}', msg.from).replace('{guild}', msg.guild), 'system');
+          break;
+        }
+        case 'guild': {
+          guildState = msg.guild || null;
+          renderGuild();
+          break;
+        }
         case 'typing': {
           setTypingIndicator(msg.id, msg.typing);
           break;
@@ -2966,6 +3417,24 @@
           showDialog(msg.title, msg.text);
           break;
         }
+        case 'kicked': {
+          disconnectNotice = msg.reason
+            ? t('kickedReason').replace('{reason}', msg.reason)
+            : t('statusKicked');
+          break;
+        }
+        case 'banned': {
+          disconnectNotice = banText(msg);
+          break;
+        }
+        case 'world_time': {
+          worldClock = {
+            time: msg.time,
+            dayLengthMs: Math.max(1, msg.day_length_ms),
+            receivedAt: performance.now(),
+          };
+          break;
+        }
         default:
           break;
       }
@@ -2973,7 +3442,11 @@
 
     ws.addEventListener('close', () => {
       wsOpen = false;
-      setStatusText(t('statusDisconnected'));
+      partyState = null;
+      renderParty();
+      guildState = null;
+      renderGuild();
+      setStatusText(disconnectNotice ?? t('statusDisconnected'));
       renderStatusHearts(0);
       setStatusCoords('');
       localPrediction = null;
@@ -2987,10 +3460,45 @@
         startupEl.classList.add('is-connecting');
         setStartupStatus(t('statusDisconnected'), t('startupReconnectSubtitle'));
       }
-      setTimeout(connect, 1000);
+      if (disconnectNotice !== null) {
+        showDisconnectNotice(disconnectNotice);
+        return;
+      }
+      // A refused upgrade carries no reason, so ask the session endpoint whether we
+      // were banned before trying again.
+      fetch('/api/session')
+        .then((response) => (response.status === 403 ? response.json() : null))
+        .then((ban) => {
+          if (ban) {
+            showDisconnectNotice(banText(ban));
+          } else {
+            setTimeout(connect, 1000);
+          }
+        })
+        .catch(() => setTimeout(connect, 1000));
     });
   }
 
+  function banText(ban) {
+    const parts = [t('banned')];
+    if (ban.reason) {
+      parts.push(t('bannedReason').replace('{reason}', ban.reason));
+    }
+    if (ban.expires_ms) {
+      parts.push(t('bannedUntil').replace('{time}', new Date(ban.expires_ms).toLocaleString(language)));
+    }
+    return parts.join(' ');
+  }
+
+  function showDisconnectNotice(text) {
+    disconnectNotice = text;
+    setStatusText(text);
+    addChat(text, 'system');
+    if (!startupEntered && startupEl) {
+      setStartupStatus(text, '');
+    }
+  }
+
   if (helpEl && window.matchMedia('(pointer: coarse)').matches) {
     helpEl.textContent = t('helpTouch');
   }
@@ -3071,8 +3579,60 @@
     joystickEl.addEventListener('pointercancel', releaseJoystick);
   }
 
+  function updateAimFromEvent(event) {
+    if (event.pointerType !== 'mouse') return;
+    aimState.x = (event.clientX - world.x) / tileSize;
+    aimState.y = (event.clientY - world.y) / tileSize;
+    aimState.valid = true;
+  }
+
+  function currentAim() {
+    if (!aimState.valid || !playerId) return null;
+    const self = localPrediction || playerEntities.get(playerId);
+    if (!self) return null;
+    let targetId = null;
+    let nearest = AIM_TARGET_RADIUS;
+    for (const [id, entity] of monsterEntities.entries()) {
+      const dist = Math.hypot(entity.x - aimState.x, entity.y - aimState.y);
+      if (dist < nearest) {
+        nearest = dist;
+        targetId = id;
+      }
+    }
+    let targetPlayer = null;
+    if (targetId === null) {
+      nearest = AIM_TARGET_RADIUS;
+      for (const [id, entity] of playerEntities.entries()) {
+        if (id === playerId) continue;
+        const dist = Math.hypot(entity.x - aimState.x, entity.y - aimState.y);
+        if (dist < nearest) {
+          nearest = dist;
+          targetPlayer = id;
+        }
+      }
+    }
+    return {
+      x: aimState.x - self.x,
+      y: aimState.y - self.y,
+      targetId,
+      targetPlayer,
+    };
+  }
+
   if (app.canvas) {
+    app.canvas.addEventListener('contextmenu', (event) => {
+      event.preventDefault();
+    });
+
     app.canvas.addEventListener('pointerdown', (event) => {
+      updateAimFromEvent(event);
+      if (event.pointerType === 'mouse' && event.button === 2) {
+        if (!isTextInputFocused()) {
+          touchState.attackPulse = true;
+          playSfxForAction('attack');
+        }
+        return;
+      }
       if (event.pointerType === 'mouse' && event.button !== 0) return;
       if (event.pointerType === 'mouse') {
         setInputMethod('keyboard');
@@ -3089,6 +3649,7 @@
     });
 
     app.canvas.addEventListener('pointermove', (event) => {
+      updateAimFromEvent(event);
       if (!pointerMoveState.active || event.pointerId !== pointerMoveState.pointerId) return;
       updatePointerMoveFromEvent(event);
     });
@@ -3104,6 +3665,9 @@
     app.canvas.addEventListener('pointerup', releasePointerMove);
     app.canvas.addEventListener('pointercancel', releasePointerMove);
     app.canvas.addEventListener('pointerleave', releasePointerMove);
+    app.canvas.addEventListener('pointerleave', () => {
+      aimState.valid = false;
+    });
   }
 
   actionButtons.forEach((button) => {
@@ -3195,7 +3759,15 @@
     if (event.key === 'Enter') {
       const text = chatInput.value.trim();
       if (text) {
-        sendMessage({ type: 'chat', text });
+        const guild = guildCommand(text);
+        const command = guild ?? partyCommand(text);
+        if (command) {
+          sendMessage(command);
+        } else if (command === false) {
+          addChat(t(guild === false ? 'guildCommandUsage' : 'partyCommandUsage'), 'system');
+        } else {
+          sendMessage({ type: 'chat', text, channel: chatChannel ? chatChannel.value : 'local' });
+        }
       }
       chatInput.value = '';
       setLocalTyping(false);
@@ -3268,6 +3840,7 @@
       pendingInputs.shift();
     }
     const expected = localPrediction || (playerId ? playerEntities.get(playerId) : null);
+    const aim = attack ? currentAim() : null;
     sendMessage({
       type: 'input',
       dir_x: dirX,
@@ -3278,6 +3851,10 @@
       seq,
       expected_x: expected ? expected.x : null,
       expected_y: expected ? expected.y : null,
+      aim_x: aim ? aim.x : null,
+      aim_y: aim ? aim.y : null,
+      target_id: aim ? aim.targetId : null,
+      target_player: aim ? aim.targetPlayer : null,
     });
     touchState.attackPulse = false;
     touchState.gatherPulse = false;
@@ -3291,6 +3868,8 @@
     updateActionAvailability();
     updateTypingIndicators(now);
     updateCamera();
+    updateNightOverlay(now);
+    updateWeatherOverlay();
     if (now - lastStatusUpdate > 200 && playerId) {
       const playerEntity = playerEntities.get(playerId);
       if (playerEntity) {
@@ -3328,6 +3907,12 @@
     });
   }
 
+  if (pvpToggle) {
+    pvpToggle.addEventListener('click', () => {
+      sendMessage({ type: 'pvp', enabled: !localPvp });
+    });
+  }
+
   applyLocale();
   if (inventoryPanel) {
     inventoryPanel.classList.add('collapsed');
@@ -3354,13 +3939,18 @@
   app.view.addEventListener('mouseleave', clearBuildPreview);
 
   fetch('/api/session')
-    .then((response) => {
+    .then(async (response) => {
+      if (response.status === 403) {
+        showDisconnectNotice(banText(await response.json()));
+        return null;
+      }
       if (!response.ok) {
         throw new Error('Session failed');
       }
       return response.json();
     })
     .then((session) => {
+      if (session === null) return;
       if (session?.name) {
         syncLocalName(session.name);
       } else if (nameInput) {
@@ -1,52 +1,63 @@
 use axum::{
-    extract::{ws::{Message, WebSocket}, State, WebSocketUpgrade},
-    http::HeaderMap,
-    response::IntoResponse,
-    routing::get,
+    extract::{
+        ws::{Message, WebSocket},
+        ConnectInfo, Path, Query, Request, State, WebSocketUpgrade,
+    },
+    http::{HeaderMap, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
     Json, Router,
 };
 use axum_extra::extract::cookie::{Cookie, CookieJar, SameSite};
+use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
 use futures_util::{SinkExt, StreamExt};
-use mongodb::{bson::doc, options::ReplaceOptions, Client, Collection};
+use mongodb::{bson::doc, options::ReplaceOptions, Client, Collection, Database};
 use noise::{NoiseFn, Perlin};
 use rand::{seq::SliceRandom, Rng};
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::{
-    collections::{HashMap, HashSet},
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet, VecDeque},
+    future::Future,
     hash::{Hash, Hasher},
-    net::SocketAddr,
-    sync::Arc,
-    time::{Duration, SystemTime, UNIX_EPOCH},
+    io::{Read, Write},
+    net::{IpAddr, SocketAddr},
+    sync::{
+        atomic::{AtomicBool, AtomicI64, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 use tokio::sync::{mpsc, RwLock};
 use tower_http::services::ServeDir;
 use tracing::{info, warn};
 use uuid::Uuid;
 
-const TICK_MS: u64 = 100;
-const PLAYER_SPEED: f32 = 3.4;
-const MONSTER_AGGRO_RANGE: f32 = 5.0;
-const MONSTER_ATTACK_RANGE: f32 = 0.8;
-const GATHER_RANGE: f32 = 1.1;
-const INTERACT_RANGE: f32 = 1.2;
 const ENTITY_FOOT_OFFSET_X: f32 = 0.5;
 const ENTITY_FOOT_OFFSET_Y: f32 = 0.9;
-const SAVE_INTERVAL_MS: i64 = 5_000;
-const MAX_HP: i32 = 10;
-const PLAYER_REGEN_INTERVAL_MS: i64 = 5_000;
-const FISH_MIN_CLICKS: i32 = 1;
-const FISH_MAX_CLICKS: i32 = 10;
-const TYPING_TIMEOUT_MS: i64 = 2500;
-const CHUNK_KEEP_RADIUS: i32 = 3;
-const ENTITY_VISIBILITY_RADIUS: i32 = 2;
-const EXPECTED_POS_CORRECTION_RANGE: f32 = 1.5;
-const EXPECTED_POS_CORRECTION_WEIGHT: f32 = 0.35;
-const CHUNK_TTL_MS: i64 = 60_000;
-const MAX_NAME_CHARS: usize = 20;
 const PLAYER_COORD_VERSION: i32 = 1;
-const TREE_GROW_INTERVAL_MS: i64 = 30_000;
-const TREE_MAX_SIZE: i32 = 3;
-const ROCK_MAX_SIZE: i32 = 3;
+const HEALTH_TICK_STALE_MS: i64 = 5_000;
+const READY_STORE_TIMEOUT_MS: u64 = 2_000;
+const TICK_BUCKETS_SECS: [f64; 9] = [0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5];
+const DATA_WATCH_INTERVAL_MS: u64 = 2_000;
+const MAP_MAX_PIXELS: u32 = 8192;
+const MAP_TILE_PX: u32 = 256;
+const MAP_MAX_ZOOM: u32 = 6;
+const MAP_STRUCTURE_COLOR: [u8; 3] = [0x5b, 0x3a, 0x29];
+const MAP_ROAD_COLOR: [u8; 3] = [0x9a, 0x8f, 0x80];
+const MAP_BRIDGE_COLOR: [u8; 3] = [0x8b, 0x5a, 0x2b];
+const MAP_BOAT_COLOR: [u8; 3] = [0xff, 0xff, 0xff];
+const MAP_NPC_COLOR: [u8; 3] = [0xff, 0xd7, 0x00];
+const MAP_PLAYER_COLOR: [u8; 3] = [0xe0, 0x30, 0x30];
+const GAME_DATA_FILES: [&str; 6] = [
+    "data/items.json",
+    "data/resources.json",
+    "data/monsters.json",
+    "data/quests.json",
+    "data/npcs.json",
+    "data/chat_filter.json",
+];
 
 const TILE_GRASS: u8 = 0;
 const TILE_WATER: u8 = 1;
@@ -88,9 +99,30 @@ fn language_from_headers(headers: &HeaderMap) -> Language {
 struct AppState {
     state: Arc<RwLock<GameState>>,
     store: GameStore,
-    data: Arc<GameData>,
+    /// Swapped wholesale by `reload_game_data`; read through `AppState::data`.
+    data: Arc<std::sync::RwLock<Arc<GameData>>>,
     world: WorldConfig,
     noise: Arc<WorldNoise>,
+    config: Arc<GameplayConfig>,
+    admin_token: Option<String>,
+    metrics_token: Option<String>,
+    metrics: Arc<Metrics>,
+    health: Arc<Health>,
+    map_cache: Arc<MapCache>,
+    /// Map tiles are served for world tiles within this distance of the origin on each axis.
+    map_radius: i32,
+    trusted_proxies: Arc<Vec<TrustedProxy>>,
+}
+
+impl AppState {
+    /// The current game data. Callers keep the returned snapshot for the whole request or
+    /// tick so one reload can't mix old and new definitions.
+    fn data(&self) -> Arc<GameData> {
+        self.data
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
 }
 
 type AppResult<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
@@ -101,22 +133,77 @@ async fn main() -> AppResult<()> {
         .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
         .init();
 
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        None | Some("serve") => serve().await,
+        Some("validate-data") | Some("--check-data") => check_data(),
+        Some("render-map") => render_map_command(&args[1..]),
+        Some("export-world") => export_world_command(&args[1..]).await,
+        Some("import-world") => import_world_command(&args[1..]).await,
+        Some("inspect-player") => inspect_player_command(&args[1..]).await,
+        Some("help") | Some("--help") | Some("-h") => {
+            println!("{}", CLI_USAGE);
+            Ok(())
+        }
+        Some(other) => {
+            eprintln!("unknown command {:?}\n\n{}", other, CLI_USAGE);
+            std::process::exit(2);
+        }
+    }
+}
+
+const CLI_USAGE: &str = "usage: onlinerpg [command]
+
+commands:
+  serve                               run the game server (default)
+  validate-data                       check the config and data files, then exit
+  render-map [--x X] [--y Y] [--width W] [--height H] [--scale S] [--out FILE]
+                                      write a PNG of the tiles around X,Y (default: spawn)
+  export-world FILE                   write structures, boats and players to FILE
+  import-world FILE [--force]         restore an export; replaces structures and boats
+  inspect-player ID                   print a stored player and any active ban";
+
+async fn serve() -> AppResult<()> {
+    let server_config = load_server_config()?;
+    let config = Arc::new(server_config.gameplay.clone());
     let world: WorldConfig = load_json("data/world.json")?;
-    let data = Arc::new(load_game_data()?);
+    let data = match load_game_data() {
+        Ok(data) => Arc::new(data),
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    };
     let noise = Arc::new(WorldNoise::new(world.seed));
-    let mongo_uri = std::env::var("MONGODB_URI")
-        .unwrap_or_else(|_| "mongodb://localhost:27017".to_string());
-    let store = GameStore::new(&mongo_uri).await?;
-    let state = Arc::new(RwLock::new(GameState::new()));
-    {
+    let metrics = Arc::new(Metrics::default());
+    let store = GameStore::new(&server_config.mongodb_uri, metrics.clone()).await?;
+    let state = Arc::new(RwLock::new(GameState::new(config.clone())));
+    let restored = {
         let structures = store.load_structures().await?;
         let boats = store.load_boats().await?;
+        let plantings = store.load_plantings().await?;
+        let guilds = store.load_guilds().await?;
+        let mut restored = RestoreCounts {
+            structures: 0,
+            boats: boats.len(),
+            plantings: plantings.len(),
+            guilds: guilds.len(),
+        };
         let mut state_guard = state.write().await;
+        for guild in guilds {
+            for member in &guild.members {
+                state_guard
+                    .guild_of
+                    .insert(member.id.clone(), guild.id.clone());
+            }
+            state_guard.guilds.insert(guild.id.clone(), guild);
+        }
         let mut max_id = 0;
         for doc in structures {
             if doc.kind == "boat" {
                 continue;
             }
+            restored.structures += 1;
             let id = doc.id as u64;
             max_id = max_id.max(id);
             let tile = StructureTile {
@@ -125,6 +212,7 @@ async fn main() -> AppResult<()> {
                 x: doc.x,
                 y: doc.y,
                 owner_id: doc.owner_id,
+                guild_id: doc.guild_id,
             };
             state_guard
                 .structure_tiles
@@ -144,77 +232,200 @@ async fn main() -> AppResult<()> {
                 },
             );
         }
+        for doc in plantings {
+            let id = doc.id as u64;
+            max_id = max_id.max(id);
+            let coord = chunk_coord_for_tile(doc.x, doc.y, world.chunk_size);
+            let hp = data.resources.get(&doc.kind).map(|def| def.hp).unwrap_or(1);
+            state_guard
+                .planted
+                .entry(coord)
+                .or_default()
+                .push(ResourceNode {
+                    id,
+                    kind: doc.kind,
+                    x: doc.x,
+                    y: doc.y,
+                    hp,
+                    respawn_at_ms: None,
+                    size: doc.size,
+                    next_growth_ms: doc.next_growth_ms,
+                    owner_id: Some(doc.owner_id),
+                    seed_id: doc.seed_id,
+                });
+        }
         if max_id >= state_guard.next_entity_id {
             state_guard.next_entity_id = max_id + 1;
         }
         if max_id >= state_guard.next_structure_id {
             state_guard.next_structure_id = max_id + 1;
         }
-    }
+        restored
+    };
 
     let app_state = AppState {
         state: state.clone(),
         store,
-        data,
+        data: Arc::new(std::sync::RwLock::new(data)),
         world: world.clone(),
         noise,
+        config,
+        admin_token: server_config.admin_token.clone(),
+        metrics_token: server_config.metrics_token.clone(),
+        metrics,
+        health: Arc::new(Health {
+            started_ms: now_millis(),
+            last_tick_ms: AtomicI64::new(0),
+            restored,
+        }),
+        map_cache: Arc::new(MapCache::open(
+            &server_config.map_cache_dir,
+            world.seed,
+            server_config.map_cache_max_mb * 1024 * 1024,
+        )),
+        map_radius: server_config.map_radius,
+        trusted_proxies: Arc::new(server_config.trusted_proxies()?),
     };
 
     spawn_game_loop(app_state.clone());
+    if server_config.watch_data {
+        spawn_data_watcher(app_state.clone());
+    }
 
-    let app = Router::new()
+    let mut app = Router::new()
         .route("/api/session", get(session_handler))
         .route("/ws", get(ws_handler))
+        .route("/metrics", get(metrics_handler))
+        .route("/healthz", get(healthz_handler))
+        .route("/readyz", get(readyz_handler))
+        .route("/api/map/:z/:x/:y", get(map_tile_handler));
+    if app_state.admin_token.is_some() {
+        app = app.nest("/admin", admin_router(app_state.clone()));
+    } else {
+        info!("ADMIN_TOKEN not set, admin API disabled");
+    }
+    let app = app
         .nest_service("/", ServeDir::new("public").append_index_html_on_directories(true))
         .with_state(app_state);
 
-    let port = std::env::var("PORT")
-        .ok()
-        .and_then(|value| value.parse::<u16>().ok())
-        .unwrap_or(3000);
+    let port = server_config.port;
     let addr = SocketAddr::from(([0, 0, 0, 0], port));
     info!("listening on {}", addr);
     println!("open in browser: http://localhost:{}", port);
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
 
     Ok(())
 }
 
 async fn session_handler(
     State(app_state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     jar: CookieJar,
-) -> (CookieJar, Json<SessionResponse>) {
+) -> Response {
     let (sid, jar, _is_new) = ensure_session_cookie(jar);
+    if let Some(rejection) = ban_rejection(
+        &app_state,
+        &sid,
+        &client_ip(&headers, peer, &app_state.trusted_proxies),
+    )
+    .await
+    {
+        return rejection;
+    }
     let doc = app_state
         .store
-        .load_or_create_player(&sid, &app_state.world, &app_state.noise)
+        .load_or_create_player(&sid, &app_state.world, &app_state.noise, &app_state.config)
         .await
         .unwrap_or_else(|err| {
             warn!("session load failed: {}", err);
-            default_player_doc(&sid, &app_state.world, &app_state.noise)
+            default_player_doc(&sid, &app_state.world, &app_state.noise, &app_state.config)
         });
 
     let response = SessionResponse {
         session_id: sid,
         name: doc.name,
     };
-    (jar, Json(response))
+    (jar, Json(response)).into_response()
 }
 
 async fn ws_handler(
     State(app_state): State<AppState>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
     ws: WebSocketUpgrade,
     headers: HeaderMap,
-) -> impl IntoResponse {
+) -> Response {
     let sid = extract_session_id(&headers).unwrap_or_else(|| Uuid::new_v4().to_string());
+    let ip = client_ip(&headers, peer, &app_state.trusted_proxies);
+    if let Some(rejection) = ban_rejection(&app_state, &sid, &ip).await {
+        return rejection;
+    }
     let language = language_from_headers(&headers);
-    ws.on_upgrade(move |socket| handle_socket(socket, app_state, sid, language))
+    ws.on_upgrade(move |socket| handle_socket(socket, app_state, sid, language, ip))
+}
+
+/// A 403 explaining the ban when the session or address is banned. Lookup failures
+/// let the player in rather than locking everyone out while the database is down.
+async fn ban_rejection(app_state: &AppState, sid: &str, ip: &str) -> Option<Response> {
+    match app_state
+        .store
+        .find_active_ban(sid, Some(ip), now_millis())
+        .await
+    {
+        Ok(Some(ban)) => Some(
+            (
+                StatusCode::FORBIDDEN,
+                Json(BanRejection {
+                    error: "banned",
+                    reason: ban.reason,
+                    expires_ms: ban.expires_ms,
+                }),
+            )
+                .into_response(),
+        ),
+        Ok(None) => None,
+        Err(err) => {
+            warn!("ban lookup failed: {}", err);
+            None
+        }
+    }
+}
+
+/// The peer address, or the `X-Real-IP` set by the reverse proxy when the peer is one of
+/// the configured `trusted_proxies`. With none configured the header is ignored.
+fn client_ip(headers: &HeaderMap, peer: SocketAddr, trusted_proxies: &[TrustedProxy]) -> String {
+    let peer_ip = peer.ip();
+    if trusted_proxies.iter().any(|proxy| proxy.contains(peer_ip)) {
+        let real_ip = headers
+            .get("x-real-ip")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<IpAddr>().ok());
+        if let Some(real_ip) = real_ip {
+            return real_ip.to_string();
+        }
+    }
+    peer_ip.to_string()
 }
 
-async fn handle_socket(socket: WebSocket, app_state: AppState, sid: String, language: Language) {
+async fn handle_socket(
+    socket: WebSocket,
+    app_state: AppState,
+    sid: String,
+    language: Language,
+    ip: String,
+) {
     let (mut socket_sender, mut socket_receiver) = socket.split();
     let (tx, mut rx) = mpsc::unbounded_channel::<ServerMessage>();
+    let queue_depth = Arc::new(AtomicUsize::new(0));
+    let tx = ClientSender {
+        tx,
+        depth: queue_depth.clone(),
+    };
 
     {
         let mut state = app_state.state.write().await;
@@ -223,10 +434,16 @@ async fn handle_socket(socket: WebSocket, app_state: AppState, sid: String, lang
             .visibility
             .insert(sid.clone(), VisibilityState::default());
         state.locales.insert(sid.clone(), language);
+        state.client_ips.insert(sid.clone(), ip);
     }
 
-    let send_task = tokio::spawn(async move {
+    let mut send_task = tokio::spawn(async move {
         while let Some(msg) = rx.recv().await {
+            queue_depth.fetch_sub(1, Ordering::Relaxed);
+            let kicked = matches!(
+                msg,
+                ServerMessage::Kicked { .. } | ServerMessage::Banned { .. }
+            );
             let payload = match serde_json::to_string(&msg) {
                 Ok(text) => text,
                 Err(err) => {
@@ -237,16 +454,20 @@ async fn handle_socket(socket: WebSocket, app_state: AppState, sid: String, lang
             if socket_sender.send(Message::Text(payload)).await.is_err() {
                 break;
             }
+            if kicked {
+                let _ = socket_sender.send(Message::Close(None)).await;
+                break;
+            }
         }
     });
 
     let mut doc = app_state
         .store
-        .load_or_create_player(&sid, &app_state.world, &app_state.noise)
+        .load_or_create_player(&sid, &app_state.world, &app_state.noise, &app_state.config)
         .await
         .unwrap_or_else(|err| {
             warn!("player load failed: {}", err);
-            default_player_doc(&sid, &app_state.world, &app_state.noise)
+            default_player_doc(&sid, &app_state.world, &app_state.noise, &app_state.config)
         });
 
     let (doc_x, doc_y) = player_position_from_doc(&doc);
@@ -301,37 +522,56 @@ async fn handle_socket(socket: WebSocket, app_state: AppState, sid: String, lang
         ServerMessage::Welcome {
             player: player.self_view(),
             world: app_state.world.clone(),
-            npcs: app_state
-                .data
-                .npcs
-                .iter()
-                .map(|npc| NpcPublic {
-                    id: npc.id.clone(),
-                    name: localize_npc_name(npc, lang),
-                    x: npc.x,
-                    y: npc.y,
-                    dialog: localize_npc_dialog(npc, lang),
-                })
-                .collect(),
-            inventory_items: build_inventory_items(&player.inventory, app_state.data.as_ref(), lang),
+            config: Box::new(app_state.config.as_ref().clone()),
+            npcs: npcs_public(&app_state.data(), lang),
+            inventory_items: build_inventory_items(&player.inventory, &app_state.data(), lang),
         }
     };
 
     send_to_player(&app_state.state, &sid, welcome_msg).await;
+    {
+        let mut state = app_state.state.write().await;
+        refresh_guild_member(&mut state, &sid);
+        if let Some(guild_id) = player_guild(&state, &sid) {
+            send_guild_update(&state, &guild_id);
+        }
+    }
+    let (world_time_msg, campfire_lit) = {
+        let state = app_state.state.read().await;
+        (
+            world_time_message(&state, &app_state.world, now_millis()),
+            state.campfire_lit,
+        )
+    };
+    send_to_player(&app_state.state, &sid, world_time_msg).await;
+    send_to_player(
+        &app_state.state,
+        &sid,
+        ServerMessage::Campfire { lit: campfire_lit },
+    )
+    .await;
     let inventory_msg = ServerMessage::Inventory {
-        items: build_inventory_items(&doc.inventory, app_state.data.as_ref(), language),
+        items: build_inventory_items(&doc.inventory, &app_state.data(), language),
     };
     send_to_player(&app_state.state, &sid, inventory_msg).await;
 
-    while let Some(Ok(msg)) = socket_receiver.next().await {
-        match msg {
-            Message::Text(text) => {
-                if let Ok(client_msg) = serde_json::from_str::<ClientMessage>(&text) {
-                    handle_client_message(&app_state, &sid, client_msg).await;
+    // The send task only finishes early when the player was kicked.
+    let mut send_done = false;
+    loop {
+        tokio::select! {
+            incoming = socket_receiver.next() => match incoming {
+                Some(Ok(Message::Text(text))) => {
+                    if let Ok(client_msg) = serde_json::from_str::<ClientMessage>(&text) {
+                        handle_client_message(&app_state, &sid, client_msg).await;
+                    }
                 }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+            _ = &mut send_task => {
+                send_done = true;
+                break;
             }
-            Message::Close(_) => break,
-            _ => {}
         }
     }
 
@@ -340,7 +580,11 @@ async fn handle_socket(socket: WebSocket, app_state: AppState, sid: String, lang
         state.clients.remove(&sid);
         state.inputs.remove(&sid);
         state.visibility.remove(&sid);
+        remove_from_party(&mut state, &sid);
+        state.party_invites.remove(&sid);
+        state.guild_invites.remove(&sid);
         state.locales.remove(&sid);
+        state.client_ips.remove(&sid);
         if let Some(player) = state.players.remove(&sid) {
             let doc = player.to_doc();
             let store = app_state.store.clone();
@@ -348,6 +592,9 @@ async fn handle_socket(socket: WebSocket, app_state: AppState, sid: String, lang
                 let _ = store.save_player(&doc).await;
             });
         }
+        if let Some(guild_id) = player_guild(&state, &sid) {
+            send_guild_update(&state, &guild_id);
+        }
         if state.typing.remove(&sid).is_some() {
             broadcast_message_inline(
                 &state,
@@ -359,2393 +606,6701 @@ async fn handle_socket(socket: WebSocket, app_state: AppState, sid: String, lang
         }
     }
 
-    let _ = send_task.await;
+    if !send_done {
+        let _ = send_task.await;
+    }
 }
 
-async fn handle_client_message(app_state: &AppState, sid: &str, msg: ClientMessage) {
-    match msg {
-        ClientMessage::Input {
-            dir_x,
-            dir_y,
-            attack,
-            gather,
-            interact,
-            seq,
-            expected_x,
-            expected_y,
-        } => {
-            let mut state = app_state.state.write().await;
-            let entry = state.inputs.entry(sid.to_string()).or_insert(InputState::default());
-            entry.dir_x = dir_x.clamp(-1.0, 1.0);
-            entry.dir_y = dir_y.clamp(-1.0, 1.0);
-            entry.attack = attack;
-            entry.gather = gather;
-            entry.interact = interact;
-            entry.seq = seq;
-            entry.expected_x = expected_x;
-            entry.expected_y = expected_y;
-        }
-        ClientMessage::Chat { text } => {
-            let trimmed = text.trim();
-            if trimmed.is_empty() {
-                return;
-            }
-            let trimmed = trimmed.chars().take(160).collect::<String>();
-            let (sender_name, was_typing) = {
-                let mut state = app_state.state.write().await;
-                let name = state
-                    .players
-                    .get(sid)
-                    .map(|player| player.name.clone())
-                    .unwrap_or_else(|| "Wanderer".to_string());
-                let was_typing = state.typing.remove(sid).is_some();
-                (name, was_typing)
-            };
-            broadcast_message(
-                &app_state.state,
-                ServerMessage::Chat {
-                    from: sender_name,
-                    text: trimmed,
-                },
-            )
-            .await;
-            if was_typing {
-                broadcast_message(
-                    &app_state.state,
-                    ServerMessage::Typing {
-                        id: sid.to_string(),
-                        typing: false,
-                    },
-                )
-                .await;
-            }
+fn admin_router(app_state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/players", get(admin_list_players))
+        .route(
+            "/players/:id",
+            get(admin_get_player).patch(admin_patch_player),
+        )
+        .route("/players/:id/teleport", post(admin_teleport_player))
+        .route("/players/:id/items", post(admin_give_items))
+        .route("/players/:id/kick", post(admin_kick_player))
+        .route(
+            "/players/:id/ban",
+            post(admin_ban_player).delete(admin_unban_player),
+        )
+        .route("/bans", get(admin_list_bans).post(admin_create_ban))
+        .route("/bans/:ban_id", delete(admin_delete_ban))
+        .route("/broadcast", post(admin_broadcast))
+        .route(
+            "/structures",
+            get(admin_list_structures).delete(admin_delete_structures),
+        )
+        .route("/data/reload", post(admin_reload_data))
+        .route_layer(middleware::from_fn_with_state(app_state, admin_auth))
+}
+
+/// Requires `Authorization: Bearer <ADMIN_TOKEN>` on every admin route.
+async fn admin_auth(State(app_state): State<AppState>, request: Request, next: Next) -> Response {
+    match app_state.admin_token.as_deref() {
+        Some(expected) if bearer_matches(request.headers(), expected) => next.run(request).await,
+        _ => (StatusCode::UNAUTHORIZED, "invalid admin token").into_response(),
+    }
+}
+
+fn bearer_matches(headers: &HeaderMap, expected: &str) -> bool {
+    headers
+        .get(axum::http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .is_some_and(|given| tokens_match(expected, given.trim()))
+}
+
+/// Alive as long as the game loop has ticked within the last few seconds.
+async fn healthz_handler(State(app_state): State<AppState>) -> Response {
+    let now_ms = now_millis();
+    let last_tick_ms = app_state.health.last_tick_ms.load(Ordering::Relaxed);
+    let tick_age_ms = now_ms - last_tick_ms.max(app_state.health.started_ms);
+    let alive = tick_age_ms <= HEALTH_TICK_STALE_MS;
+    let body = serde_json::json!({
+        "status": if alive { "ok" } else { "stalled" },
+        "uptime_ms": now_ms - app_state.health.started_ms,
+        "last_tick_ms": last_tick_ms,
+        "tick_age_ms": tick_age_ms,
+    });
+    let status = if alive {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(body)).into_response()
+}
+
+/// Ready when the store answers a ping and the game data and saved world are loaded.
+async fn readyz_handler(State(app_state): State<AppState>) -> Response {
+    let started = Instant::now();
+    let ping = tokio::time::timeout(
+        Duration::from_millis(READY_STORE_TIMEOUT_MS),
+        app_state.store.ping(),
+    )
+    .await;
+    let store = match ping {
+        Ok(Ok(())) => serde_json::json!({
+            "status": "ok",
+            "latency_ms": started.elapsed().as_millis() as u64,
+        }),
+        Ok(Err(err)) => serde_json::json!({ "status": "error", "error": err.to_string() }),
+        Err(_) => serde_json::json!({ "status": "error", "error": "timed out" }),
+    };
+    let data = app_state.data();
+    let data_ok = !data.items.is_empty() && !data.resources.is_empty() && !data.monsters.is_empty();
+    let game_data = serde_json::json!({
+        "status": if data_ok { "ok" } else { "error" },
+        "items": data.items.len(),
+        "resources": data.resources.len(),
+        "monsters": data.monsters.len(),
+        "npcs": data.npcs.len(),
+        "quests": data.quests_by_npc.len(),
+    });
+    let world = serde_json::json!({
+        "status": "ok",
+        "restored": app_state.health.restored,
+    });
+    let ready = store["status"] == "ok" && data_ok;
+    let body = serde_json::json!({
+        "status": if ready { "ready" } else { "not_ready" },
+        "components": {
+            "store": store,
+            "game_data": game_data,
+            "world": world,
+        },
+    });
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(body)).into_response()
+}
+
+/// Prometheus text format. Open unless `METRICS_TOKEN` is set.
+async fn metrics_handler(State(app_state): State<AppState>, headers: HeaderMap) -> Response {
+    if let Some(expected) = app_state.metrics_token.as_deref() {
+        if !bearer_matches(&headers, expected) {
+            return (StatusCode::UNAUTHORIZED, "invalid metrics token").into_response();
         }
-        ClientMessage::SetName { name } => {
-            let normalized = match normalize_player_name(&name) {
-                Some(normalized) => normalized,
-                None => return,
-            };
-            let doc = {
-                let mut state = app_state.state.write().await;
-                let player = match state.players.get_mut(sid) {
-                    Some(player) => player,
-                    None => return,
-                };
-                if player.name == normalized {
-                    None
-                } else {
-                    player.name = normalized.clone();
-                    Some(player.to_doc())
-                }
-            };
-            if let Some(doc) = doc {
-                let store = app_state.store.clone();
-                tokio::spawn(async move {
-                    let _ = store.save_player(&doc).await;
-                });
-            }
+    }
+    let body = {
+        let state = app_state.state.read().await;
+        app_state.metrics.render(&state)
+    };
+    (
+        [(
+            axum::http::header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        body,
+    )
+        .into_response()
+}
+
+#[derive(Deserialize)]
+struct MapTileQuery {
+    /// Comma separated: `structures`, `boats`, `npcs`, `players` (admins only).
+    #[serde(default)]
+    overlays: String,
+}
+
+/// One 256x256 PNG tile of the world map. At zoom `z` a pixel covers `2^z` world tiles,
+/// so tile `x,y` spans world tiles `x * 256 * 2^z` onwards. Terrain is cached on disk;
+/// overlays are drawn fresh on every request.
+async fn map_tile_handler(
+    State(app_state): State<AppState>,
+    Path((z, x, y)): Path<(u32, i32, i32)>,
+    Query(query): Query<MapTileQuery>,
+    headers: HeaderMap,
+) -> Response {
+    if z > MAP_MAX_ZOOM {
+        return (StatusCode::NOT_FOUND, "zoom out of range").into_response();
+    }
+    let step = 1i32 << z;
+    let span = MAP_TILE_PX as i32 * step;
+    let limit = (app_state.map_radius + span - 1) / span;
+    if x < -limit || x >= limit || y < -limit || y >= limit {
+        return (StatusCode::NOT_FOUND, "tile out of range").into_response();
+    }
+    let overlays: Vec<&str> = query
+        .overlays
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .collect();
+    if let Some(name) = overlays
+        .iter()
+        .find(|name| !["structures", "boats", "npcs", "players"].contains(*name))
+    {
+        return (StatusCode::BAD_REQUEST, format!("unknown overlay {}", name)).into_response();
+    }
+    if overlays.contains(&"players") && !map_viewer_is_admin(&app_state, &headers).await {
+        return (StatusCode::FORBIDDEN, "the players overlay is for admins").into_response();
+    }
+
+    let png = match map_terrain_tile(&app_state, z, x, y).await {
+        Ok(png) => png,
+        Err(err) => {
+            warn!("map tile {}/{}/{} failed: {}", z, x, y, err);
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
         }
-        ClientMessage::UseItem { id } => {
-            let heal_amount = match app_state.data.items.get(&id).and_then(|def| def.heal) {
-                Some(amount) if amount > 0 => amount,
-                _ => return,
-            };
-            let mut state = app_state.state.write().await;
-            let lang = player_language(&state, sid);
-            let item_name = localize_item_name(app_state.data.as_ref(), &id, lang);
-            let (items, player_id, message) = {
-                let player = match state.players.get_mut(sid) {
-                    Some(player) => player,
-                    None => return,
-                };
-                if player.hp >= MAX_HP {
-                    return;
-                }
-                if !consume_item(&mut player.inventory, &id, 1) {
-                    return;
-                }
-                let hp_before = player.hp;
-                player.hp = (player.hp + heal_amount).min(MAX_HP);
-                player.last_inventory_hash = inventory_hash(&player.inventory);
-                let items =
-                    build_inventory_items(&player.inventory, app_state.data.as_ref(), lang);
-                let player_id = player.id.clone();
-                let message = if id == "apple" {
-                    message_eat_apple(lang)
+    };
+    if overlays.is_empty() {
+        return png_response(png, "public, max-age=86400");
+    }
+    let mut pixels = match decode_png(&png) {
+        Some((_, _, pixels)) => pixels,
+        None => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+
+    let x0 = x * MAP_TILE_PX as i32 * step;
+    let y0 = y * MAP_TILE_PX as i32 * step;
+    let mut marks: Vec<(f32, f32, i32, [u8; 3])> = Vec::new();
+    {
+        let state = app_state.state.read().await;
+        if overlays.contains(&"structures") {
+            marks.extend(state.structure_tiles.values().map(|tile| {
+                let color = if tile.kind.starts_with("bridge_") {
+                    MAP_BRIDGE_COLOR
+                } else if tile.kind == "road" || tile.kind == "path" {
+                    MAP_ROAD_COLOR
                 } else {
-                    message_eat_item(&item_name, player.hp > hp_before, lang)
+                    MAP_STRUCTURE_COLOR
                 };
-                (items, player_id, message)
-            };
-            if let Some(sender) = state.clients.get(sid) {
-                let _ = sender.send(ServerMessage::Inventory { items });
-            }
-            send_system_message(&mut state, &player_id, message);
-        }
-        ClientMessage::Build { kind, x, y } => {
-            handle_build_request(app_state, sid, kind, x, y).await;
-        }
-        ClientMessage::Demolish { x, y } => {
-            handle_demolish_request(app_state, sid, x, y).await;
-        }
-        ClientMessage::Typing { typing } => {
-            let now_ms = now_millis();
-            let mut state = app_state.state.write().await;
-            let mut should_broadcast = false;
-            if typing {
-                let was_typing = state.typing.contains_key(sid);
-                state.typing.insert(sid.to_string(), now_ms);
-                if !was_typing {
-                    should_broadcast = true;
-                }
-            } else if state.typing.remove(sid).is_some() {
-                should_broadcast = true;
-            }
-            if should_broadcast {
-                broadcast_message_inline(
-                    &state,
-                    ServerMessage::Typing {
-                        id: sid.to_string(),
-                        typing,
-                    },
-                );
-            }
-        }
-        ClientMessage::Locale { language } => {
-            let mut state = app_state.state.write().await;
-            let lang = language_from_tag(&language);
-            state.locales.insert(sid.to_string(), lang);
-            if let Some(player) = state.players.get(sid) {
-                let items =
-                    build_inventory_items(&player.inventory, app_state.data.as_ref(), lang);
-                if let Some(sender) = state.clients.get(sid) {
-                    let _ = sender.send(ServerMessage::Inventory { items });
-                }
-            }
+                (tile.x as f32, tile.y as f32, 0, color)
+            }));
+        }
+        if overlays.contains(&"boats") {
+            marks.extend(
+                state
+                    .boats
+                    .values()
+                    .map(|boat| (boat.x, boat.y, 1, MAP_BOAT_COLOR)),
+            );
         }
-        ClientMessage::ChunkRequest { chunks } => {
-            handle_chunk_request(app_state, sid, chunks).await;
+        if overlays.contains(&"players") {
+            marks.extend(
+                state
+                    .players
+                    .values()
+                    .map(|player| (player.x, player.y, 1, MAP_PLAYER_COLOR)),
+            );
         }
-        ClientMessage::Ping => {}
     }
+    if overlays.contains(&"npcs") {
+        marks.extend(
+            app_state
+                .data()
+                .npcs
+                .iter()
+                .map(|npc| (npc.x, npc.y, 1, MAP_NPC_COLOR)),
+        );
+    }
+    for (wx, wy, radius, color) in marks {
+        let px = ((wx - x0 as f32) / step as f32).floor() as i32;
+        let py = ((wy - y0 as f32) / step as f32).floor() as i32;
+        draw_map_mark(&mut pixels, px, py, radius, color);
+    }
+    png_response(encode_png(MAP_TILE_PX, MAP_TILE_PX, &pixels), "no-cache")
 }
 
-async fn handle_chunk_request(app_state: &AppState, sid: &str, chunks: Vec<ChunkCoord>) {
-    let mut state = app_state.state.write().await;
-    let sender = match state.clients.get(sid) {
-        Some(sender) => sender.clone(),
-        None => return,
-    };
-    let now_ms = now_millis();
-
-    for coord in chunks {
-        state.chunk_last_access.insert(coord, now_ms);
-        if !state.spawned_chunks.contains(&coord) {
-            spawn_monsters_for_chunk(
-                &mut state,
-                app_state.world.seed,
-                coord,
-                &app_state.world,
-                &app_state.noise,
-                &app_state.data,
-            );
-            state.spawned_chunks.insert(coord);
+/// The admin token, or the session of an online player with the admin role.
+async fn map_viewer_is_admin(app_state: &AppState, headers: &HeaderMap) -> bool {
+    if let Some(expected) = app_state.admin_token.as_deref() {
+        if bearer_matches(headers, expected) {
+            return true;
+        }
+    }
+    match extract_session_id(headers) {
+        Some(sid) => {
+            let state = app_state.state.read().await;
+            state
+                .players
+                .get(&sid)
+                .is_some_and(|player| player.role == PlayerRole::Admin)
         }
+        None => false,
+    }
+}
 
-        if !state.resources.contains_key(&coord) {
-            let generated = generate_resources(
-                app_state.world.seed,
-                now_ms,
-                coord,
-                &app_state.world,
-                &app_state.noise,
-                &app_state.data,
-                &state.structure_tiles,
-            );
-            state.resources.insert(coord, generated);
+/// The terrain PNG for a map tile, from the disk cache or rendered (and cached) now.
+async fn map_terrain_tile(app_state: &AppState, z: u32, x: i32, y: i32) -> AppResult<Vec<u8>> {
+    let path = app_state.map_cache.tile_path(z, x, y);
+    if let Some(path) = &path {
+        if let Ok(png) = tokio::fs::read(path).await {
+            return Ok(png);
         }
+    }
 
-        let tiles = generate_tiles(coord, &app_state.world, &app_state.noise);
-        let visible_resources = match state.resources.get(&coord) {
-            Some(resources) => resources
-                .iter()
-                .filter(|res| res.hp > 0)
-                .map(ResourceNodePublic::from)
-                .collect(),
-            None => Vec::new(),
-        };
-        let structures = state
-            .structure_tiles
-            .values()
-            .filter(|structure| {
-                chunk_coord_for_tile(structure.x, structure.y, app_state.world.chunk_size) == coord
-            })
-            .map(StructurePublic::from)
-            .collect();
+    let noise = app_state.noise.clone();
+    let step = 1i32 << z;
+    let (x0, y0) = (x * MAP_TILE_PX as i32 * step, y * MAP_TILE_PX as i32 * step);
+    let png = tokio::task::spawn_blocking(move || {
+        let pixels = render_tiles(&noise, x0, y0, MAP_TILE_PX, MAP_TILE_PX, step, 1);
+        encode_png(MAP_TILE_PX, MAP_TILE_PX, &pixels)
+    })
+    .await?;
 
-        let _ = sender.send(ServerMessage::ChunkData {
-            chunk_x: coord.x,
-            chunk_y: coord.y,
-            tiles,
-            resources: visible_resources,
-            structures,
-        });
+    if let Some(path) = path {
+        let tmp = path.with_extension(format!("{}.tmp", Uuid::new_v4()));
+        let written = async {
+            if let Some(dir) = path.parent() {
+                tokio::fs::create_dir_all(dir).await?;
+            }
+            tokio::fs::write(&tmp, &png).await?;
+            // A hard link never replaces an existing file, so when two requests render the
+            // same tile only the first one adds its bytes to the cache size.
+            let linked = tokio::fs::hard_link(&tmp, &path).await;
+            let _ = tokio::fs::remove_file(&tmp).await;
+            linked
+        };
+        match written.await {
+            Ok(()) => app_state.map_cache.record_write(png.len() as u64),
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {}
+            Err(err) => warn!("map cache write {} failed: {}", path.display(), err),
+        }
     }
+    Ok(png)
 }
 
-async fn handle_build_request(app_state: &AppState, sid: &str, kind: String, x: i32, y: i32) {
-    let mut state = app_state.state.write().await;
-    let (player_id, inventory_snapshot, player_pos) = match state.players.get(sid) {
-        Some(player) => (player.id.clone(), player.inventory.clone(), (player.x, player.y)),
-        None => return,
-    };
+/// Terrain tiles on disk under `<dir>/<seed>/<z>/<x>_<y>.png`. Once they take more than
+/// `max_bytes`, files are deleted down to 90% of the limit: tiles of other seeds first,
+/// then the current seed's, oldest first within each group.
+struct MapCache {
+    dir: Option<std::path::PathBuf>,
+    seed: u64,
+    max_bytes: u64,
+    bytes: AtomicU64,
+    evicting: Arc<AtomicBool>,
+}
+
+impl MapCache {
+    /// An empty `dir` disables the cache.
+    fn open(dir: &str, seed: u64, max_bytes: u64) -> Self {
+        let dir = (!dir.is_empty()).then(|| std::path::PathBuf::from(dir));
+        let bytes = dir
+            .as_deref()
+            .map(|dir| map_cache_files(dir).iter().map(|file| file.1).sum())
+            .unwrap_or(0);
+        Self {
+            dir,
+            seed,
+            max_bytes,
+            bytes: AtomicU64::new(bytes),
+            evicting: Arc::new(AtomicBool::new(false)),
+        }
+    }
 
-    let mut tiles = Vec::new();
-    let mut placements: Vec<(TileCoord, String)> = Vec::new();
-    let mut cost = Vec::new();
-    let mut require_shovel = false;
-    let mut requires_land = true;
-    let mut require_water = false;
-    let mut require_near_water = false;
-    let mut is_boat = false;
-    let lang = player_language(&state, &player_id);
+    fn tile_path(&self, z: u32, x: i32, y: i32) -> Option<std::path::PathBuf> {
+        self.dir.as_ref().map(|dir| {
+            dir.join(self.seed.to_string())
+                .join(z.to_string())
+                .join(format!("{}_{}.png", x, y))
+        })
+    }
 
-    let build_kind = kind.as_str();
-    if matches!(
-        build_kind,
-        "craft_basic_axe" | "craft_basic_pick" | "craft_arrows"
-    ) {
-        let (crafted_id, crafted_count, cost) = match build_kind {
-            "craft_basic_axe" => (
-                "basic_axe",
-                1,
-                vec![ItemStack::new("wood", 4)],
-            ),
-            "craft_basic_pick" => (
-                "basic_pick",
-                1,
-                vec![ItemStack::new("wood", 4)],
-            ),
-            "craft_arrows" => (
-                "arrow",
-                6,
-                vec![ItemStack::new("wood", 1), ItemStack::new("stone", 1)],
-            ),
-            _ => return,
-        };
-        if !has_items(&inventory_snapshot, &cost) {
-            send_system_message(
-                &mut state,
-                &player_id,
-                message_not_enough_materials(lang).to_string(),
-            );
+    fn record_write(self: &Arc<Self>, len: u64) {
+        let total = self.bytes.fetch_add(len, Ordering::Relaxed) + len;
+        if total <= self.max_bytes || self.evicting.swap(true, Ordering::AcqRel) {
             return;
         }
-        let items = {
-            let player = match state.players.get_mut(sid) {
-                Some(player) => player,
-                None => return,
-            };
-            if !remove_items(&mut player.inventory, &cost) {
-                send_system_message(
-                    &mut state,
-                    &player_id,
-                    message_not_enough_materials(lang).to_string(),
-                );
-                return;
-            }
-            add_item(&mut player.inventory, crafted_id, crafted_count);
-            player.last_inventory_hash = inventory_hash(&player.inventory);
-            build_inventory_items(&player.inventory, app_state.data.as_ref(), lang)
+        let cache = self.clone();
+        tokio::task::spawn_blocking(move || {
+            cache.evict();
+            cache.evicting.store(false, Ordering::Release);
+        });
+    }
+
+    fn evict(&self) {
+        let dir = match &self.dir {
+            Some(dir) => dir,
+            None => return,
         };
-        if let Some(sender) = state.clients.get(sid) {
-            let _ = sender.send(ServerMessage::Inventory { items });
+        let current = dir.join(self.seed.to_string());
+        let mut files = map_cache_files(dir);
+        files.sort_by_key(|file| (file.0.starts_with(&current), file.2));
+        let mut total: u64 = files.iter().map(|file| file.1).sum();
+        let target = self.max_bytes / 10 * 9;
+        let mut removed = 0;
+        for (path, len, _) in files {
+            if total <= target {
+                break;
+            }
+            if std::fs::remove_file(&path).is_ok() {
+                total = total.saturating_sub(len);
+                removed += 1;
+            }
         }
-        send_system_message(
-            &mut state,
-            &player_id,
-            message_build_success(lang, build_kind),
-        );
-        return;
+        self.bytes.store(total, Ordering::Relaxed);
+        info!("map cache: evicted {} tiles, {} bytes left", removed, total);
     }
+}
 
-    match build_kind {
-        "hut_wood" => {
-            cost.push(ItemStack::new("wood", 20));
-            let width = 2;
-            let height = 2;
-            let base_y = y - (height - 1);
-            for dy in 0..height {
-                for dx in 0..width {
-                    let coord = TileCoord {
-                        x: x + dx,
-                        y: base_y + dy,
-                    };
-                    tiles.push(coord);
-                    let kind = if dy == height - 1 && dx == 0 {
-                        "hut_wood_root"
-                    } else if dy == height - 1 {
-                        "hut_wood_block"
-                    } else {
-                        "hut_wood_top"
-                    };
-                    placements.push((coord, kind.to_string()));
-                }
+/// Every cached tile as (path, size, modified time).
+fn map_cache_files(dir: &std::path::Path) -> Vec<(std::path::PathBuf, u64, SystemTime)> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(dir) = pending.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            let meta = match entry.metadata() {
+                Ok(meta) => meta,
+                Err(_) => continue,
+            };
+            if meta.is_dir() {
+                pending.push(entry.path());
+            } else if entry.path().extension().is_some_and(|ext| ext == "png") {
+                let modified = meta.modified().unwrap_or(UNIX_EPOCH);
+                files.push((entry.path(), meta.len(), modified));
             }
         }
-        "house_stone" => {
-            cost.push(ItemStack::new("stone", 50));
-            let width = 3;
-            let height = 3;
-            let base_y = y - (height - 1);
-            for dy in 0..height {
-                for dx in 0..width {
-                    let coord = TileCoord {
-                        x: x + dx,
-                        y: base_y + dy,
-                    };
-                    tiles.push(coord);
-                    let kind = if dy == height - 1 && dx == 0 {
-                        "house_stone_root"
-                    } else if dy == height - 1 {
-                        "house_stone_block"
-                    } else {
-                        "house_stone_top"
-                    };
-                    placements.push((coord, kind.to_string()));
+    }
+    files
+}
+
+fn draw_map_mark(pixels: &mut [u8], px: i32, py: i32, radius: i32, color: [u8; 3]) {
+    let size = MAP_TILE_PX as i32;
+    for y in py.saturating_sub(radius).max(0)..=py.saturating_add(radius).min(size - 1) {
+        for x in px.saturating_sub(radius).max(0)..=px.saturating_add(radius).min(size - 1) {
+            let offset = ((y * size + x) * 3) as usize;
+            pixels[offset..offset + 3].copy_from_slice(&color);
+        }
+    }
+}
+
+fn png_response(png: Vec<u8>, cache_control: &'static str) -> Response {
+    (
+        [
+            (axum::http::header::CONTENT_TYPE, "image/png"),
+            (axum::http::header::CACHE_CONTROL, cache_control),
+        ],
+        png,
+    )
+        .into_response()
+}
+
+fn tokens_match(expected: &str, given: &str) -> bool {
+    if expected.len() != given.len() {
+        return false;
+    }
+    expected
+        .bytes()
+        .zip(given.bytes())
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+        == 0
+}
+
+type AdminResult<T> = Result<T, (StatusCode, String)>;
+
+fn admin_store_error(err: Box<dyn std::error::Error + Send + Sync>) -> (StatusCode, String) {
+    warn!("admin store error: {}", err);
+    (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+}
+
+fn admin_not_found() -> (StatusCode, String) {
+    (StatusCode::NOT_FOUND, "player not found".to_string())
+}
+
+async fn admin_list_players(State(app_state): State<AppState>) -> Json<Vec<AdminPlayerSummary>> {
+    let state = app_state.state.read().await;
+    let mut players: Vec<AdminPlayerSummary> = state
+        .players
+        .values()
+        .map(|player| AdminPlayerSummary {
+            id: player.id.clone(),
+            name: player.name.clone(),
+            x: player.x,
+            y: player.y,
+            hp: player.hp,
+            in_boat: player.in_boat,
+            pvp: player.pvp,
+            guild: player.guild_tag.clone(),
+            party: state.party_of.get(&player.id).copied(),
+        })
+        .collect();
+    players.sort_by(|a, b| a.name.cmp(&b.name));
+    Json(players)
+}
+
+/// Online players are read from the game state, everyone else from the store.
+async fn admin_get_player(
+    State(app_state): State<AppState>,
+    Path(id): Path<String>,
+) -> AdminResult<Json<AdminPlayerView>> {
+    let online = {
+        let state = app_state.state.read().await;
+        state.players.get(&id).map(Player::to_doc)
+    };
+    if let Some(player) = online {
+        return Ok(Json(AdminPlayerView {
+            online: true,
+            player,
+        }));
+    }
+    match app_state.store.load_player(&id).await {
+        Ok(Some(player)) => Ok(Json(AdminPlayerView {
+            online: false,
+            player,
+        })),
+        Ok(None) => Err(admin_not_found()),
+        Err(err) => Err(admin_store_error(err)),
+    }
+}
+
+async fn admin_patch_player(
+    State(app_state): State<AppState>,
+    Path(id): Path<String>,
+    Json(patch): Json<AdminPlayerPatch>,
+) -> AdminResult<Json<AdminPlayerView>> {
+    let data = app_state.data();
+    let name = match &patch.name {
+        Some(name) => Some(
+            normalize_player_name(name, &data.chat_filter, &app_state.config)
+                .ok_or((StatusCode::BAD_REQUEST, "name not allowed".to_string()))?,
+        ),
+        None => None,
+    };
+    if let Some(inventory) = &patch.inventory {
+        if let Some(item) = inventory
+            .keys()
+            .find(|item| !data.items.contains_key(*item))
+        {
+            return Err((StatusCode::BAD_REQUEST, format!("unknown item {}", item)));
+        }
+    }
+    let hp = patch.hp.map(|hp| hp.clamp(0, app_state.config.max_hp));
+
+    let online = {
+        let mut state = app_state.state.write().await;
+        match state.players.get_mut(&id) {
+            Some(player) => {
+                if let Some(name) = &name {
+                    player.name = name.clone();
+                }
+                if let Some(hp) = hp {
+                    player.hp = hp;
+                }
+                if let Some(inventory) = &patch.inventory {
+                    player.inventory = inventory.clone();
+                }
+                if let Some(quests) = &patch.completed_quests {
+                    player.completed_quests = quests.iter().cloned().collect();
+                }
+                if let Some(role) = patch.role {
+                    player.role = role;
+                    if role != PlayerRole::Admin {
+                        player.god_mode = false;
+                    }
+                }
+                let doc = player.to_doc();
+                if name.is_some() {
+                    refresh_guild_member(&mut state, &id);
                 }
+                Some(doc)
             }
+            None => None,
         }
-        "bridge_wood" => {
-            cost.push(ItemStack::new("wood", 10));
-            requires_land = false;
-            match find_bridge_span(&app_state.noise, x, y) {
-                Some(span) => tiles = span,
-                None => {
-                    send_system_message(
-                        &mut state,
-                        &player_id,
-                        message_bridge_span_error(lang).to_string(),
-                    );
-                    return;
-                }
+    };
+    let (online, doc) = match online {
+        Some(doc) => (true, doc),
+        None => {
+            let mut doc = app_state
+                .store
+                .load_player(&id)
+                .await
+                .map_err(admin_store_error)?
+                .ok_or_else(admin_not_found)?;
+            if let Some(name) = name {
+                doc.name = name;
+            }
+            if let Some(hp) = hp {
+                doc.hp = hp;
+            }
+            if let Some(inventory) = patch.inventory {
+                doc.inventory = inventory;
+            }
+            if let Some(quests) = patch.completed_quests {
+                doc.completed_quests = quests;
             }
-        }
-        "bridge_stone" => {
-            cost.push(ItemStack::new("stone", 20));
-            requires_land = false;
-            match find_bridge_span(&app_state.noise, x, y) {
-                Some(span) => tiles = span,
-                None => {
-                    send_system_message(
-                        &mut state,
-                        &player_id,
-                        message_bridge_span_error(lang).to_string(),
-                    );
-                    return;
-                }
-            }
-        }
-        "path" => {
-            require_shovel = true;
-            let coord = TileCoord { x, y };
-            tiles.push(coord);
-            placements.push((coord, "path".to_string()));
-        }
-        "road" => {
-            require_shovel = true;
-            cost.push(ItemStack::new("stone", 2));
-            let coord = TileCoord { x, y };
-            tiles.push(coord);
-            placements.push((coord, "road".to_string()));
-        }
-        "boat" => {
-            cost.push(ItemStack::new("wood", 10));
-            requires_land = false;
-            require_water = true;
-            require_near_water = true;
-            is_boat = true;
-            let coord = TileCoord { x, y };
-            tiles.push(coord);
-        }
-        _ => {
-            send_system_message(
-                &mut state,
-                &player_id,
-                message_unknown_build(lang).to_string(),
-            );
-            return;
+            if let Some(role) = patch.role {
+                doc.role = role;
+            }
+            (false, doc)
         }
     };
+    app_state
+        .store
+        .save_player(&doc)
+        .await
+        .map_err(admin_store_error)?;
+    info!("admin edited player {}", id);
+    Ok(Json(AdminPlayerView {
+        online,
+        player: doc,
+    }))
+}
 
-    let mut structure_kind = build_kind.to_string();
-    if build_kind == "bridge_wood" || build_kind == "bridge_stone" {
-        let is_vertical = tiles
-            .first()
-            .map(|first| tiles.iter().all(|tile| tile.x == first.x))
-            .unwrap_or(false);
-        if build_kind == "bridge_wood" {
-            structure_kind = if is_vertical {
-                "bridge_wood_v".to_string()
-            } else {
-                "bridge_wood_h".to_string()
-            };
-        } else {
-            structure_kind = if is_vertical {
-                "bridge_stone_v".to_string()
-            } else {
-                "bridge_stone_h".to_string()
-            };
+async fn admin_teleport_player(
+    State(app_state): State<AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<AdminTeleportRequest>,
+) -> AdminResult<Json<AdminPlayerView>> {
+    if !request.x.is_finite() || !request.y.is_finite() {
+        return Err((StatusCode::BAD_REQUEST, "invalid position".to_string()));
+    }
+    let (tile_x, tile_y) = entity_foot_tile(request.x, request.y);
+    if tile_at(&app_state.noise, tile_x, tile_y) == TILE_WATER {
+        return Err((StatusCode::BAD_REQUEST, "target tile is water".to_string()));
+    }
+    let online = {
+        let mut state = app_state.state.write().await;
+        state.players.get_mut(&id).map(|player| {
+            player.x = request.x;
+            player.y = request.y;
+            player.in_boat = false;
+            player.boat_id = None;
+            player.fishing_clicks = 0;
+            player.fishing_target = 0;
+            player.to_doc()
+        })
+    };
+    let (online, doc) = match online {
+        Some(doc) => (true, doc),
+        None => {
+            let mut doc = app_state
+                .store
+                .load_player(&id)
+                .await
+                .map_err(admin_store_error)?
+                .ok_or_else(admin_not_found)?;
+            doc.x = request.x;
+            doc.y = request.y;
+            doc.coord_version = PLAYER_COORD_VERSION;
+            (false, doc)
         }
+    };
+    app_state
+        .store
+        .save_player(&doc)
+        .await
+        .map_err(admin_store_error)?;
+    info!(
+        "admin teleported player {} to {:.1},{:.1}",
+        id, request.x, request.y
+    );
+    Ok(Json(AdminPlayerView {
+        online,
+        player: doc,
+    }))
+}
+
+/// Gives items, or removes them when `count` is negative.
+async fn admin_give_items(
+    State(app_state): State<AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<AdminItemRequest>,
+) -> AdminResult<Json<AdminPlayerView>> {
+    let data = app_state.data();
+    if !data.items.contains_key(&request.item) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!("unknown item {}", request.item),
+        ));
     }
+    if request.count == 0 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "count must not be zero".to_string(),
+        ));
+    }
+    let online = {
+        let mut state = app_state.state.write().await;
+        state.players.get_mut(&id).map(|player| {
+            adjust_item(&mut player.inventory, &request.item, request.count);
+            player.to_doc()
+        })
+    };
+    let (online, doc) = match online {
+        Some(doc) => (true, doc),
+        None => {
+            let mut doc = app_state
+                .store
+                .load_player(&id)
+                .await
+                .map_err(admin_store_error)?
+                .ok_or_else(admin_not_found)?;
+            adjust_item(&mut doc.inventory, &request.item, request.count);
+            (false, doc)
+        }
+    };
+    app_state
+        .store
+        .save_player(&doc)
+        .await
+        .map_err(admin_store_error)?;
+    info!(
+        "admin gave {} x{} to player {}",
+        request.item, request.count, id
+    );
+    Ok(Json(AdminPlayerView {
+        online,
+        player: doc,
+    }))
+}
 
-    if require_shovel && !has_tool(&inventory_snapshot, app_state.data.as_ref(), "shovel") {
-        send_system_message(
-            &mut state,
-            &player_id,
-            message_need_shovel(lang).to_string(),
-        );
-        return;
+fn adjust_item(inventory: &mut HashMap<String, i32>, item_id: &str, count: i32) {
+    add_item(inventory, item_id, count);
+    if inventory.get(item_id).copied().unwrap_or(0) <= 0 {
+        inventory.remove(item_id);
     }
+}
 
-    if !cost.is_empty() && !has_items(&inventory_snapshot, &cost) {
-        send_system_message(
-            &mut state,
-            &player_id,
-            message_not_enough_materials(lang).to_string(),
-        );
-        return;
+/// Tells the client why it is being dropped; the socket task closes it afterwards.
+fn kick_player(state: &GameState, player_id: &str, reason: &str) -> bool {
+    match state.clients.get(player_id) {
+        Some(sender) => sender.send(ServerMessage::Kicked {
+            reason: reason.to_string(),
+        }),
+        None => false,
     }
+}
 
-    if require_near_water {
-        let (px, py) = entity_foot_tile(player_pos.0, player_pos.1);
-        if !is_adjacent_to_water(&app_state.noise, px, py) {
-            send_system_message(
-                &mut state,
-                &player_id,
-                message_need_water(lang).to_string(),
-            );
-            return;
-        }
+async fn admin_kick_player(
+    State(app_state): State<AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<AdminReasonRequest>,
+) -> AdminResult<StatusCode> {
+    let state = app_state.state.read().await;
+    if !kick_player(&state, &id, request.reason.as_deref().unwrap_or_default()) {
+        return Err((StatusCode::NOT_FOUND, "player not online".to_string()));
     }
+    info!("admin kicked player {}", id);
+    Ok(StatusCode::NO_CONTENT)
+}
 
-    for tile in &tiles {
-        if state
-            .structure_tiles
-            .contains_key(&TileCoord { x: tile.x, y: tile.y })
-        {
-            send_system_message(
-                &mut state,
-                &player_id,
-                message_spot_occupied(lang).to_string(),
-            );
-            return;
-        }
-        if is_boat
-            && state
-                .boats
-                .values()
-                .any(|boat| entity_foot_tile(boat.x, boat.y) == (tile.x, tile.y))
-        {
-            send_system_message(
-                &mut state,
-                &player_id,
-                message_spot_occupied(lang).to_string(),
-            );
-            return;
-        }
-        if resource_at_tile(&state, tile.x, tile.y) {
-            send_system_message(
-                &mut state,
-                &player_id,
-                message_clear_resource(lang).to_string(),
-            );
-            return;
-        }
-        if require_water && tile_at(&app_state.noise, tile.x, tile.y) != TILE_WATER {
-            send_system_message(
-                &mut state,
-                &player_id,
-                message_build_on_water(lang).to_string(),
-            );
-            return;
-        }
-        if requires_land && tile_at(&app_state.noise, tile.x, tile.y) == TILE_WATER {
-            send_system_message(
-                &mut state,
-                &player_id,
-                message_build_on_land(lang).to_string(),
-            );
-            return;
+/// Bans the account and, with `ban_ip`, the address it is connected from.
+async fn admin_ban_player(
+    State(app_state): State<AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<AdminBanRequest>,
+) -> AdminResult<Json<BanDoc>> {
+    let ip = if request.ban_ip {
+        let state = app_state.state.read().await;
+        match state.client_ips.get(&id) {
+            Some(ip) => Some(ip.clone()),
+            None => return Err((StatusCode::BAD_REQUEST, "player not online".to_string())),
         }
+    } else {
+        None
+    };
+    let ban = new_ban(Some(id), ip, request.reason, request.duration_ms);
+    issue_ban(&app_state, &ban).await?;
+    Ok(Json(ban))
+}
+
+async fn admin_create_ban(
+    State(app_state): State<AppState>,
+    Json(request): Json<AdminCreateBanRequest>,
+) -> AdminResult<Json<BanDoc>> {
+    let ip = match request.ip.as_deref().map(str::trim) {
+        Some(ip) => Some(
+            ip.parse::<IpAddr>()
+                .map_err(|_| (StatusCode::BAD_REQUEST, "invalid ip".to_string()))?
+                .to_string(),
+        ),
+        None => None,
+    };
+    let player_id = request.player_id.filter(|id| !id.trim().is_empty());
+    if player_id.is_none() && ip.is_none() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            "player_id or ip is required".to_string(),
+        ));
     }
+    let ban = new_ban(player_id, ip, request.reason, request.duration_ms);
+    issue_ban(&app_state, &ban).await?;
+    Ok(Json(ban))
+}
 
-    if !is_boat && placements.is_empty() {
-        for tile in &tiles {
-            placements.push((TileCoord { x: tile.x, y: tile.y }, structure_kind.clone()));
-        }
+fn new_ban(
+    player_id: Option<String>,
+    ip: Option<String>,
+    reason: Option<String>,
+    duration_ms: Option<i64>,
+) -> BanDoc {
+    let now_ms = now_millis();
+    BanDoc {
+        id: Uuid::new_v4().to_string(),
+        player_id,
+        ip,
+        reason: reason.unwrap_or_default(),
+        created_ms: now_ms,
+        expires_ms: duration_ms
+            .filter(|duration| *duration > 0)
+            .map(|duration| now_ms + duration),
     }
+}
 
-    let mut inventory_items = None;
-    if !cost.is_empty() {
-        let mut removal_failed = false;
-        let items = {
-            let player = match state.players.get_mut(sid) {
-                Some(player) => player,
-                None => return,
-            };
-            if !remove_items(&mut player.inventory, &cost) {
-                removal_failed = true;
-                Vec::new()
-            } else {
-                player.last_inventory_hash = inventory_hash(&player.inventory);
-                build_inventory_items(&player.inventory, app_state.data.as_ref(), lang)
+/// Stores the ban, then saves and disconnects every socket it covers.
+async fn issue_ban(app_state: &AppState, ban: &BanDoc) -> AdminResult<()> {
+    app_state
+        .store
+        .insert_ban(ban)
+        .await
+        .map_err(admin_store_error)?;
+    let affected: Vec<(String, Option<PlayerDoc>)> = {
+        let state = app_state.state.read().await;
+        state
+            .clients
+            .keys()
+            .filter(|id| {
+                ban.player_id.as_ref() == Some(*id)
+                    || (ban.ip.is_some() && state.client_ips.get(*id) == ban.ip.as_ref())
+            })
+            .map(|id| (id.clone(), state.players.get(id).map(Player::to_doc)))
+            .collect()
+    };
+    for (_, doc) in &affected {
+        if let Some(doc) = doc {
+            if let Err(err) = app_state.store.save_player(doc).await {
+                warn!("save before ban failed: {}", err);
             }
-        };
-        if removal_failed {
-            send_system_message(
-                &mut state,
-                &player_id,
-                message_not_enough_materials(lang).to_string(),
-            );
-            return;
         }
-        inventory_items = Some(items);
     }
-
-    if is_boat {
-        let (boat_x, boat_y) = tile_anchor_position(x, y);
-        let boat_id = state.next_id();
-        let boat = Boat {
-            id: boat_id,
-            x: boat_x,
-            y: boat_y,
-            owner_id: player_id.clone(),
-            last_saved_ms: now_millis(),
-        };
-        state.boats.insert(boat_id, boat.clone());
-
-        if let Some(items) = inventory_items {
-            if let Some(sender) = state.clients.get(sid) {
-                let _ = sender.send(ServerMessage::Inventory { items });
+    {
+        let state = app_state.state.read().await;
+        for (id, _) in &affected {
+            if let Some(sender) = state.clients.get(id) {
+                let _ = sender.send(ServerMessage::Banned {
+                    reason: ban.reason.clone(),
+                    expires_ms: ban.expires_ms,
+                });
             }
         }
+    }
+    info!(
+        "admin banned player {:?} ip {:?}, {} disconnected",
+        ban.player_id,
+        ban.ip,
+        affected.len()
+    );
+    Ok(())
+}
 
-        let boat_public = BoatPublic::from(&boat);
-        let chunk = chunk_coord_for_position(boat.x, boat.y, app_state.world.chunk_size);
-        send_to_players_in_chunk(
-            &state,
-            app_state.world.chunk_size,
-            chunk,
-            ServerMessage::EntitiesUpdate {
-                players: Vec::new(),
-                monsters: Vec::new(),
-                projectiles: Vec::new(),
-                boats: vec![boat_public],
-            },
-        );
-        send_system_message(
-            &mut state,
-            &player_id,
-            message_build_success(lang, build_kind),
-        );
+async fn admin_list_bans(State(app_state): State<AppState>) -> AdminResult<Json<Vec<BanDoc>>> {
+    let bans = app_state
+        .store
+        .load_active_bans(now_millis())
+        .await
+        .map_err(admin_store_error)?;
+    Ok(Json(bans))
+}
 
-        let store = app_state.store.clone();
-        let doc = BoatDoc {
-            id: boat_id as i64,
-            x: boat.x,
-            y: boat.y,
-            owner_id: boat.owner_id,
-        };
-        tokio::spawn(async move {
-            let _ = store.insert_boat(&doc).await;
-        });
-        return;
-    }
-
-    let structure_id = state.next_structure_id();
-    let mut new_tiles = Vec::new();
-    for (tile, kind) in placements {
-        let structure = StructureTile {
-            id: structure_id,
-            kind,
-            x: tile.x,
-            y: tile.y,
-            owner_id: player_id.clone(),
-        };
-        state
-            .structure_tiles
-            .insert(TileCoord { x: tile.x, y: tile.y }, structure.clone());
-        new_tiles.push(structure);
+async fn admin_delete_ban(
+    State(app_state): State<AppState>,
+    Path(ban_id): Path<String>,
+) -> AdminResult<StatusCode> {
+    let deleted = app_state
+        .store
+        .delete_ban(&ban_id)
+        .await
+        .map_err(admin_store_error)?;
+    if !deleted {
+        return Err((StatusCode::NOT_FOUND, "ban not found".to_string()));
     }
+    info!("admin lifted ban {}", ban_id);
+    Ok(StatusCode::NO_CONTENT)
+}
 
-    if let Some(items) = inventory_items {
-        if let Some(sender) = state.clients.get(sid) {
-            let _ = sender.send(ServerMessage::Inventory { items });
-        }
-    }
+async fn admin_unban_player(
+    State(app_state): State<AppState>,
+    Path(id): Path<String>,
+) -> AdminResult<StatusCode> {
+    app_state
+        .store
+        .delete_bans(&id)
+        .await
+        .map_err(admin_store_error)?;
+    info!("admin lifted bans for player {}", id);
+    Ok(StatusCode::NO_CONTENT)
+}
 
-    let structures_public: Vec<StructurePublic> = new_tiles.iter().map(StructurePublic::from).collect();
-    let mut chunks = HashSet::new();
-    for structure in &structures_public {
-        chunks.insert(chunk_coord_for_tile(
-            structure.x,
-            structure.y,
-            app_state.world.chunk_size,
-        ));
-    }
-    send_to_players_in_chunks(
+async fn admin_broadcast(
+    State(app_state): State<AppState>,
+    Json(request): Json<AdminBroadcastRequest>,
+) -> AdminResult<StatusCode> {
+    let text = request.text.trim();
+    if text.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "text is empty".to_string()));
+    }
+    let state = app_state.state.read().await;
+    broadcast_message_inline(
         &state,
-        app_state.world.chunk_size,
-        &chunks,
-        ServerMessage::StructureUpdate {
-            structures: structures_public.clone(),
-            state: "added".to_string(),
+        ServerMessage::System {
+            text: text.to_string(),
         },
     );
-    if let Some(sender) = state.clients.get(sid) {
-        let _ = sender.send(ServerMessage::StructureUpdate {
-            structures: structures_public.clone(),
-            state: "added".to_string(),
-        });
-    }
-    send_system_message(
-        &mut state,
-        &player_id,
-        message_build_success(lang, build_kind),
-    );
+    info!("admin broadcast: {}", text);
+    Ok(StatusCode::NO_CONTENT)
+}
 
-    let docs: Vec<StructureDoc> = new_tiles
-        .into_iter()
+async fn admin_list_structures(
+    State(app_state): State<AppState>,
+    Query(query): Query<AdminStructureQuery>,
+) -> Json<Vec<StructureDoc>> {
+    let state = app_state.state.read().await;
+    let mut structures: Vec<StructureDoc> = state
+        .structure_tiles
+        .values()
+        .filter(|tile| {
+            query
+                .owner
+                .as_ref()
+                .is_none_or(|owner| &tile.owner_id == owner)
+        })
         .map(|tile| StructureDoc {
-            id: structure_id as i64,
-            kind: tile.kind,
+            id: tile.id as i64,
+            kind: tile.kind.clone(),
             x: tile.x,
             y: tile.y,
-            owner_id: tile.owner_id,
+            owner_id: tile.owner_id.clone(),
+            guild_id: tile.guild_id.clone(),
         })
         .collect();
-    let store = app_state.store.clone();
-    tokio::spawn(async move {
-        let _ = store.insert_structures(&docs).await;
-    });
+    structures.sort_by_key(|doc| (doc.id, doc.y, doc.x));
+    Json(structures)
 }
 
-async fn handle_demolish_request(app_state: &AppState, sid: &str, x: i32, y: i32) {
-    let mut state = app_state.state.write().await;
-    let player_id = match state.players.get(sid) {
-        Some(player) => player.id.clone(),
-        None => return,
-    };
-    let lang = player_language(&state, &player_id);
-    let tile = TileCoord { x, y };
-    let structure = match state.structure_tiles.get(&tile) {
-        Some(structure) => structure.clone(),
-        None => {
-            send_system_message(&mut state, &player_id, message_nothing_to_remove(lang).to_string());
-            return;
-        }
-    };
-    if structure.owner_id != player_id {
-        send_system_message(
-            &mut state,
-            &player_id,
-            message_remove_own_only(lang).to_string(),
-        );
-        return;
-    }
-
-    let target_id = structure.id;
-    let mut removed = Vec::new();
-    state
-        .structure_tiles
-        .retain(|_, structure| {
-            if structure.id == target_id {
+async fn admin_delete_structures(
+    State(app_state): State<AppState>,
+    Query(query): Query<AdminStructureQuery>,
+) -> AdminResult<Json<AdminDeleteResponse>> {
+    let owner = query
+        .owner
+        .ok_or((StatusCode::BAD_REQUEST, "owner is required".to_string()))?;
+    let group_ids = {
+        let mut state = app_state.state.write().await;
+        let mut removed = Vec::new();
+        state.structure_tiles.retain(|_, tile| {
+            if tile.owner_id == owner {
                 removed.push(StructurePublic {
-                    id: structure.id,
-                    kind: structure.kind.clone(),
-                    x: structure.x,
-                    y: structure.y,
+                    id: tile.id,
+                    kind: tile.kind.clone(),
+                    x: tile.x,
+                    y: tile.y,
                 });
                 false
             } else {
                 true
             }
         });
-
-    if removed.is_empty() {
-        return;
-    }
-
-    let mut chunks = HashSet::new();
-    for structure in &removed {
-        chunks.insert(chunk_coord_for_tile(
-            structure.x,
-            structure.y,
-            app_state.world.chunk_size,
-        ));
-    }
-    send_to_players_in_chunks(
-        &state,
-        app_state.world.chunk_size,
-        &chunks,
-        ServerMessage::StructureUpdate {
-            structures: removed,
-            state: "removed".to_string(),
-        },
-    );
-    send_system_message(
-        &mut state,
-        &player_id,
-        message_structure_removed(lang).to_string(),
+        let chunks: HashSet<ChunkCoord> = removed
+            .iter()
+            .map(|tile| chunk_coord_for_tile(tile.x, tile.y, app_state.world.chunk_size))
+            .collect();
+        let group_ids: HashSet<u64> = removed.iter().map(|tile| tile.id).collect();
+        if !removed.is_empty() {
+            send_to_players_in_chunks(
+                &state,
+                app_state.world.chunk_size,
+                &chunks,
+                ServerMessage::StructureUpdate {
+                    structures: removed,
+                    state: "removed".to_string(),
+                },
+            );
+        }
+        group_ids
+    };
+    for id in &group_ids {
+        app_state
+            .store
+            .delete_structure_group(*id as i64)
+            .await
+            .map_err(admin_store_error)?;
+    }
+    info!(
+        "admin removed {} structures owned by {}",
+        group_ids.len(),
+        owner
     );
-
-    let store = app_state.store.clone();
-    tokio::spawn(async move {
-        let _ = store.delete_structure_group(target_id as i64).await;
-    });
-}
-
-fn chunk_coord_for_position(x: f32, y: f32, chunk_size: i32) -> ChunkCoord {
-    let size = chunk_size as f32;
-    ChunkCoord {
-        x: (x / size).floor() as i32,
-        y: (y / size).floor() as i32,
-    }
-}
-
-fn chunk_coord_for_tile(x: i32, y: i32, chunk_size: i32) -> ChunkCoord {
-    let size = chunk_size as f32;
-    ChunkCoord {
-        x: (x as f32 / size).floor() as i32,
-        y: (y as f32 / size).floor() as i32,
-    }
+    Ok(Json(AdminDeleteResponse {
+        removed: group_ids.len(),
+    }))
 }
 
-fn chunk_in_radius(center: ChunkCoord, coord: ChunkCoord, radius: i32) -> bool {
-    (coord.x - center.x).abs() <= radius && (coord.y - center.y).abs() <= radius
+async fn admin_reload_data(
+    State(app_state): State<AppState>,
+) -> AdminResult<Json<DataReloadReport>> {
+    reload_game_data(&app_state)
+        .await
+        .map(Json)
+        .map_err(|err| (StatusCode::UNPROCESSABLE_ENTITY, err.to_string()))
 }
 
-fn send_to_players_in_chunk(
-    state: &GameState,
-    chunk_size: i32,
-    coord: ChunkCoord,
-    msg: ServerMessage,
-) {
-    for (player_id, sender) in state.clients.iter() {
-        if let Some(player) = state.players.get(player_id) {
-            let center = chunk_coord_for_position(player.x, player.y, chunk_size);
-            if chunk_in_radius(center, coord, ENTITY_VISIBILITY_RADIUS) {
-                let _ = sender.send(msg.clone());
-            }
+async fn handle_client_message(app_state: &AppState, sid: &str, msg: ClientMessage) {
+    let data = app_state.data();
+    match msg {
+        ClientMessage::Input {
+            dir_x,
+            dir_y,
+            attack,
+            gather,
+            interact,
+            seq,
+            expected_x,
+            expected_y,
+            aim_x,
+            aim_y,
+            target_id,
+            target_player,
+        } => {
+            let mut state = app_state.state.write().await;
+            let entry = state.inputs.entry(sid.to_string()).or_insert(InputState::default());
+            entry.dir_x = dir_x.clamp(-1.0, 1.0);
+            entry.dir_y = dir_y.clamp(-1.0, 1.0);
+            entry.attack = attack;
+            entry.gather = gather;
+            entry.interact = interact;
+            entry.seq = seq;
+            entry.expected_x = expected_x;
+            entry.expected_y = expected_y;
+            entry.aim = match (aim_x, aim_y) {
+                (Some(x), Some(y)) if x.is_finite() && y.is_finite() && x * x + y * y > 0.0001 => {
+                    Some((x, y))
+                }
+                _ => None,
+            };
+            entry.target_id = target_id;
+            entry.target_player = target_player;
         }
-    }
-}
-
-fn send_to_players_in_chunks(
-    state: &GameState,
-    chunk_size: i32,
-    chunks: &HashSet<ChunkCoord>,
-    msg: ServerMessage,
-) {
-    for (player_id, sender) in state.clients.iter() {
-        if let Some(player) = state.players.get(player_id) {
-            let center = chunk_coord_for_position(player.x, player.y, chunk_size);
-            if chunks
-                .iter()
-                .any(|coord| chunk_in_radius(center, *coord, ENTITY_VISIBILITY_RADIUS))
-            {
-                let _ = sender.send(msg.clone());
+        ClientMessage::Chat { text, channel } => {
+            let trimmed = text.trim();
+            if trimmed.is_empty() {
+                return;
             }
-        }
-    }
-}
-
-fn collect_active_chunks(state: &GameState, chunk_size: i32) -> HashSet<ChunkCoord> {
-    let mut keep = HashSet::new();
-    for player in state.players.values() {
-        let center = chunk_coord_for_position(player.x, player.y, chunk_size);
-        for dx in -CHUNK_KEEP_RADIUS..=CHUNK_KEEP_RADIUS {
-            for dy in -CHUNK_KEEP_RADIUS..=CHUNK_KEEP_RADIUS {
-                keep.insert(ChunkCoord {
-                    x: center.x + dx,
-                    y: center.y + dy,
-                });
+            let trimmed = trimmed.chars().take(160).collect::<String>();
+            let was_typing = {
+                let mut state = app_state.state.write().await;
+                if !handle_gm_command(app_state, &mut state, sid, &trimmed) {
+                    handle_chat(
+                        &mut state,
+                        sid,
+                        &trimmed,
+                        channel,
+                        app_state.world.chunk_size,
+                        &data.chat_filter,
+                    );
+                }
+                state.typing.remove(sid).is_some()
+            };
+            if was_typing {
+                broadcast_message(
+                    &app_state.state,
+                    ServerMessage::Typing {
+                        id: sid.to_string(),
+                        typing: false,
+                    },
+                )
+                .await;
             }
         }
-    }
-    keep
-}
-
-fn prune_chunks(state: &mut GameState, now_ms: i64, chunk_size: i32) {
-    let keep = collect_active_chunks(state, chunk_size);
-    for coord in &keep {
-        state.chunk_last_access.insert(*coord, now_ms);
-    }
-
-    let mut expired = Vec::new();
-    for (coord, last_access) in state.chunk_last_access.iter() {
-        if keep.contains(coord) {
-            continue;
+        ClientMessage::SetName { name } => {
+            let normalized =
+                match normalize_player_name(&name, &data.chat_filter, &app_state.config) {
+                    Some(normalized) => normalized,
+                    None => {
+                        if !name.trim().is_empty() {
+                            let mut state = app_state.state.write().await;
+                            let lang = player_language(&state, sid);
+                            send_system_message(
+                                &mut state,
+                                sid,
+                                message_name_not_allowed(lang).to_string(),
+                            );
+                        }
+                        return;
+                    }
+                };
+            let doc = {
+                let mut state = app_state.state.write().await;
+                let player = match state.players.get_mut(sid) {
+                    Some(player) => player,
+                    None => return,
+                };
+                if player.name == normalized {
+                    None
+                } else {
+                    player.name = normalized.clone();
+                    let doc = player.to_doc();
+                    refresh_guild_member(&mut state, sid);
+                    Some(doc)
+                }
+            };
+            if let Some(doc) = doc {
+                let store = app_state.store.clone();
+                tokio::spawn(async move {
+                    let _ = store.save_player(&doc).await;
+                });
+            }
         }
-        if now_ms - *last_access > CHUNK_TTL_MS {
-            expired.push(*coord);
+        ClientMessage::UseItem { id } => {
+            let heal_amount = match data.items.get(&id).and_then(|def| def.heal) {
+                Some(amount) if amount > 0 => amount,
+                _ => return,
+            };
+            let mut state = app_state.state.write().await;
+            let lang = player_language(&state, sid);
+            let item_name = localize_item_name(data.as_ref(), &id, lang);
+            let (items, player_id, message) = {
+                let player = match state.players.get_mut(sid) {
+                    Some(player) => player,
+                    None => return,
+                };
+                if player.hp >= app_state.config.max_hp {
+                    return;
+                }
+                if !consume_item(&mut player.inventory, &id, 1) {
+                    return;
+                }
+                let hp_before = player.hp;
+                player.hp = (player.hp + heal_amount).min(app_state.config.max_hp);
+                player.last_inventory_hash = inventory_hash(&player.inventory);
+                let items = build_inventory_items(&player.inventory, data.as_ref(), lang);
+                let player_id = player.id.clone();
+                let message = if id == "apple" {
+                    message_eat_apple(lang)
+                } else {
+                    message_eat_item(&item_name, player.hp > hp_before, lang)
+                };
+                (items, player_id, message)
+            };
+            if let Some(sender) = state.clients.get(sid) {
+                let _ = sender.send(ServerMessage::Inventory { items });
+            }
+            send_system_message(&mut state, &player_id, message);
         }
-    }
-
-    if expired.is_empty() {
-        return;
-    }
-
-    let expired_set: HashSet<ChunkCoord> = expired.iter().copied().collect();
-    for coord in &expired {
-        state.chunk_last_access.remove(coord);
-        state.resources.remove(coord);
-        state.spawned_chunks.remove(coord);
-    }
-
-    state.monsters.retain(|_, monster| {
-        !expired_set.contains(&chunk_coord_for_position(monster.x, monster.y, chunk_size))
-    });
-    state.projectiles.retain(|_, projectile| {
-        !expired_set.contains(&chunk_coord_for_position(projectile.x, projectile.y, chunk_size))
-    });
-}
-
-fn spawn_game_loop(app_state: AppState) {
-    tokio::spawn(async move {
-        let mut interval = tokio::time::interval(Duration::from_millis(TICK_MS));
-        loop {
-            interval.tick().await;
-            let now = now_millis();
-            if let Err(err) = game_tick(&app_state, now).await {
-                warn!("game tick failed: {}", err);
+        ClientMessage::Build { kind, x, y } => {
+            handle_build_request(app_state, sid, kind, x, y).await;
+        }
+        ClientMessage::Demolish { x, y } => {
+            handle_demolish_request(app_state, sid, x, y).await;
+        }
+        ClientMessage::Plant { id, x, y } => {
+            handle_plant_request(app_state, sid, id, x, y).await;
+        }
+        ClientMessage::Pvp { enabled } => {
+            let mut state = app_state.state.write().await;
+            handle_pvp_toggle(&mut state, sid, enabled);
+        }
+        ClientMessage::PartyInvite { name } => {
+            let mut state = app_state.state.write().await;
+            handle_party_invite(&mut state, sid, &name);
+        }
+        ClientMessage::PartyAccept => {
+            let mut state = app_state.state.write().await;
+            handle_party_accept(&mut state, sid);
+        }
+        ClientMessage::PartyLeave => {
+            let mut state = app_state.state.write().await;
+            if state.party_of.contains_key(sid) {
+                remove_from_party(&mut state, sid);
+                let lang = player_language(&state, sid);
+                send_system_message(&mut state, sid, message_party_left(lang).to_string());
             }
         }
-    });
-}
-
-async fn game_tick(app_state: &AppState, now_ms: i64) -> AppResult<()> {
-    let mut to_save = Vec::new();
-    let mut boats_to_save = Vec::new();
-    {
-        let mut state = app_state.state.write().await;
-        let dt = TICK_MS as f32 / 1000.0;
-
-        let player_ids: Vec<String> = state.players.keys().cloned().collect();
-        for id in player_ids {
-            let input = state.inputs.get(&id).cloned().unwrap_or_default();
-            if let Some(mut player) = state.players.remove(&id) {
-                let prev_inventory_hash = player.last_inventory_hash;
-                update_player_movement(
-                    &mut player,
-                    input,
-                    &state.structure_tiles,
-                    &app_state.noise,
-                    dt,
-                );
-                if player.in_boat {
-                    sync_boat_position(&mut player, &mut state, &app_state.noise);
+        ClientMessage::PartyKick { name } => {
+            let mut state = app_state.state.write().await;
+            handle_party_kick(&mut state, sid, &name);
+        }
+        ClientMessage::PartyLoot { mode } => {
+            let mut state = app_state.state.write().await;
+            handle_party_loot(&mut state, sid, mode);
+        }
+        ClientMessage::GuildCreate { name, tag } => {
+            let mut state = app_state.state.write().await;
+            handle_guild_create(&mut state, sid, &name, &tag);
+        }
+        ClientMessage::GuildInvite { name } => {
+            let mut state = app_state.state.write().await;
+            handle_guild_invite(&mut state, sid, &name);
+        }
+        ClientMessage::GuildAccept => {
+            let mut state = app_state.state.write().await;
+            handle_guild_accept(&mut state, sid);
+        }
+        ClientMessage::GuildLeave => {
+            let mut state = app_state.state.write().await;
+            if state.guild_of.contains_key(sid) {
+                remove_from_guild(&mut state, sid);
+                let lang = player_language(&state, sid);
+                send_system_message(&mut state, sid, message_guild_left(lang).to_string());
+            }
+        }
+        ClientMessage::GuildKick { name } => {
+            let mut state = app_state.state.write().await;
+            handle_guild_kick(&mut state, sid, &name);
+        }
+        ClientMessage::GuildRank { name, rank } => {
+            let mut state = app_state.state.write().await;
+            handle_guild_rank(&mut state, sid, &name, rank);
+        }
+        ClientMessage::GuildPermission {
+            rank,
+            permission,
+            allowed,
+        } => {
+            let mut state = app_state.state.write().await;
+            handle_guild_permission(&mut state, sid, rank, permission, allowed);
+        }
+        ClientMessage::GuildClaim { x, y } => {
+            let mut state = app_state.state.write().await;
+            handle_guild_claim(&mut state, sid, x, y);
+        }
+        ClientMessage::Typing { typing } => {
+            let now_ms = now_millis();
+            let mut state = app_state.state.write().await;
+            let mut should_broadcast = false;
+            if typing {
+                let was_typing = state.typing.contains_key(sid);
+                state.typing.insert(sid.to_string(), now_ms);
+                if !was_typing {
+                    should_broadcast = true;
                 }
-                handle_player_actions(
-                    &mut player,
-                    input,
-                    now_ms,
-                    &mut state,
-                    &app_state.world,
-                    &app_state.noise,
-                    &app_state.data,
+            } else if state.typing.remove(sid).is_some() {
+                should_broadcast = true;
+            }
+            if should_broadcast {
+                broadcast_message_inline(
+                    &state,
+                    ServerMessage::Typing {
+                        id: sid.to_string(),
+                        typing,
+                    },
                 );
-                apply_player_regen(&mut player, now_ms);
-                let next_inventory_hash = inventory_hash(&player.inventory);
-                if next_inventory_hash != prev_inventory_hash {
-                    player.last_inventory_hash = next_inventory_hash;
-                    if let Some(sender) = state.clients.get(&id) {
-                        let lang = player_language(&state, &id);
-                        let items =
-                            build_inventory_items(&player.inventory, app_state.data.as_ref(), lang);
-                        let _ = sender.send(ServerMessage::Inventory { items });
-                    }
+            }
+        }
+        ClientMessage::Locale { language } => {
+            let mut state = app_state.state.write().await;
+            let lang = language_from_tag(&language);
+            state.locales.insert(sid.to_string(), lang);
+            if let Some(player) = state.players.get(sid) {
+                let items = build_inventory_items(&player.inventory, data.as_ref(), lang);
+                if let Some(sender) = state.clients.get(sid) {
+                    let _ = sender.send(ServerMessage::Inventory { items });
                 }
-                state.players.insert(id, player);
             }
         }
+        ClientMessage::ChunkRequest { chunks } => {
+            handle_chunk_request(app_state, sid, chunks).await;
+        }
+        ClientMessage::Ping => {}
+    }
+}
 
-        update_monsters(
-            &mut state,
-            now_ms,
-            dt,
-            &app_state.noise,
-            &app_state.data,
-        );
-        update_projectiles(&mut state, now_ms, dt, &app_state.data);
-        update_resources(&mut state, now_ms, &app_state.data, app_state.world.chunk_size);
-        prune_chunks(&mut state, now_ms, app_state.world.chunk_size);
+async fn handle_chunk_request(app_state: &AppState, sid: &str, chunks: Vec<ChunkCoord>) {
+    let data = app_state.data();
+    let mut state = app_state.state.write().await;
+    let sender = match state.clients.get(sid) {
+        Some(sender) => sender.clone(),
+        None => return,
+    };
+    let now_ms = now_millis();
 
-        let mut expired_typing = Vec::new();
-        for (id, last) in state.typing.iter() {
-            if now_ms - *last > TYPING_TIMEOUT_MS {
-                expired_typing.push(id.clone());
-            }
-        }
-        for id in expired_typing {
-            state.typing.remove(&id);
-            broadcast_message_inline(
-                &state,
-                ServerMessage::Typing {
-                    id,
-                    typing: false,
-                },
+    for coord in chunks {
+        state.chunk_last_access.insert(coord, now_ms);
+        if !state.spawned_chunks.contains(&coord) {
+            let night = state.night;
+            spawn_monsters_for_chunk(
+                &mut state,
+                app_state.world.seed,
+                coord,
+                &app_state.world,
+                &app_state.noise,
+                &data,
+                night,
             );
+            state.spawned_chunks.insert(coord);
         }
 
-        let chunk_size = app_state.world.chunk_size;
-        let mut players_by_chunk: HashMap<ChunkCoord, Vec<PlayerPublic>> = HashMap::new();
-        let mut monsters_by_chunk: HashMap<ChunkCoord, Vec<MonsterPublic>> = HashMap::new();
-        let mut projectiles_by_chunk: HashMap<ChunkCoord, Vec<ProjectilePublic>> = HashMap::new();
-        let mut boats_by_chunk: HashMap<ChunkCoord, Vec<BoatPublic>> = HashMap::new();
-
-        for player in state.players.values() {
-            let coord = chunk_coord_for_position(player.x, player.y, chunk_size);
-            players_by_chunk
-                .entry(coord)
-                .or_default()
-                .push(PlayerPublic::from(player));
-        }
-        for monster in state.monsters.values() {
-            let coord = chunk_coord_for_position(monster.x, monster.y, chunk_size);
-            monsters_by_chunk
-                .entry(coord)
-                .or_default()
-                .push(MonsterPublic::from(monster));
-        }
-        for projectile in state.projectiles.values() {
-            let coord = chunk_coord_for_position(projectile.x, projectile.y, chunk_size);
-            projectiles_by_chunk
-                .entry(coord)
-                .or_default()
-                .push(ProjectilePublic::from(projectile));
-        }
-        for boat in state.boats.values() {
-            let coord = chunk_coord_for_position(boat.x, boat.y, chunk_size);
-            boats_by_chunk
-                .entry(coord)
-                .or_default()
-                .push(BoatPublic::from(boat));
+        if !state.resources.contains_key(&coord) {
+            let mut generated = generate_resources(
+                now_ms,
+                coord,
+                &app_state.world,
+                &app_state.noise,
+                &data,
+                &app_state.config,
+                &state.structure_tiles,
+            );
+            if let Some(planted) = state.planted.remove(&coord) {
+                generated.extend(planted);
+            }
+            state.resources.insert(coord, generated);
         }
 
-        let client_entries: Vec<(String, mpsc::UnboundedSender<ServerMessage>)> = state
-            .clients
-            .iter()
-            .map(|(id, sender)| (id.clone(), sender.clone()))
+        let tiles = generate_tiles(coord, &app_state.world, &app_state.noise);
+        let visible_resources = match state.resources.get(&coord) {
+            Some(resources) => resources
+                .iter()
+                .filter(|res| res.hp > 0)
+                .map(ResourceNodePublic::from)
+                .collect(),
+            None => Vec::new(),
+        };
+        let structures = state
+            .structure_tiles
+            .values()
+            .filter(|structure| {
+                chunk_coord_for_tile(structure.x, structure.y, app_state.world.chunk_size) == coord
+            })
+            .map(StructurePublic::from)
             .collect();
 
-        for (client_id, sender) in client_entries {
-            let player = match state.players.get(&client_id) {
-                Some(player) => player,
-                None => continue,
-            };
-            let center = chunk_coord_for_position(player.x, player.y, chunk_size);
-            let mut visible_players = Vec::new();
-            let mut visible_monsters = Vec::new();
-            let mut visible_projectiles = Vec::new();
-            let mut visible_boats = Vec::new();
-            let mut visible_player_ids = HashSet::new();
-            let mut visible_monster_ids = HashSet::new();
-            let mut visible_projectile_ids = HashSet::new();
-            let mut visible_boat_ids = HashSet::new();
+        let _ = sender.send(ServerMessage::ChunkData {
+            chunk_x: coord.x,
+            chunk_y: coord.y,
+            tiles,
+            resources: visible_resources,
+            structures,
+            weather: weather_at_chunk(&app_state.noise, app_state.world.chunk_size, coord, now_ms),
+        });
+    }
+}
 
-            for dx in -ENTITY_VISIBILITY_RADIUS..=ENTITY_VISIBILITY_RADIUS {
-                for dy in -ENTITY_VISIBILITY_RADIUS..=ENTITY_VISIBILITY_RADIUS {
-                    let coord = ChunkCoord {
-                        x: center.x + dx,
-                        y: center.y + dy,
-                    };
-                    if let Some(players) = players_by_chunk.get(&coord) {
-                        for player_public in players {
-                            let mut entry = player_public.clone();
-                            if entry.id == client_id {
-                                entry.last_input_seq = Some(player.last_input_seq);
-                            }
-                            if visible_player_ids.insert(entry.id.clone()) {
-                                visible_players.push(entry);
-                            }
-                        }
-                    }
-                    if let Some(monsters) = monsters_by_chunk.get(&coord) {
-                        for monster_public in monsters {
-                            if visible_monster_ids.insert(monster_public.id) {
-                                visible_monsters.push(monster_public.clone());
-                            }
-                        }
-                    }
-                    if let Some(projectiles) = projectiles_by_chunk.get(&coord) {
-                        for projectile_public in projectiles {
-                            if visible_projectile_ids.insert(projectile_public.id) {
-                                visible_projectiles.push(projectile_public.clone());
-                            }
-                        }
-                    }
-                    if let Some(boats) = boats_by_chunk.get(&coord) {
-                        for boat_public in boats {
-                            if visible_boat_ids.insert(boat_public.id) {
-                                visible_boats.push(boat_public.clone());
-                            }
-                        }
-                    }
-                }
-            }
+async fn handle_build_request(app_state: &AppState, sid: &str, kind: String, x: i32, y: i32) {
+    let data = app_state.data();
+    let mut state = app_state.state.write().await;
+    let (player_id, inventory_snapshot, player_pos) = match state.players.get(sid) {
+        Some(player) => (player.id.clone(), player.inventory.clone(), (player.x, player.y)),
+        None => return,
+    };
 
-            let visibility = state
-                .visibility
-                .entry(client_id.clone())
-                .or_insert_with(VisibilityState::default);
-            let removed_players: Vec<String> = visibility
-                .players
-                .difference(&visible_player_ids)
-                .cloned()
-                .collect();
-            let removed_monsters: Vec<u64> = visibility
-                .monsters
-                .difference(&visible_monster_ids)
-                .cloned()
-                .collect();
-            let removed_projectiles: Vec<u64> = visibility
-                .projectiles
-                .difference(&visible_projectile_ids)
-                .cloned()
-                .collect();
-            let removed_boats: Vec<u64> = visibility
-                .boats
-                .difference(&visible_boat_ids)
-                .cloned()
-                .collect();
+    let mut tiles = Vec::new();
+    let mut placements: Vec<(TileCoord, String)> = Vec::new();
+    let mut cost = Vec::new();
+    let mut require_shovel = false;
+    let mut requires_land = true;
+    let mut require_water = false;
+    let mut require_near_water = false;
+    let mut is_boat = false;
+    let lang = player_language(&state, &player_id);
 
-            if !removed_players.is_empty()
-                || !removed_monsters.is_empty()
-                || !removed_projectiles.is_empty()
-                || !removed_boats.is_empty()
-            {
-                let _ = sender.send(ServerMessage::EntitiesRemove {
-                    players: removed_players,
-                    monsters: removed_monsters,
-                    projectiles: removed_projectiles,
-                    boats: removed_boats,
-                });
+    let build_kind = kind.as_str();
+    if matches!(
+        build_kind,
+        "craft_basic_axe" | "craft_basic_pick" | "craft_arrows"
+    ) {
+        let (crafted_id, crafted_count, cost) = match build_kind {
+            "craft_basic_axe" => (
+                "basic_axe",
+                1,
+                vec![ItemStack::new("wood", 4)],
+            ),
+            "craft_basic_pick" => (
+                "basic_pick",
+                1,
+                vec![ItemStack::new("wood", 4)],
+            ),
+            "craft_arrows" => (
+                "arrow",
+                6,
+                vec![ItemStack::new("wood", 1), ItemStack::new("stone", 1)],
+            ),
+            _ => return,
+        };
+        if !has_items(&inventory_snapshot, &cost) {
+            send_system_message(
+                &mut state,
+                &player_id,
+                message_not_enough_materials(lang).to_string(),
+            );
+            return;
+        }
+        let items = {
+            let player = match state.players.get_mut(sid) {
+                Some(player) => player,
+                None => return,
+            };
+            if !remove_items(&mut player.inventory, &cost) {
+                send_system_message(
+                    &mut state,
+                    &player_id,
+                    message_not_enough_materials(lang).to_string(),
+                );
+                return;
             }
-
-            visibility.players = visible_player_ids;
-            visibility.monsters = visible_monster_ids;
-            visibility.projectiles = visible_projectile_ids;
-            visibility.boats = visible_boat_ids;
-
-            let _ = sender.send(ServerMessage::EntitiesUpdate {
-                players: visible_players,
-                monsters: visible_monsters,
-                projectiles: visible_projectiles,
-                boats: visible_boats,
-            });
+            add_item(&mut player.inventory, crafted_id, crafted_count);
+            player.last_inventory_hash = inventory_hash(&player.inventory);
+            build_inventory_items(&player.inventory, data.as_ref(), lang)
+        };
+        if let Some(sender) = state.clients.get(sid) {
+            let _ = sender.send(ServerMessage::Inventory { items });
         }
+        send_system_message(
+            &mut state,
+            &player_id,
+            message_build_success(lang, build_kind),
+        );
+        return;
+    }
 
-        for player in state.players.values_mut() {
-            if now_ms - player.last_saved_ms >= SAVE_INTERVAL_MS {
-                player.last_saved_ms = now_ms;
-                to_save.push(player.to_doc());
+    match build_kind {
+        "hut_wood" => {
+            cost.push(ItemStack::new("wood", 20));
+            let width = 2;
+            let height = 2;
+            let base_y = y - (height - 1);
+            for dy in 0..height {
+                for dx in 0..width {
+                    let coord = TileCoord {
+                        x: x + dx,
+                        y: base_y + dy,
+                    };
+                    tiles.push(coord);
+                    let kind = if dy == height - 1 && dx == 0 {
+                        "hut_wood_root"
+                    } else if dy == height - 1 {
+                        "hut_wood_block"
+                    } else {
+                        "hut_wood_top"
+                    };
+                    placements.push((coord, kind.to_string()));
+                }
             }
         }
+        "house_stone" => {
+            cost.push(ItemStack::new("stone", 50));
+            let width = 3;
+            let height = 3;
+            let base_y = y - (height - 1);
+            for dy in 0..height {
+                for dx in 0..width {
+                    let coord = TileCoord {
+                        x: x + dx,
+                        y: base_y + dy,
+                    };
+                    tiles.push(coord);
+                    let kind = if dy == height - 1 && dx == 0 {
+                        "house_stone_root"
+                    } else if dy == height - 1 {
+                        "house_stone_block"
+                    } else {
+                        "house_stone_top"
+                    };
+                    placements.push((coord, kind.to_string()));
+                }
+            }
+        }
+        "bridge_wood" => {
+            cost.push(ItemStack::new("wood", 10));
+            requires_land = false;
+            match find_bridge_span(&app_state.noise, x, y) {
+                Some(span) => tiles = span,
+                None => {
+                    send_system_message(
+                        &mut state,
+                        &player_id,
+                        message_bridge_span_error(lang).to_string(),
+                    );
+                    return;
+                }
+            }
+        }
+        "bridge_stone" => {
+            cost.push(ItemStack::new("stone", 20));
+            requires_land = false;
+            match find_bridge_span(&app_state.noise, x, y) {
+                Some(span) => tiles = span,
+                None => {
+                    send_system_message(
+                        &mut state,
+                        &player_id,
+                        message_bridge_span_error(lang).to_string(),
+                    );
+                    return;
+                }
+            }
+        }
+        "path" => {
+            require_shovel = true;
+            let coord = TileCoord { x, y };
+            tiles.push(coord);
+            placements.push((coord, "path".to_string()));
+        }
+        "road" => {
+            require_shovel = true;
+            cost.push(ItemStack::new("stone", 2));
+            let coord = TileCoord { x, y };
+            tiles.push(coord);
+            placements.push((coord, "road".to_string()));
+        }
+        "boat" => {
+            cost.push(ItemStack::new("wood", 10));
+            requires_land = false;
+            require_water = true;
+            require_near_water = true;
+            is_boat = true;
+            let coord = TileCoord { x, y };
+            tiles.push(coord);
+        }
+        _ => {
+            send_system_message(
+                &mut state,
+                &player_id,
+                message_unknown_build(lang).to_string(),
+            );
+            return;
+        }
+    };
+
+    let mut structure_kind = build_kind.to_string();
+    if build_kind == "bridge_wood" || build_kind == "bridge_stone" {
+        let is_vertical = tiles
+            .first()
+            .map(|first| tiles.iter().all(|tile| tile.x == first.x))
+            .unwrap_or(false);
+        if build_kind == "bridge_wood" {
+            structure_kind = if is_vertical {
+                "bridge_wood_v".to_string()
+            } else {
+                "bridge_wood_h".to_string()
+            };
+        } else {
+            structure_kind = if is_vertical {
+                "bridge_stone_v".to_string()
+            } else {
+                "bridge_stone_h".to_string()
+            };
+        }
+    }
+
+    if require_shovel && !has_tool(&inventory_snapshot, data.as_ref(), "shovel") {
+        send_system_message(
+            &mut state,
+            &player_id,
+            message_need_shovel(lang).to_string(),
+        );
+        return;
+    }
+
+    if !cost.is_empty() && !has_items(&inventory_snapshot, &cost) {
+        send_system_message(
+            &mut state,
+            &player_id,
+            message_not_enough_materials(lang).to_string(),
+        );
+        return;
+    }
+
+    if require_near_water {
+        let (px, py) = entity_foot_tile(player_pos.0, player_pos.1);
+        if !is_adjacent_to_water(&app_state.noise, px, py) {
+            send_system_message(
+                &mut state,
+                &player_id,
+                message_need_water(lang).to_string(),
+            );
+            return;
+        }
+    }
+
+    for tile in &tiles {
+        if state
+            .structure_tiles
+            .contains_key(&TileCoord { x: tile.x, y: tile.y })
+        {
+            send_system_message(
+                &mut state,
+                &player_id,
+                message_spot_occupied(lang).to_string(),
+            );
+            return;
+        }
+        if is_boat
+            && state
+                .boats
+                .values()
+                .any(|boat| entity_foot_tile(boat.x, boat.y) == (tile.x, tile.y))
+        {
+            send_system_message(
+                &mut state,
+                &player_id,
+                message_spot_occupied(lang).to_string(),
+            );
+            return;
+        }
+        if resource_at_tile(&state, tile.x, tile.y) {
+            send_system_message(
+                &mut state,
+                &player_id,
+                message_clear_resource(lang).to_string(),
+            );
+            return;
+        }
+        if require_water && tile_at(&app_state.noise, tile.x, tile.y) != TILE_WATER {
+            send_system_message(
+                &mut state,
+                &player_id,
+                message_build_on_water(lang).to_string(),
+            );
+            return;
+        }
+        if requires_land && tile_at(&app_state.noise, tile.x, tile.y) == TILE_WATER {
+            send_system_message(
+                &mut state,
+                &player_id,
+                message_build_on_land(lang).to_string(),
+            );
+            return;
+        }
+    }
+
+    if !is_boat && placements.is_empty() {
+        for tile in &tiles {
+            placements.push((TileCoord { x: tile.x, y: tile.y }, structure_kind.clone()));
+        }
+    }
+
+    let mut inventory_items = None;
+    if !cost.is_empty() {
+        let mut removal_failed = false;
+        let items = {
+            let player = match state.players.get_mut(sid) {
+                Some(player) => player,
+                None => return,
+            };
+            if !remove_items(&mut player.inventory, &cost) {
+                removal_failed = true;
+                Vec::new()
+            } else {
+                player.last_inventory_hash = inventory_hash(&player.inventory);
+                build_inventory_items(&player.inventory, data.as_ref(), lang)
+            }
+        };
+        if removal_failed {
+            send_system_message(
+                &mut state,
+                &player_id,
+                message_not_enough_materials(lang).to_string(),
+            );
+            return;
+        }
+        inventory_items = Some(items);
+    }
+
+    if is_boat {
+        let (boat_x, boat_y) = tile_anchor_position(x, y);
+        let boat_id = state.next_id();
+        let boat = Boat {
+            id: boat_id,
+            x: boat_x,
+            y: boat_y,
+            owner_id: player_id.clone(),
+            last_saved_ms: now_millis(),
+        };
+        state.boats.insert(boat_id, boat.clone());
+
+        if let Some(items) = inventory_items {
+            if let Some(sender) = state.clients.get(sid) {
+                let _ = sender.send(ServerMessage::Inventory { items });
+            }
+        }
+
+        let boat_public = BoatPublic::from(&boat);
+        let chunk = chunk_coord_for_position(boat.x, boat.y, app_state.world.chunk_size);
+        send_to_players_in_chunk(
+            &state,
+            app_state.world.chunk_size,
+            chunk,
+            ServerMessage::EntitiesUpdate {
+                players: Vec::new(),
+                monsters: Vec::new(),
+                projectiles: Vec::new(),
+                boats: vec![boat_public],
+                ground_items: Vec::new(),
+            },
+        );
+        send_system_message(
+            &mut state,
+            &player_id,
+            message_build_success(lang, build_kind),
+        );
+
+        let store = app_state.store.clone();
+        let doc = BoatDoc {
+            id: boat_id as i64,
+            x: boat.x,
+            y: boat.y,
+            owner_id: boat.owner_id,
+        };
+        tokio::spawn(async move {
+            let _ = store.insert_boat(&doc).await;
+        });
+        return;
+    }
+
+    let structure_id = state.next_structure_id();
+    let mut new_tiles = Vec::new();
+    for (tile, kind) in placements {
+        let structure = StructureTile {
+            id: structure_id,
+            kind,
+            x: tile.x,
+            y: tile.y,
+            owner_id: player_id.clone(),
+            guild_id: None,
+        };
+        state
+            .structure_tiles
+            .insert(TileCoord { x: tile.x, y: tile.y }, structure.clone());
+        new_tiles.push(structure);
+    }
+
+    if let Some(items) = inventory_items {
+        if let Some(sender) = state.clients.get(sid) {
+            let _ = sender.send(ServerMessage::Inventory { items });
+        }
+    }
+
+    let structures_public: Vec<StructurePublic> = new_tiles.iter().map(StructurePublic::from).collect();
+    let mut chunks = HashSet::new();
+    for structure in &structures_public {
+        chunks.insert(chunk_coord_for_tile(
+            structure.x,
+            structure.y,
+            app_state.world.chunk_size,
+        ));
+    }
+    send_to_players_in_chunks(
+        &state,
+        app_state.world.chunk_size,
+        &chunks,
+        ServerMessage::StructureUpdate {
+            structures: structures_public.clone(),
+            state: "added".to_string(),
+        },
+    );
+    if let Some(sender) = state.clients.get(sid) {
+        let _ = sender.send(ServerMessage::StructureUpdate {
+            structures: structures_public.clone(),
+            state: "added".to_string(),
+        });
+    }
+    send_system_message(
+        &mut state,
+        &player_id,
+        message_build_success(lang, build_kind),
+    );
+
+    let docs: Vec<StructureDoc> = new_tiles
+        .into_iter()
+        .map(|tile| StructureDoc {
+            id: structure_id as i64,
+            kind: tile.kind,
+            x: tile.x,
+            y: tile.y,
+            owner_id: tile.owner_id,
+            guild_id: tile.guild_id,
+        })
+        .collect();
+    let store = app_state.store.clone();
+    tokio::spawn(async move {
+        let _ = store.insert_structures(&docs).await;
+    });
+}
+
+async fn handle_demolish_request(app_state: &AppState, sid: &str, x: i32, y: i32) {
+    let mut state = app_state.state.write().await;
+    let player_id = match state.players.get(sid) {
+        Some(player) => player.id.clone(),
+        None => return,
+    };
+    let lang = player_language(&state, &player_id);
+    let tile = TileCoord { x, y };
+    let structure = match state.structure_tiles.get(&tile) {
+        Some(structure) => structure.clone(),
+        None => {
+            send_system_message(&mut state, &player_id, message_nothing_to_remove(lang).to_string());
+            return;
+        }
+    };
+    if !can_manage_structure(&state, &player_id, &structure, GuildPermission::Demolish) {
+        send_system_message(
+            &mut state,
+            &player_id,
+            message_remove_own_only(lang).to_string(),
+        );
+        return;
+    }
+
+    let target_id = structure.id;
+    let mut removed = Vec::new();
+    state
+        .structure_tiles
+        .retain(|_, structure| {
+            if structure.id == target_id {
+                removed.push(StructurePublic {
+                    id: structure.id,
+                    kind: structure.kind.clone(),
+                    x: structure.x,
+                    y: structure.y,
+                });
+                false
+            } else {
+                true
+            }
+        });
+
+    if removed.is_empty() {
+        return;
+    }
+
+    let mut chunks = HashSet::new();
+    for structure in &removed {
+        chunks.insert(chunk_coord_for_tile(
+            structure.x,
+            structure.y,
+            app_state.world.chunk_size,
+        ));
+    }
+    send_to_players_in_chunks(
+        &state,
+        app_state.world.chunk_size,
+        &chunks,
+        ServerMessage::StructureUpdate {
+            structures: removed,
+            state: "removed".to_string(),
+        },
+    );
+    send_system_message(
+        &mut state,
+        &player_id,
+        message_structure_removed(lang).to_string(),
+    );
+
+    let store = app_state.store.clone();
+    tokio::spawn(async move {
+        let _ = store.delete_structure_group(target_id as i64).await;
+    });
+}
+
+async fn handle_plant_request(app_state: &AppState, sid: &str, item_id: String, x: i32, y: i32) {
+    let data = app_state.data();
+    let def = match data
+        .items
+        .get(&item_id)
+        .and_then(|item| item.plants.as_ref())
+        .and_then(|kind| data.resources.get(kind))
+    {
+        Some(def) => def,
+        None => return,
+    };
+    let mut state = app_state.state.write().await;
+    let (player_id, player_pos, has_seed) = match state.players.get(sid) {
+        Some(player) => (
+            player.id.clone(),
+            (player.x, player.y),
+            player.inventory.get(&item_id).copied().unwrap_or(0) > 0,
+        ),
+        None => return,
+    };
+    if !has_seed {
+        return;
+    }
+    let lang = player_language(&state, &player_id);
+
+    if distance(player_pos.0, player_pos.1, x as f32 + 0.5, y as f32 + 0.5)
+        > app_state.config.plant_range
+    {
+        send_system_message(&mut state, &player_id, message_too_far(lang).to_string());
+        return;
+    }
+
+    let chunk = chunk_coord_for_tile(x, y, app_state.world.chunk_size);
+    let tile_ok = def
+        .plant_on
+        .iter()
+        .any(|name| name == tile_name(tile_at(&app_state.noise, x, y)));
+    let campfire_x = app_state.world.spawn_x.round() as i32;
+    let campfire_y = app_state.world.spawn_y.round() as i32;
+    let occupied = state.structure_tiles.contains_key(&TileCoord { x, y })
+        || (y == campfire_y && (x == campfire_x || x == campfire_x + 1))
+        || state
+            .resources
+            .get(&chunk)
+            .map(|resources| resources.iter().any(|res| res.x == x && res.y == y))
+            .unwrap_or(true);
+    if !tile_ok || occupied {
+        send_system_message(
+            &mut state,
+            &player_id,
+            message_cannot_plant_here(lang).to_string(),
+        );
+        return;
+    }
+
+    let now_ms = now_millis();
+    let node_id = state.next_id();
+    let node = ResourceNode {
+        id: node_id,
+        kind: def.id.clone(),
+        x,
+        y,
+        hp: def.hp,
+        respawn_at_ms: None,
+        size: 1,
+        next_growth_ms: growth_max_size(data.as_ref(), &app_state.config, &def.id)
+            .filter(|max_size| *max_size > 1)
+            .map(|_| {
+                now_ms + growth_delay(data.as_ref(), &app_state.config, &def.id, node_id, x, y)
+            }),
+        owner_id: Some(player_id.clone()),
+        seed_id: Some(item_id.clone()),
+    };
+    let items = {
+        let player = match state.players.get_mut(sid) {
+            Some(player) => player,
+            None => return,
+        };
+        if !consume_item(&mut player.inventory, &item_id, 1) {
+            return;
+        }
+        player.last_inventory_hash = inventory_hash(&player.inventory);
+        build_inventory_items(&player.inventory, data.as_ref(), lang)
+    };
+    if let Some(resources) = state.resources.get_mut(&chunk) {
+        resources.push(node.clone());
+    }
+
+    if let Some(sender) = state.clients.get(sid) {
+        let _ = sender.send(ServerMessage::Inventory { items });
+    }
+    send_to_players_in_chunk(
+        &state,
+        app_state.world.chunk_size,
+        chunk,
+        ServerMessage::ResourceUpdate {
+            resource: ResourceNodePublic::from(&node),
+            state: "planted".to_string(),
+        },
+    );
+    let resource_name = localize_resource_name(data.as_ref(), &def.id, lang);
+    send_system_message(
+        &mut state,
+        &player_id,
+        message_planted(&resource_name, lang),
+    );
+
+    if let Some(doc) = node.to_planting_doc() {
+        state.planting_writes.push(PlantingWrite::Save(doc));
+    }
+}
+
+fn chunk_coord_for_position(x: f32, y: f32, chunk_size: i32) -> ChunkCoord {
+    let size = chunk_size as f32;
+    ChunkCoord {
+        x: (x / size).floor() as i32,
+        y: (y / size).floor() as i32,
+    }
+}
+
+fn chunk_coord_for_tile(x: i32, y: i32, chunk_size: i32) -> ChunkCoord {
+    let size = chunk_size as f32;
+    ChunkCoord {
+        x: (x as f32 / size).floor() as i32,
+        y: (y as f32 / size).floor() as i32,
+    }
+}
+
+fn chunk_in_radius(center: ChunkCoord, coord: ChunkCoord, radius: i32) -> bool {
+    (coord.x - center.x).abs() <= radius && (coord.y - center.y).abs() <= radius
+}
+
+fn send_to_players_in_chunk(
+    state: &GameState,
+    chunk_size: i32,
+    coord: ChunkCoord,
+    msg: ServerMessage,
+) {
+    for (player_id, sender) in state.clients.iter() {
+        if let Some(player) = state.players.get(player_id) {
+            let center = chunk_coord_for_position(player.x, player.y, chunk_size);
+            if chunk_in_radius(center, coord, state.config.entity_visibility_radius) {
+                let _ = sender.send(msg.clone());
+            }
+        }
+    }
+}
+
+fn send_to_players_in_chunks(
+    state: &GameState,
+    chunk_size: i32,
+    chunks: &HashSet<ChunkCoord>,
+    msg: ServerMessage,
+) {
+    for (player_id, sender) in state.clients.iter() {
+        if let Some(player) = state.players.get(player_id) {
+            let center = chunk_coord_for_position(player.x, player.y, chunk_size);
+            if chunks
+                .iter()
+                .any(|coord| chunk_in_radius(center, *coord, state.config.entity_visibility_radius))
+            {
+                let _ = sender.send(msg.clone());
+            }
+        }
+    }
+}
+
+fn collect_active_chunks(state: &GameState, chunk_size: i32) -> HashSet<ChunkCoord> {
+    let mut keep = HashSet::new();
+    for player in state.players.values() {
+        let center = chunk_coord_for_position(player.x, player.y, chunk_size);
+        for dx in -state.config.chunk_keep_radius..=state.config.chunk_keep_radius {
+            for dy in -state.config.chunk_keep_radius..=state.config.chunk_keep_radius {
+                keep.insert(ChunkCoord {
+                    x: center.x + dx,
+                    y: center.y + dy,
+                });
+            }
+        }
+    }
+    keep
+}
+
+fn prune_chunks(state: &mut GameState, now_ms: i64, chunk_size: i32, data: &GameData) {
+    let keep = collect_active_chunks(state, chunk_size);
+    for coord in &keep {
+        state.chunk_last_access.insert(*coord, now_ms);
+    }
+
+    let mut expired = Vec::new();
+    for (coord, last_access) in state.chunk_last_access.iter() {
+        if keep.contains(coord) {
+            continue;
+        }
+        if now_ms - *last_access > state.config.chunk_ttl_ms {
+            expired.push(*coord);
+        }
+    }
+
+    if expired.is_empty() {
+        return;
+    }
+
+    let expired_set: HashSet<ChunkCoord> = expired.iter().copied().collect();
+    for coord in &expired {
+        state.chunk_last_access.remove(coord);
+        if let Some(resources) = state.resources.remove(coord) {
+            let planted: Vec<ResourceNode> = resources
+                .into_iter()
+                .filter(|res| res.owner_id.is_some() && res.hp > 0)
+                .collect();
+            if !planted.is_empty() {
+                state.planted.insert(*coord, planted);
+            }
+        }
+        state.spawned_chunks.remove(coord);
+        state.chunk_respawn_at.remove(coord);
+        state.chunk_land.remove(coord);
+    }
+
+    let chunk_monsters = &mut state.chunk_monsters;
+    let mut departed_bosses = Vec::new();
+    state.monsters.retain(|_, monster| {
+        let keep =
+            !expired_set.contains(&chunk_coord_for_position(monster.x, monster.y, chunk_size));
+        if !keep {
+            release_monster_home(chunk_monsters, monster.home);
+            if monster.boss {
+                departed_bosses.push(monster.kind.clone());
+            }
+        }
+        keep
+    });
+    // A boss nobody is near leaves with an announcement, and the next one comes soon
+    // instead of a full interval later.
+    for kind in departed_bosses {
+        broadcast_localized(state, |lang| {
+            message_boss_departed(&localize_monster_name(data, &kind, lang), lang)
+        });
+        state.next_boss_spawn_ms = now_ms + state.config.boss_spawn_interval_ms / 10;
+    }
+    state.projectiles.retain(|_, projectile| {
+        !expired_set.contains(&chunk_coord_for_position(projectile.x, projectile.y, chunk_size))
+    });
+}
+
+fn spawn_game_loop(app_state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_millis(app_state.config.tick_ms));
+        loop {
+            interval.tick().await;
+            let now = now_millis();
+            let started = Instant::now();
+            let result = game_tick(&app_state, now).await;
+            app_state.metrics.record_tick(
+                started.elapsed(),
+                result.is_ok(),
+                app_state.config.tick_ms,
+            );
+            app_state.health.last_tick_ms.store(now, Ordering::Relaxed);
+            if let Err(err) = result {
+                warn!("game tick failed: {}", err);
+            }
+        }
+    });
+}
+
+async fn game_tick(app_state: &AppState, now_ms: i64) -> AppResult<()> {
+    let mut to_save = Vec::new();
+    let mut boats_to_save = Vec::new();
+    let planting_writes;
+    let guild_writes;
+    let report_writes;
+    let audit_writes;
+    {
+        let mut state = app_state.state.write().await;
+        let data = app_state.data();
+        let dt = app_state.config.tick_ms as f32 / 1000.0;
+
+        let player_ids: Vec<String> = state.players.keys().cloned().collect();
+        for id in player_ids {
+            let input = state.inputs.get(&id).cloned().unwrap_or_default();
+            if let Some(mut player) = state.players.remove(&id) {
+                let prev_inventory_hash = player.last_inventory_hash;
+                let storm = weather_at_position(
+                    &app_state.noise,
+                    app_state.world.chunk_size,
+                    player.x,
+                    player.y,
+                    now_ms,
+                ) == Weather::Storm;
+                let speed_factor = if player.in_boat && storm {
+                    app_state.config.storm_boat_speed_factor
+                } else {
+                    1.0
+                };
+                update_player_movement(
+                    &mut player,
+                    input.clone(),
+                    &state.structure_tiles,
+                    &app_state.noise,
+                    dt,
+                    speed_factor,
+                    &app_state.config,
+                );
+                if player.in_boat {
+                    sync_boat_position(&mut player, &mut state, &app_state.noise);
+                }
+                handle_player_actions(
+                    &mut player,
+                    input,
+                    now_ms,
+                    &mut state,
+                    &app_state.world,
+                    &app_state.noise,
+                    &data,
+                );
+                apply_player_regen(&mut player, now_ms, &app_state.config);
+                update_pvp_zone(&mut player, &state, &app_state.world);
+                let next_inventory_hash = inventory_hash(&player.inventory);
+                if next_inventory_hash != prev_inventory_hash {
+                    player.last_inventory_hash = next_inventory_hash;
+                    if let Some(sender) = state.clients.get(&id) {
+                        let lang = player_language(&state, &id);
+                        let items = build_inventory_items(&player.inventory, data.as_ref(), lang);
+                        let _ = sender.send(ServerMessage::Inventory { items });
+                    }
+                }
+                state.players.insert(id, player);
+            }
+        }
+
+        update_monsters(&mut state, now_ms, dt, &app_state.noise, &data);
+        update_projectiles(
+            &mut state,
+            now_ms,
+            dt,
+            &data,
+            &app_state.noise,
+            &app_state.world,
+        );
+        update_ground_items(&mut state, now_ms, &data);
+        update_parties(&mut state, now_ms);
+        update_resources(&mut state, now_ms, &data, app_state.world.chunk_size);
+        update_world_time(&mut state, now_ms, &app_state.world);
+        update_monster_population(
+            &mut state,
+            now_ms,
+            &app_state.world,
+            &app_state.noise,
+            &data,
+        );
+        update_boss_spawns(
+            &mut state,
+            now_ms,
+            &app_state.world,
+            &app_state.noise,
+            &data,
+        );
+        update_weather(&mut state, now_ms, &app_state.world, &app_state.noise);
+        prune_chunks(&mut state, now_ms, app_state.world.chunk_size, &data);
+
+        let mut expired_typing = Vec::new();
+        for (id, last) in state.typing.iter() {
+            if now_ms - *last > app_state.config.typing_timeout_ms {
+                expired_typing.push(id.clone());
+            }
+        }
+        for id in expired_typing {
+            state.typing.remove(&id);
+            broadcast_message_inline(
+                &state,
+                ServerMessage::Typing {
+                    id,
+                    typing: false,
+                },
+            );
+        }
+
+        let chunk_size = app_state.world.chunk_size;
+        let mut players_by_chunk: HashMap<ChunkCoord, Vec<PlayerPublic>> = HashMap::new();
+        let mut monsters_by_chunk: HashMap<ChunkCoord, Vec<MonsterPublic>> = HashMap::new();
+        let mut projectiles_by_chunk: HashMap<ChunkCoord, Vec<ProjectilePublic>> = HashMap::new();
+        let mut boats_by_chunk: HashMap<ChunkCoord, Vec<BoatPublic>> = HashMap::new();
+        let mut ground_items_by_chunk: HashMap<ChunkCoord, Vec<GroundItemPublic>> = HashMap::new();
+
+        for player in state.players.values() {
+            let coord = chunk_coord_for_position(player.x, player.y, chunk_size);
+            players_by_chunk
+                .entry(coord)
+                .or_default()
+                .push(PlayerPublic::from(player));
+        }
+        for monster in state.monsters.values() {
+            let coord = chunk_coord_for_position(monster.x, monster.y, chunk_size);
+            monsters_by_chunk
+                .entry(coord)
+                .or_default()
+                .push(MonsterPublic::from(monster));
+        }
+        for projectile in state.projectiles.values() {
+            let coord = chunk_coord_for_position(projectile.x, projectile.y, chunk_size);
+            projectiles_by_chunk
+                .entry(coord)
+                .or_default()
+                .push(ProjectilePublic::from(projectile));
+        }
+        for boat in state.boats.values() {
+            let coord = chunk_coord_for_position(boat.x, boat.y, chunk_size);
+            boats_by_chunk
+                .entry(coord)
+                .or_default()
+                .push(BoatPublic::from(boat));
+        }
+        for item in state.ground_items.values() {
+            let coord = chunk_coord_for_position(item.x, item.y, chunk_size);
+            ground_items_by_chunk
+                .entry(coord)
+                .or_default()
+                .push(GroundItemPublic::from(item));
+        }
+
+        let client_entries: Vec<(String, ClientSender)> = state
+            .clients
+            .iter()
+            .map(|(id, sender)| (id.clone(), sender.clone()))
+            .collect();
+
+        for (client_id, sender) in client_entries {
+            let player = match state.players.get(&client_id) {
+                Some(player) => player,
+                None => continue,
+            };
+            let center = chunk_coord_for_position(player.x, player.y, chunk_size);
+            let fog =
+                weather_at_chunk(&app_state.noise, chunk_size, center, now_ms) == Weather::Fog;
+            let in_view = |x: f32, y: f32| {
+                !fog || distance(player.x, player.y, x, y) <= app_state.config.fog_view_distance
+            };
+            let mut visible_players = Vec::new();
+            let mut visible_monsters = Vec::new();
+            let mut visible_projectiles = Vec::new();
+            let mut visible_boats = Vec::new();
+            let mut visible_ground_items = Vec::new();
+            let mut visible_player_ids = HashSet::new();
+            let mut visible_monster_ids = HashSet::new();
+            let mut visible_projectile_ids = HashSet::new();
+            let mut visible_boat_ids = HashSet::new();
+            let mut visible_ground_item_ids = HashSet::new();
+
+            for dx in -app_state.config.entity_visibility_radius
+                ..=app_state.config.entity_visibility_radius
+            {
+                for dy in -app_state.config.entity_visibility_radius
+                    ..=app_state.config.entity_visibility_radius
+                {
+                    let coord = ChunkCoord {
+                        x: center.x + dx,
+                        y: center.y + dy,
+                    };
+                    if let Some(players) = players_by_chunk.get(&coord) {
+                        for player_public in players {
+                            let mut entry = player_public.clone();
+                            if entry.id == client_id {
+                                entry.last_input_seq = Some(player.last_input_seq);
+                            } else if !in_view(entry.x, entry.y) {
+                                continue;
+                            }
+                            if visible_player_ids.insert(entry.id.clone()) {
+                                visible_players.push(entry);
+                            }
+                        }
+                    }
+                    if let Some(monsters) = monsters_by_chunk.get(&coord) {
+                        for monster_public in monsters {
+                            if !in_view(monster_public.x, monster_public.y) {
+                                continue;
+                            }
+                            if visible_monster_ids.insert(monster_public.id) {
+                                visible_monsters.push(monster_public.clone());
+                            }
+                        }
+                    }
+                    if let Some(projectiles) = projectiles_by_chunk.get(&coord) {
+                        for projectile_public in projectiles {
+                            if !in_view(projectile_public.x, projectile_public.y) {
+                                continue;
+                            }
+                            if visible_projectile_ids.insert(projectile_public.id) {
+                                visible_projectiles.push(projectile_public.clone());
+                            }
+                        }
+                    }
+                    if let Some(boats) = boats_by_chunk.get(&coord) {
+                        for boat_public in boats {
+                            if !in_view(boat_public.x, boat_public.y) {
+                                continue;
+                            }
+                            if visible_boat_ids.insert(boat_public.id) {
+                                visible_boats.push(boat_public.clone());
+                            }
+                        }
+                    }
+                    if let Some(items) = ground_items_by_chunk.get(&coord) {
+                        for item_public in items {
+                            if !in_view(item_public.x, item_public.y) {
+                                continue;
+                            }
+                            if visible_ground_item_ids.insert(item_public.id) {
+                                visible_ground_items.push(item_public.clone());
+                            }
+                        }
+                    }
+                }
+            }
+
+            let visibility = state
+                .visibility
+                .entry(client_id.clone())
+                .or_insert_with(VisibilityState::default);
+            let removed_players: Vec<String> = visibility
+                .players
+                .difference(&visible_player_ids)
+                .cloned()
+                .collect();
+            let removed_monsters: Vec<u64> = visibility
+                .monsters
+                .difference(&visible_monster_ids)
+                .cloned()
+                .collect();
+            let removed_projectiles: Vec<u64> = visibility
+                .projectiles
+                .difference(&visible_projectile_ids)
+                .cloned()
+                .collect();
+            let removed_boats: Vec<u64> = visibility
+                .boats
+                .difference(&visible_boat_ids)
+                .cloned()
+                .collect();
+            let removed_ground_items: Vec<u64> = visibility
+                .ground_items
+                .difference(&visible_ground_item_ids)
+                .cloned()
+                .collect();
+
+            if !removed_players.is_empty()
+                || !removed_monsters.is_empty()
+                || !removed_projectiles.is_empty()
+                || !removed_boats.is_empty()
+                || !removed_ground_items.is_empty()
+            {
+                let _ = sender.send(ServerMessage::EntitiesRemove {
+                    players: removed_players,
+                    monsters: removed_monsters,
+                    projectiles: removed_projectiles,
+                    boats: removed_boats,
+                    ground_items: removed_ground_items,
+                });
+            }
+
+            visibility.players = visible_player_ids;
+            visibility.monsters = visible_monster_ids;
+            visibility.projectiles = visible_projectile_ids;
+            visibility.boats = visible_boat_ids;
+            visibility.ground_items = visible_ground_item_ids;
+
+            let _ = sender.send(ServerMessage::EntitiesUpdate {
+                players: visible_players,
+                monsters: visible_monsters,
+                projectiles: visible_projectiles,
+                boats: visible_boats,
+                ground_items: visible_ground_items,
+            });
+        }
+
+        for player in state.players.values_mut() {
+            if now_ms - player.last_saved_ms >= app_state.config.save_interval_ms {
+                player.last_saved_ms = now_ms;
+                to_save.push(player.to_doc());
+            }
+        }
+
+        for boat in state.boats.values_mut() {
+            if now_ms - boat.last_saved_ms >= app_state.config.save_interval_ms {
+                boat.last_saved_ms = now_ms;
+                boats_to_save.push(BoatDoc {
+                    id: boat.id as i64,
+                    x: boat.x,
+                    y: boat.y,
+                    owner_id: boat.owner_id.clone(),
+                });
+            }
+        }
+
+        planting_writes = std::mem::take(&mut state.planting_writes);
+        guild_writes = std::mem::take(&mut state.guild_writes);
+        report_writes = std::mem::take(&mut state.report_writes);
+        audit_writes = std::mem::take(&mut state.audit_writes);
+    }
+
+    for doc in to_save {
+        let _ = app_state.store.save_player(&doc).await;
+    }
+    for doc in boats_to_save {
+        let _ = app_state.store.update_boat(&doc).await;
+    }
+    for write in planting_writes {
+        let _ = match write {
+            PlantingWrite::Save(doc) => app_state.store.save_planting(&doc).await,
+            PlantingWrite::Delete(id) => app_state.store.delete_planting(id).await,
+        };
+    }
+    for report in report_writes {
+        let _ = app_state.store.insert_report(&report).await;
+    }
+    for entry in audit_writes {
+        let _ = app_state.store.insert_audit(&entry).await;
+    }
+    for write in guild_writes {
+        let _ = match write {
+            GuildWrite::Save(doc) => app_state.store.save_guild(&doc).await,
+            GuildWrite::Delete(id) => app_state.store.delete_guild(&id).await,
+            GuildWrite::StructureGuild { id, guild_id } => {
+                app_state
+                    .store
+                    .update_structure_guild(id as i64, guild_id.as_deref())
+                    .await
+            }
+        };
+    }
+
+    Ok(())
+}
+
+fn update_player_movement(
+    player: &mut Player,
+    input: InputState,
+    structure_tiles: &HashMap<TileCoord, StructureTile>,
+    noise: &WorldNoise,
+    dt: f32,
+    speed_factor: f32,
+    config: &GameplayConfig,
+) {
+    player.last_input_seq = input.seq;
+    let mut dx = input.dir_x;
+    let mut dy = input.dir_y;
+    let input_len = (dx * dx + dy * dy).sqrt();
+    if input_len > 0.01 {
+        dx /= input_len;
+        dy /= input_len;
+        if let (Some(expected_x), Some(expected_y)) = (input.expected_x, input.expected_y) {
+            let corr_x = expected_x - player.x;
+            let corr_y = expected_y - player.y;
+            let corr_dist = (corr_x * corr_x + corr_y * corr_y).sqrt();
+            if corr_dist > 0.01 && corr_dist <= config.expected_pos_correction_range {
+                let corr_weight = (corr_dist / config.expected_pos_correction_range)
+                    * config.expected_pos_correction_weight;
+                dx += (corr_x / corr_dist) * corr_weight;
+                dy += (corr_y / corr_dist) * corr_weight;
+                let combined_len = (dx * dx + dy * dy).sqrt();
+                if combined_len > 0.01 {
+                    dx /= combined_len;
+                    dy /= combined_len;
+                }
+            }
+        }
+    }
+    if (dx * dx + dy * dy).sqrt() > 0.01 {
+        player.face_x = dx;
+        player.face_y = dy;
+    }
+
+    let speed = config.player_speed * speed_factor;
+    let next_x = player.x + dx * speed * dt;
+    let next_y = player.y + dy * speed * dt;
+
+    if player.in_boat {
+        if can_sail(structure_tiles, noise, next_x, player.y) {
+            player.x = next_x;
+        } else if can_walk(structure_tiles, noise, next_x, player.y) {
+            player.x = next_x;
+            player.in_boat = false;
+            player.boat_id = None;
+        }
+        if player.in_boat {
+            if can_sail(structure_tiles, noise, player.x, next_y) {
+                player.y = next_y;
+            } else if can_walk(structure_tiles, noise, player.x, next_y) {
+                player.y = next_y;
+                player.in_boat = false;
+                player.boat_id = None;
+            }
+        }
+    } else {
+        if can_walk(structure_tiles, noise, next_x, player.y) {
+            player.x = next_x;
+        }
+        if can_walk(structure_tiles, noise, player.x, next_y) {
+            player.y = next_y;
+        }
+    }
+}
+
+fn apply_player_regen(player: &mut Player, now_ms: i64, config: &GameplayConfig) {
+    if player.hp >= config.max_hp {
+        player.last_regen_ms = now_ms;
+        return;
+    }
+
+    if now_ms - player.last_regen_ms >= config.player_regen_interval_ms {
+        player.hp = (player.hp + 1).min(config.max_hp);
+        player.last_regen_ms = now_ms;
+    }
+}
+
+fn handle_player_actions(
+    player: &mut Player,
+    input: InputState,
+    now_ms: i64,
+    state: &mut GameState,
+    world: &WorldConfig,
+    noise: &WorldNoise,
+    data: &GameData,
+) {
+    if input.gather && now_ms - player.last_gather_ms >= 400 {
+        let lang = player_language(state, &player.id);
+        if player.in_boat {
+            player.last_gather_ms = now_ms;
+            if player
+                .inventory
+                .get("fishing_rod")
+                .copied()
+                .unwrap_or(0)
+                <= 0
+            {
+                send_system_message(
+                    state,
+                    &player.id,
+                    message_need_fishing_rod(lang).to_string(),
+                );
+            } else {
+                if player.fishing_target <= 0 {
+                    player.fishing_target = rand::thread_rng()
+                        .gen_range(state.config.fish_min_clicks..=state.config.fish_max_clicks);
+                    player.fishing_clicks = 0;
+                }
+                let weather =
+                    weather_at_position(noise, world.chunk_size, player.x, player.y, now_ms);
+                player.fishing_clicks += if weather.is_wet() { 2 } else { 1 };
+                if player.fishing_clicks >= player.fishing_target {
+                    player.fishing_clicks = 0;
+                    player.fishing_target = rand::thread_rng()
+                        .gen_range(state.config.fish_min_clicks..=state.config.fish_max_clicks);
+                    add_item(&mut player.inventory, "fish", 1);
+                    let item_name = localize_item_name(data, "fish", lang);
+                    send_system_message(state, &player.id, message_fishing_catch(&item_name, lang));
+                } else {
+                    send_system_message(state, &player.id, message_fishing_wait(lang).to_string());
+                }
+            }
+        } else {
+            let mut messages = Vec::new();
+            let mut resource_update: Option<(ResourceNodePublic, String)> = None;
+            let mut harvested_planting = None;
+            let mut did_gather = false;
+
+            {
+                let config = state.config.clone();
+                if let Some((resource, def)) = find_nearby_resource(player, state, data) {
+                    did_gather = true;
+                    let tool_power = best_tool_power(&player.inventory, data, &def.tool);
+                    let bare_handed = matches!(def.tool.as_str(), "axe" | "hand");
+                    let power = tool_power.or(bare_handed.then_some(1));
+                    if let Some(mut power) = power {
+                        if resource.kind == "rock" {
+                            power = (power as f32 / resource.size.max(1) as f32).ceil() as i32;
+                        }
+                        resource.hp -= power;
+                        if resource.hp <= 0 {
+                            resource.hp = 0;
+                            if resource.owner_id.is_some() {
+                                resource.respawn_at_ms = None;
+                                harvested_planting = Some(resource.id);
+                            } else {
+                                resource.respawn_at_ms = Some(now_ms + def.respawn_ms);
+                            }
+                            let unripe = resource.owner_id.is_some()
+                                && growth_max_size(data, &config, &resource.kind)
+                                    .is_some_and(|max_size| resource.size < max_size);
+                            if unripe {
+                                // An unripe crop only gives its seed back.
+                                if let Some(seed_id) = resource
+                                    .seed_id
+                                    .as_ref()
+                                    .filter(|id| data.items.contains_key(*id))
+                                {
+                                    add_item(&mut player.inventory, seed_id, 1);
+                                    let item_name = localize_item_name(data, seed_id, lang);
+                                    messages.push(message_collected(&item_name, 1, lang));
+                                }
+                            } else {
+                                let yield_multiplier = resource.size.max(1);
+                                let mut rng = rand::thread_rng();
+                                for drop in &def.drops {
+                                    let count = match drop.chance {
+                                        Some(chance) if rng.gen::<f32>() >= chance => continue,
+                                        Some(_) => drop.count,
+                                        None => drop.count * yield_multiplier,
+                                    };
+                                    add_item(&mut player.inventory, &drop.id, count);
+                                    let item_name = localize_item_name(data, &drop.id, lang);
+                                    messages.push(message_collected(&item_name, count, lang));
+                                }
+                            }
+                            resource_update = Some((
+                                ResourceNodePublic::from(resource.clone()),
+                                "removed".to_string(),
+                            ));
+                        } else {
+                            let resource_name =
+                                localize_resource_name(data, &resource.kind, lang);
+                            messages.push(message_hit_resource(&resource_name, resource.hp, lang));
+                        }
+                    } else {
+                        let tool_name = localize_tool_name(&def.tool, lang);
+                        messages.push(message_need_tool(&tool_name, lang));
+                    }
+                }
+            }
+
+            if did_gather {
+                player.last_gather_ms = now_ms;
+            }
+            if let Some(id) = harvested_planting {
+                state.planting_writes.push(PlantingWrite::Delete(id as i64));
+            }
+            for text in messages {
+                send_system_message(state, &player.id, text);
+            }
+            if let Some((resource, state_label)) = resource_update {
+                let chunk = chunk_coord_for_tile(resource.x, resource.y, world.chunk_size);
+                if let Some(sender) = state.clients.get(&player.id) {
+                    let _ = sender.send(ServerMessage::ResourceUpdate {
+                        resource: resource.clone(),
+                        state: state_label.clone(),
+                    });
+                }
+                send_to_players_in_chunk(
+                    state,
+                    world.chunk_size,
+                    chunk,
+                    ServerMessage::ResourceUpdate {
+                        resource,
+                        state: state_label,
+                    },
+                );
+            }
+        }
+    }
+
+    if input.attack {
+        if let Some(weapon) = best_melee_weapon(&player.inventory, data) {
+            if now_ms - player.last_attack_ms >= weapon.cooldown_ms {
+                if attack_monster_melee(player, state, &weapon, data, now_ms)
+                    || attack_player_melee(player, state, &weapon, world, now_ms)
+                {
+                    player.last_attack_ms = now_ms;
+                } else if try_ranged_attack(player, state, data, now_ms, &input, world) {
+                    player.last_attack_ms = now_ms;
+                }
+            }
+        } else if try_ranged_attack(player, state, data, now_ms, &input, world) {
+            player.last_attack_ms = now_ms;
+        }
+    }
+
+    if input.interact && now_ms - player.last_interact_ms >= 500 {
+        if !player.in_boat {
+            if let Some(boat) = find_nearby_boat(player, state) {
+                if state.players.values().any(|other| {
+                    other.in_boat && other.boat_id == Some(boat.id) && other.id != player.id
+                }) {
+                    let lang = player_language(state, &player.id);
+                    send_system_message(
+                        state,
+                        &player.id,
+                        message_boat_occupied(lang).to_string(),
+                    );
+                } else {
+                    player.x = boat.x;
+                    player.y = boat.y;
+                    player.in_boat = true;
+                    player.boat_id = Some(boat.id);
+                    let lang = player_language(state, &player.id);
+                    send_system_message(
+                        state,
+                        &player.id,
+                        message_board_boat(lang).to_string(),
+                    );
+                    if let Some(sender) = state.clients.get(&player.id) {
+                        let _ = sender.send(ServerMessage::EntitiesUpdate {
+                            players: vec![PlayerPublic::from(&*player)],
+                            monsters: Vec::new(),
+                            projectiles: Vec::new(),
+                            boats: state
+                                .boats
+                                .get(&boat.id)
+                                .map(|entry| vec![BoatPublic::from(entry)])
+                                .unwrap_or_default(),
+                            ground_items: Vec::new(),
+                        });
+                    }
+                }
+                player.last_interact_ms = now_ms;
+            } else if let Some(npc) = find_nearby_npc(player, data, &state.config) {
+                handle_npc_interaction(player, npc, state, data);
+                player.last_interact_ms = now_ms;
+            }
+        } else if let Some(npc) = find_nearby_npc(player, data, &state.config) {
+            handle_npc_interaction(player, npc, state, data);
+            player.last_interact_ms = now_ms;
+        }
+    }
+
+    if player.hp <= 0 {
+        player.hp = state.config.max_hp;
+        let (spawn_x, spawn_y) = spawn_near_campfire(world, noise);
+        player.x = spawn_x;
+        player.y = spawn_y;
+        let lang = player_language(state, &player.id);
+        send_system_message(state, &player.id, message_wake_up(lang).to_string());
+    }
+}
+
+fn sync_boat_position(player: &mut Player, state: &mut GameState, noise: &WorldNoise) {
+    let boat_id = match player.boat_id {
+        Some(id) => id,
+        None => return,
+    };
+    let boat = match state.boats.get_mut(&boat_id) {
+        Some(boat) => boat,
+        None => return,
+    };
+    let (tile_x, tile_y) = entity_foot_tile(player.x, player.y);
+    if tile_at(noise, tile_x, tile_y) == TILE_WATER {
+        boat.x = player.x;
+        boat.y = player.y;
+    }
+}
+
+fn update_monsters(
+    state: &mut GameState,
+    now_ms: i64,
+    dt: f32,
+    noise: &WorldNoise,
+    data: &GameData,
+) {
+    let config = state.config.clone();
+    let structure_tiles = &state.structure_tiles;
+    let player_positions: Vec<(String, f32, f32)> = state
+        .players
+        .values()
+        .map(|p| (p.id.clone(), p.x, p.y))
+        .collect();
+
+    // Pack members that are already hunting (or were provoked) last tick call in the
+    // rest of their kind nearby.
+    let pack_alerts: Vec<(u64, String, f32, f32, Option<String>)> = state
+        .monsters
+        .values()
+        .filter(|monster| {
+            data.monsters
+                .get(&monster.kind)
+                .map(|def| def.behavior == MonsterBehavior::Pack)
+                .unwrap_or(false)
+                && (monster.target.is_some() || now_ms < monster.provoked_until_ms)
+        })
+        .map(|monster| {
+            (
+                monster.id,
+                monster.kind.clone(),
+                monster.x,
+                monster.y,
+                monster.target.clone(),
+            )
+        })
+        .collect();
+
+    let night = state.night;
+    let mut damage_events: Vec<(String, i32, String)> = Vec::new();
+    let mut shots: Vec<Projectile> = Vec::new();
+    let mut summons: Vec<(String, u32, f32, f32, ChunkCoord)> = Vec::new();
+    let mut phase_events: Vec<(String, f32, f32)> = Vec::new();
+    let mut path_budget = PathBudget {
+        remaining: config.path_search_budget,
+        max_nodes: config.path_max_nodes,
+        retry_ms: config.path_retry_ms,
+    };
+    for monster in state.monsters.values_mut() {
+        let def = match data.monsters.get(&monster.kind) {
+            Some(def) => def,
+            None => continue,
+        };
+        let reached = |phase: &&BossPhase| monster.hp as f32 <= phase.hp_below * def.hp as f32;
+        let phase_index = def.phases.iter().filter(reached).count();
+        let phase = def
+            .phases
+            .iter()
+            .filter(reached)
+            .min_by(|a, b| a.hp_below.total_cmp(&b.hp_below));
+        if phase_index > monster.phase {
+            phase_events.push((monster.kind.clone(), monster.x, monster.y));
+        }
+        monster.phase = phase_index;
+        let speed = def.speed * phase.map_or(1.0, |phase| phase.speed_factor);
+        let night_factor = if night { def.night_damage_factor } else { 1.0 };
+        let damage =
+            (def.damage as f32 * phase.map_or(1.0, |phase| phase.damage_factor) * night_factor)
+                .round() as i32;
+
+        if monster.returning {
+            if distance(monster.x, monster.y, monster.spawn_x, monster.spawn_y) < 0.5 {
+                monster.returning = false;
+            } else {
+                let (home_x, home_y) = (monster.spawn_x, monster.spawn_y);
+                let (wx, wy) = next_waypoint(
+                    monster,
+                    home_x,
+                    home_y,
+                    structure_tiles,
+                    noise,
+                    now_ms,
+                    &mut path_budget,
+                );
+                move_towards(monster, wx, wy, speed, dt, structure_tiles, noise);
+                continue;
+            }
+        }
+
+        let provoked = now_ms < monster.provoked_until_ms;
+        let pack_alert = if def.behavior == MonsterBehavior::Pack && !provoked {
+            pack_alerts.iter().find(|(id, kind, x, y, _)| {
+                *id != monster.id
+                    && *kind == monster.kind
+                    && distance(*x, *y, monster.x, monster.y) <= def.pack_radius
+            })
+        } else {
+            None
+        };
+        let sense_range = if provoked || pack_alert.is_some() {
+            def.aggro_range.max(def.leash_distance)
+        } else {
+            def.aggro_range
+        };
+
+        let mut target = None;
+        let mut target_pos = None;
+        let mut nearest_dist = f32::MAX;
+        let called_target = pack_alert.and_then(|(_, _, _, _, target)| target.as_ref());
+        for (id, px, py) in &player_positions {
+            if called_target.is_some_and(|called| called != id) {
+                continue;
+            }
+            let dist = distance(*px, *py, monster.x, monster.y);
+            if dist < nearest_dist {
+                nearest_dist = dist;
+                target = Some(id.clone());
+                target_pos = Some((*px, *py));
+            }
+        }
+        if nearest_dist > sense_range {
+            target = None;
+            target_pos = None;
+        }
+
+        let hostile = match def.behavior {
+            MonsterBehavior::Aggressive | MonsterBehavior::Ranged => true,
+            MonsterBehavior::Pack => true,
+            MonsterBehavior::Neutral => provoked,
+            MonsterBehavior::Territorial => {
+                provoked
+                    || target_pos.is_some_and(|(tx, ty)| {
+                        distance(tx, ty, monster.spawn_x, monster.spawn_y) <= def.territory_radius
+                    })
+            }
+            MonsterBehavior::Timid => false,
+        };
+
+        if def.behavior == MonsterBehavior::Timid {
+            monster.target = None;
+            if let Some((tx, ty)) = target_pos {
+                move_away(
+                    monster,
+                    tx,
+                    ty,
+                    speed,
+                    dt,
+                    structure_tiles,
+                    noise,
+                    now_ms,
+                    def.evasive,
+                );
+                continue;
+            }
+            monster.flee_dir = None;
+            wander(monster, now_ms, speed, dt, structure_tiles, noise);
+            continue;
+        }
+
+        let (target_id, (tx, ty)) = match (target, target_pos) {
+            (Some(target_id), Some(pos)) if hostile => (target_id, pos),
+            _ => {
+                monster.target = None;
+                wander(monster, now_ms, speed, dt, structure_tiles, noise);
+                continue;
+            }
+        };
+
+        if distance(monster.x, monster.y, monster.spawn_x, monster.spawn_y) > def.leash_distance {
+            monster.target = None;
+            monster.provoked_until_ms = 0;
+            monster.returning = true;
+            monster.path.clear();
+            continue;
+        }
+
+        monster.target = Some(target_id.clone());
+        if let Some(special) = phase.and_then(|phase| phase.special.as_ref()) {
+            if now_ms - monster.last_special_ms >= special.cooldown_ms {
+                monster.last_special_ms = now_ms;
+                match special.kind {
+                    SpecialAttackKind::Slam => {
+                        for (id, px, py) in &player_positions {
+                            if distance(*px, *py, monster.x, monster.y) <= special.radius {
+                                damage_events.push((
+                                    id.clone(),
+                                    special.damage,
+                                    monster.kind.clone(),
+                                ));
+                            }
+                        }
+                    }
+                    SpecialAttackKind::Volley => {
+                        let kind = special
+                            .id
+                            .clone()
+                            .or_else(|| def.projectile.clone())
+                            .unwrap_or_else(|| "arrow".to_string());
+                        let count = special.count.max(1);
+                        for i in 0..count {
+                            let angle = std::f32::consts::TAU * i as f32 / count as f32;
+                            shots.push(monster_projectile(
+                                monster,
+                                kind.clone(),
+                                (angle.cos(), angle.sin()),
+                                def.projectile_speed.unwrap_or(6.0),
+                                1500,
+                                special.damage,
+                            ));
+                        }
+                    }
+                    SpecialAttackKind::Summon => {
+                        if let Some(kind) = &special.id {
+                            summons.push((
+                                kind.clone(),
+                                special.count,
+                                monster.x,
+                                monster.y,
+                                monster.home,
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        if def.behavior == MonsterBehavior::Ranged && nearest_dist < def.attack_range * 0.5 {
+            move_away(
+                monster,
+                tx,
+                ty,
+                speed,
+                dt,
+                structure_tiles,
+                noise,
+                now_ms,
+                def.evasive,
+            );
+        } else if def.behavior != MonsterBehavior::Ranged || nearest_dist > def.attack_range {
+            let (wx, wy) = next_waypoint(
+                monster,
+                tx,
+                ty,
+                structure_tiles,
+                noise,
+                now_ms,
+                &mut path_budget,
+            );
+            move_towards(monster, wx, wy, speed, dt, structure_tiles, noise);
+        }
+
+        if nearest_dist <= def.attack_range
+            && now_ms - monster.last_attack_ms >= def.attack_cooldown_ms
+        {
+            monster.last_attack_ms = now_ms;
+            match &def.projectile {
+                Some(kind) => {
+                    let projectile_speed = def.projectile_speed.unwrap_or(6.0);
+                    let dir = (
+                        (tx - monster.x) / nearest_dist.max(0.01),
+                        (ty - monster.y) / nearest_dist.max(0.01),
+                    );
+                    shots.push(monster_projectile(
+                        monster,
+                        kind.clone(),
+                        dir,
+                        projectile_speed,
+                        ((def.attack_range + 1.0) / projectile_speed * 1000.0) as i64,
+                        damage,
+                    ));
+                }
+                None => damage_events.push((target_id, damage, monster.kind.clone())),
+            }
+        }
+    }
+
+    for (player_id, damage, monster_kind) in damage_events {
+        monster_hits_player(state, &player_id, damage, &monster_kind, data);
+    }
+    for mut shot in shots {
+        shot.id = state.next_id();
+        state.projectiles.insert(shot.id, shot);
+    }
+    for (kind, count, x, y, home) in summons {
+        let def = match data.monsters.get(&kind) {
+            Some(def) => def,
+            None => continue,
+        };
+        for i in 0..count {
+            // Summons count against the same caps as natural spawns.
+            let in_home = state.chunk_monsters.get(&home).copied().unwrap_or(0);
+            if state.monsters.len() >= state.config.max_monsters
+                || in_home >= state.config.max_monsters_per_chunk
+            {
+                break;
+            }
+            let angle = std::f32::consts::TAU * i as f32 / count as f32;
+            let (sx, sy) = (x + angle.cos() * 1.5, y + angle.sin() * 1.5);
+            if can_walk(&state.structure_tiles, noise, sx, sy) {
+                spawn_monster(state, def, sx, sy, home);
+            }
+        }
+    }
+    for (kind, x, y) in phase_events {
+        let nearby: Vec<String> = state
+            .players
+            .values()
+            .filter(|player| {
+                distance(player.x, player.y, x, y) <= state.config.boss_phase_message_range
+            })
+            .map(|player| player.id.clone())
+            .collect();
+        for player_id in nearby {
+            let lang = player_language(state, &player_id);
+            let text = message_boss_phase(&localize_monster_name(data, &kind, lang), lang);
+            send_system_message(state, &player_id, text);
+        }
+    }
+}
+
+fn monster_projectile(
+    monster: &Monster,
+    kind: String,
+    (dir_x, dir_y): (f32, f32),
+    speed: f32,
+    ttl_ms: i64,
+    damage: i32,
+) -> Projectile {
+    Projectile {
+        id: 0,
+        kind,
+        x: monster.x + dir_x * 0.6,
+        y: monster.y + dir_y * 0.6,
+        vx: dir_x * speed,
+        vy: dir_y * speed,
+        ttl_ms,
+        damage,
+        owner: ProjectileOwner::Monster {
+            id: monster.id,
+            kind: monster.kind.clone(),
+        },
+        hits: Faction::Players,
+    }
+}
+
+fn monster_hits_player(
+    state: &mut GameState,
+    player_id: &str,
+    damage: i32,
+    monster_kind: &str,
+    data: &GameData,
+) {
+    let lang = player_language(state, player_id);
+    let monster_name = localize_monster_name(data, monster_kind, lang);
+    let message = if let Some(player) = state.players.get_mut(player_id) {
+        if player.god_mode {
+            return;
+        }
+        player.hp -= damage;
+        Some((
+            player.id.clone(),
+            message_monster_hits_you(&monster_name, player.hp.max(0), lang),
+        ))
+    } else {
+        None
+    };
+    if let Some((target_id, text)) = message {
+        send_system_message(state, &target_id, text);
+    }
+}
+
+fn update_projectiles(
+    state: &mut GameState,
+    now_ms: i64,
+    dt: f32,
+    data: &GameData,
+    noise: &WorldNoise,
+    world: &WorldConfig,
+) {
+    let config = state.config.clone();
+    let chunk_size = world.chunk_size;
+    let mut impacts: Vec<(u64, f32, f32, Impact)> = Vec::new();
+    let mut killed = HashSet::new();
+    let mut player_hits: Vec<(String, i32, String)> = Vec::new();
+    let mut pvp_hits: Vec<(String, String, String, i32)> = Vec::new();
+
+    {
+        let (monsters, players, projectiles, structure_tiles, resources) = (
+            &mut state.monsters,
+            &state.players,
+            &mut state.projectiles,
+            &state.structure_tiles,
+            &state.resources,
+        );
+        for (id, projectile) in projectiles.iter_mut() {
+            projectile.x += projectile.vx * dt;
+            projectile.y += projectile.vy * dt;
+            projectile.ttl_ms -= (dt * 1000.0) as i64;
+            if projectile.ttl_ms <= 0 {
+                impacts.push((*id, projectile.x, projectile.y, Impact::Ground));
+                continue;
+            }
+            if blocks_projectile(structure_tiles, projectile.x, projectile.y) {
+                impacts.push((*id, projectile.x, projectile.y, Impact::Structure));
+                continue;
+            }
+            if resource_blocks_projectile(resources, data, chunk_size, projectile.x, projectile.y) {
+                impacts.push((*id, projectile.x, projectile.y, Impact::Resource));
+                continue;
+            }
+
+            match projectile.hits {
+                Faction::Monsters => {
+                    let mut hit = None;
+                    for (monster_id, monster) in monsters.iter_mut() {
+                        let dist = distance(projectile.x, projectile.y, monster.x, monster.y);
+                        if dist < 0.5 {
+                            monster.hp -= projectile.damage;
+                            monster.provoked_until_ms = now_ms + state.config.monster_provoke_ms;
+                            if let ProjectileOwner::Player(owner_id) = &projectile.owner {
+                                *monster.damage_by.entry(owner_id.clone()).or_default() +=
+                                    projectile.damage;
+                            }
+                            hit = Some(*monster_id);
+                            break;
+                        }
+                    }
+
+                    if let Some(monster_id) = hit {
+                        impacts.push((*id, projectile.x, projectile.y, Impact::Monster));
+                        if let Some(monster) = monsters.get(&monster_id) {
+                            if monster.hp <= 0 {
+                                killed.insert(monster_id);
+                            }
+                        }
+                        continue;
+                    }
+
+                    let shooter = match &projectile.owner {
+                        ProjectileOwner::Player(owner_id) => players.get(owner_id),
+                        ProjectileOwner::Monster { .. } => None,
+                    };
+                    if let Some(shooter) = shooter {
+                        let victim = players.values().find(|other| {
+                            other.hp > 0
+                                && distance(projectile.x, projectile.y, other.x, other.y) < 0.5
+                                && pvp_allowed(world, &config, shooter, other)
+                        });
+                        if let Some(victim) = victim {
+                            impacts.push((*id, projectile.x, projectile.y, Impact::Player));
+                            pvp_hits.push((
+                                shooter.id.clone(),
+                                shooter.name.clone(),
+                                victim.id.clone(),
+                                projectile.damage,
+                            ));
+                        }
+                    }
+                }
+                Faction::Players => {
+                    let hit = players.values().find(|player| {
+                        distance(projectile.x, projectile.y, player.x, player.y) < 0.5
+                            && projectile.owner != ProjectileOwner::Player(player.id.clone())
+                    });
+                    if let Some(player) = hit {
+                        impacts.push((*id, projectile.x, projectile.y, Impact::Player));
+                        let source = match &projectile.owner {
+                            ProjectileOwner::Monster { kind, .. } => kind.clone(),
+                            ProjectileOwner::Player(_) => projectile.kind.clone(),
+                        };
+                        player_hits.push((player.id.clone(), projectile.damage, source));
+                    }
+                }
+            }
+        }
+    }
+
+    let mut rng = rand::thread_rng();
+    for (id, x, y, hit) in impacts {
+        let projectile = match state.projectiles.remove(&id) {
+            Some(projectile) => projectile,
+            None => continue,
+        };
+        let chunk = chunk_coord_for_position(x, y, chunk_size);
+        send_to_players_in_chunk(
+            state,
+            chunk_size,
+            chunk,
+            ServerMessage::ProjectileImpact { id, x, y, hit },
+        );
+
+        // Player arrows that miss can be picked up again.
+        let recover_chance = data
+            .items
+            .get(&projectile.kind)
+            .and_then(|def| def.recover_chance)
+            .unwrap_or(0.0);
+        let (tile_x, tile_y) = entity_foot_tile(x, y);
+        if matches!(projectile.owner, ProjectileOwner::Player(_))
+            && !matches!(hit, Impact::Monster | Impact::Player)
+            && tile_at(noise, tile_x, tile_y) != TILE_WATER
+            && rng.gen::<f32>() < recover_chance
+        {
+            // Step back so arrows stuck in a wall land on the near side.
+            let speed = (projectile.vx * projectile.vx + projectile.vy * projectile.vy).sqrt();
+            let (drop_x, drop_y) = if hit == Impact::Ground || speed <= f32::EPSILON {
+                (x, y)
+            } else {
+                (
+                    x - projectile.vx / speed * 0.5,
+                    y - projectile.vy / speed * 0.5,
+                )
+            };
+            let item_id = state.next_id();
+            state.ground_items.insert(
+                item_id,
+                GroundItem {
+                    id: item_id,
+                    item: projectile.kind,
+                    count: 1,
+                    x: drop_x,
+                    y: drop_y,
+                    expires_ms: now_ms + state.config.ground_item_ttl_ms,
+                },
+            );
+        }
+    }
+
+    for monster_id in killed {
+        handle_monster_death(state, monster_id, data, None, now_ms);
+    }
+    for (player_id, damage, monster_kind) in player_hits {
+        monster_hits_player(state, &player_id, damage, &monster_kind, data);
+    }
+    for (shooter_id, shooter_name, victim_id, damage) in pvp_hits {
+        if let Some(shooter) = state.players.get_mut(&shooter_id) {
+            shooter.last_pvp_combat_ms = now_ms;
+        }
+        player_hits_player(
+            state,
+            &shooter_id,
+            &shooter_name,
+            &victim_id,
+            damage,
+            now_ms,
+        );
+    }
+}
+
+fn has_line_of_sight(
+    state: &GameState,
+    data: &GameData,
+    chunk_size: i32,
+    ax: f32,
+    ay: f32,
+    bx: f32,
+    by: f32,
+) -> bool {
+    let steps = (distance(ax, ay, bx, by) / 0.25).ceil().max(1.0) as i32;
+    (1..steps).all(|i| {
+        let t = i as f32 / steps as f32;
+        let (x, y) = (ax + (bx - ax) * t, ay + (by - ay) * t);
+        !blocks_projectile(&state.structure_tiles, x, y)
+            && !resource_blocks_projectile(&state.resources, data, chunk_size, x, y)
+    })
+}
+
+fn resource_blocks_projectile(
+    resources: &HashMap<ChunkCoord, Vec<ResourceNode>>,
+    data: &GameData,
+    chunk_size: i32,
+    x: f32,
+    y: f32,
+) -> bool {
+    let (tile_x, tile_y) = entity_foot_tile(x, y);
+    let chunk = chunk_coord_for_tile(tile_x, tile_y, chunk_size);
+    resources.get(&chunk).is_some_and(|nodes| {
+        nodes.iter().any(|node| {
+            node.x == tile_x
+                && node.y == tile_y
+                && node.hp > 0
+                && data
+                    .resources
+                    .get(&node.kind)
+                    .and_then(|def| def.blocks_projectiles_at)
+                    .is_some_and(|min_size| node.size >= min_size)
+        })
+    })
+}
+
+fn update_ground_items(state: &mut GameState, now_ms: i64, data: &GameData) {
+    state
+        .ground_items
+        .retain(|_, item| item.expires_ms > now_ms);
+    let mut picked_up = Vec::new();
+    for item in state.ground_items.values() {
+        let nearest = state
+            .players
+            .values()
+            .filter(|player| player.hp > 0 && player.boat_id.is_none())
+            .find(|player| {
+                distance(player.x, player.y, item.x, item.y) <= state.config.pickup_range
+            });
+        if let Some(player) = nearest {
+            picked_up.push((item.id, player.id.clone()));
+        }
+    }
+    for (item_id, player_id) in picked_up {
+        let item = match state.ground_items.remove(&item_id) {
+            Some(item) => item,
+            None => continue,
+        };
+        if let Some(player) = state.players.get_mut(&player_id) {
+            add_item(&mut player.inventory, &item.item, item.count);
+        }
+        let lang = player_language(state, &player_id);
+        let item_name = localize_item_name(data, &item.item, lang);
+        send_system_message(
+            state,
+            &player_id,
+            message_picked_up(&item_name, item.count, lang),
+        );
+    }
+}
+
+fn update_world_time(state: &mut GameState, now_ms: i64, world: &WorldConfig) {
+    let night = is_night(&state.config, time_of_day(state, world, now_ms));
+    let phase_changed = night != state.night;
+    if phase_changed {
+        state.night = night;
+    }
+    if phase_changed || now_ms - state.last_world_time_ms >= state.config.world_time_broadcast_ms {
+        state.last_world_time_ms = now_ms;
+        broadcast_message_inline(state, world_time_message(state, world, now_ms));
+    }
+}
+
+fn update_weather(state: &mut GameState, now_ms: i64, world: &WorldConfig, noise: &WorldNoise) {
+    if now_ms - state.last_weather_ms < state.config.weather_update_ms {
+        return;
+    }
+    state.last_weather_ms = now_ms;
+    let chunk_size = world.chunk_size;
+
+    // Rain puts out the campfire at the world spawn; it burns again once the rain has passed.
+    let campfire_chunk = chunk_coord_for_position(world.spawn_x, world.spawn_y, chunk_size);
+    let lit = !weather_at_chunk(noise, chunk_size, campfire_chunk, now_ms).is_wet();
+    if lit != state.campfire_lit {
+        state.campfire_lit = lit;
+        broadcast_message_inline(state, ServerMessage::Campfire { lit });
+    }
+
+    let client_ids: Vec<String> = state.clients.keys().cloned().collect();
+    for client_id in client_ids {
+        let center = match state.players.get(&client_id) {
+            Some(player) => chunk_coord_for_position(player.x, player.y, chunk_size),
+            None => continue,
+        };
+        let mut current = HashMap::new();
+        for dx in -state.config.entity_visibility_radius..=state.config.entity_visibility_radius {
+            for dy in -state.config.entity_visibility_radius..=state.config.entity_visibility_radius
+            {
+                let coord = ChunkCoord {
+                    x: center.x + dx,
+                    y: center.y + dy,
+                };
+                current.insert(coord, weather_at_chunk(noise, chunk_size, coord, now_ms));
+            }
+        }
+        let visibility = state.visibility.entry(client_id.clone()).or_default();
+        let changed: Vec<ChunkWeather> = current
+            .iter()
+            .filter(|(coord, weather)| visibility.weather.get(coord) != Some(weather))
+            .map(|(coord, weather)| ChunkWeather {
+                chunk_x: coord.x,
+                chunk_y: coord.y,
+                weather: *weather,
+            })
+            .collect();
+        visibility.weather = current;
+        if changed.is_empty() {
+            continue;
+        }
+        if let Some(sender) = state.clients.get(&client_id) {
+            let _ = sender.send(ServerMessage::Weather { chunks: changed });
+        }
+    }
+}
+
+fn weather_at_chunk(
+    noise: &WorldNoise,
+    chunk_size: i32,
+    coord: ChunkCoord,
+    now_ms: i64,
+) -> Weather {
+    let center_x = (coord.x * chunk_size + chunk_size / 2) as f32;
+    let center_y = (coord.y * chunk_size + chunk_size / 2) as f32;
+    let value = noise.weather(center_x, center_y, now_ms);
+    if value > 0.38 {
+        Weather::Storm
+    } else if value > 0.18 {
+        Weather::Rain
+    } else if value < -0.3 {
+        Weather::Fog
+    } else {
+        Weather::Clear
+    }
+}
+
+fn weather_at_position(
+    noise: &WorldNoise,
+    chunk_size: i32,
+    x: f32,
+    y: f32,
+    now_ms: i64,
+) -> Weather {
+    weather_at_chunk(
+        noise,
+        chunk_size,
+        chunk_coord_for_position(x, y, chunk_size),
+        now_ms,
+    )
+}
+
+fn time_of_day(state: &GameState, world: &WorldConfig, now_ms: i64) -> f32 {
+    let day_length = world.day_length_ms.max(1);
+    (now_ms + state.time_offset_ms).rem_euclid(day_length) as f32 / day_length as f32
+}
+
+fn is_night(config: &GameplayConfig, time: f32) -> bool {
+    !(config.day_start..config.night_start).contains(&time)
+}
+
+fn world_time_message(state: &GameState, world: &WorldConfig, now_ms: i64) -> ServerMessage {
+    let time = time_of_day(state, world, now_ms);
+    ServerMessage::WorldTime {
+        time,
+        day_length_ms: world.day_length_ms,
+        night: is_night(&state.config, time),
+    }
+}
+
+fn update_resources(state: &mut GameState, now_ms: i64, data: &GameData, chunk_size: i32) {
+    let config = state.config.clone();
+    let mut respawned = Vec::new();
+    let mut grown = Vec::new();
+    for resources in state.resources.values_mut() {
+        resources.retain(|res| res.owner_id.is_none() || res.hp > 0);
+        for res in resources.iter_mut() {
+            if res.hp <= 0 {
+                if let Some(respawn_at) = res.respawn_at_ms {
+                    if now_ms >= respawn_at {
+                        if let Some(def) = data.resources.get(&res.kind) {
+                            if state
+                                .structure_tiles
+                                .contains_key(&TileCoord { x: res.x, y: res.y })
+                            {
+                                res.respawn_at_ms = Some(now_ms + def.respawn_ms);
+                                continue;
+                            }
+                            res.hp = def.hp;
+                            res.respawn_at_ms = None;
+                            if is_tree_kind(&res.kind) {
+                                let (s, next) =
+                                    tree_spawn_state(&config, now_ms as u64, res.x, res.y, now_ms);
+                                res.size = s;
+                                res.next_growth_ms = next;
+                            } else if res.kind == "rock" {
+                                let size_roll = noise_hash01(now_ms as u64, res.x, res.y);
+                                if size_roll > 0.8 {
+                                    res.size = 3.min(config.rock_max_size);
+                                } else if size_roll > 0.5 {
+                                    res.size = 2.min(config.rock_max_size);
+                                } else {
+                                    res.size = 1;
+                                }
+                                res.next_growth_ms = None;
+                            } else {
+                                res.size = 1;
+                                res.next_growth_ms = None;
+                            }
+                            respawned.push(ResourceNodePublic::from(res.clone()));
+                        }
+                    }
+                }
+            } else if let Some(max_size) = growth_max_size(data, &config, &res.kind) {
+                if res.size < max_size {
+                    if let Some(next_growth) = res.next_growth_ms {
+                        if now_ms >= next_growth {
+                            res.size += 1;
+                            res.next_growth_ms = if res.size < max_size {
+                                let delay = growth_delay(
+                                    data,
+                                    &config,
+                                    &res.kind,
+                                    now_ms as u64,
+                                    res.x,
+                                    res.y,
+                                );
+                                Some(now_ms + delay)
+                            } else {
+                                None
+                            };
+                            grown.push(ResourceNodePublic::from(res.clone()));
+                            if let Some(doc) = res.to_planting_doc() {
+                                state.planting_writes.push(PlantingWrite::Save(doc));
+                            }
+                        }
+                    }
+                } else {
+                    res.next_growth_ms = None;
+                }
+            }
+        }
+    }
+
+    if !respawned.is_empty() {
+        for res in respawned {
+            let chunk = chunk_coord_for_tile(res.x, res.y, chunk_size);
+            send_to_players_in_chunk(
+                state,
+                chunk_size,
+                chunk,
+                ServerMessage::ResourceUpdate {
+                    resource: res,
+                    state: "spawned".to_string(),
+                },
+            );
+        }
+    }
+
+    if !grown.is_empty() {
+        for res in grown {
+            let chunk = chunk_coord_for_tile(res.x, res.y, chunk_size);
+            send_to_players_in_chunk(
+                state,
+                chunk_size,
+                chunk,
+                ServerMessage::ResourceUpdate {
+                    resource: res,
+                    state: "grown".to_string(),
+                },
+            );
+        }
+    }
+}
+
+fn attack_monster_melee(
+    player: &mut Player,
+    state: &mut GameState,
+    weapon: &WeaponStats,
+    data: &GameData,
+    now_ms: i64,
+) -> bool {
+    let lang = player_language(state, &player.id);
+    let mut target_id = None;
+    let mut nearest_dist = f32::MAX;
+    for (id, monster) in state.monsters.iter() {
+        let dist = distance(player.x, player.y, monster.x, monster.y);
+        if dist < weapon.range && dist < nearest_dist {
+            nearest_dist = dist;
+            target_id = Some(*id);
+        }
+    }
+
+    if let Some(monster_id) = target_id {
+        let mut message = None;
+        let mut killed = false;
+        if let Some(monster) = state.monsters.get_mut(&monster_id) {
+            monster.hp -= weapon.damage;
+            monster.provoked_until_ms = now_ms + state.config.monster_provoke_ms;
+            *monster.damage_by.entry(player.id.clone()).or_default() += weapon.damage;
+            let monster_name = localize_monster_name(data, &monster.kind, lang);
+            message = Some(message_hit_monster(&monster_name, monster.hp.max(0), lang));
+            if monster.hp <= 0 {
+                killed = true;
+            }
+        }
+        if let Some(text) = message {
+            send_system_message(state, &player.id, text);
+        }
+        if killed {
+            handle_monster_death(state, monster_id, data, Some(player), now_ms);
+        }
+        return true;
+    }
+    false
+}
+
+fn attack_player_melee(
+    player: &mut Player,
+    state: &mut GameState,
+    weapon: &WeaponStats,
+    world: &WorldConfig,
+    now_ms: i64,
+) -> bool {
+    let target_id = state
+        .players
+        .values()
+        .filter(|other| other.hp > 0 && pvp_allowed(world, &state.config, player, other))
+        .map(|other| {
+            (
+                other.id.clone(),
+                distance(player.x, player.y, other.x, other.y),
+            )
+        })
+        .filter(|(_, dist)| *dist < weapon.range)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(id, _)| id);
+    match target_id {
+        Some(target_id) => {
+            player.last_pvp_combat_ms = now_ms;
+            let (attacker_id, attacker_name) = (player.id.clone(), player.name.clone());
+            player_hits_player(
+                state,
+                &attacker_id,
+                &attacker_name,
+                &target_id,
+                weapon.damage,
+                now_ms,
+            );
+            true
+        }
+        None => false,
+    }
+}
+
+fn player_hits_player(
+    state: &mut GameState,
+    attacker_id: &str,
+    attacker_name: &str,
+    victim_id: &str,
+    damage: i32,
+    now_ms: i64,
+) {
+    let (victim_name, hp, killed) = match state.players.get_mut(victim_id) {
+        Some(victim) if victim.god_mode => return,
+        Some(victim) => {
+            let was_alive = victim.hp > 0;
+            victim.hp -= damage;
+            victim.last_pvp_combat_ms = now_ms;
+            (victim.name.clone(), victim.hp, was_alive && victim.hp <= 0)
+        }
+        None => return,
+    };
+    let lang = player_language(state, attacker_id);
+    send_system_message(
+        state,
+        attacker_id,
+        message_hit_monster(&victim_name, hp.max(0), lang),
+    );
+    let lang = player_language(state, victim_id);
+    send_system_message(
+        state,
+        victim_id,
+        message_monster_hits_you(attacker_name, hp.max(0), lang),
+    );
+    if killed {
+        broadcast_localized(state, |lang| {
+            message_pvp_kill(attacker_name, &victim_name, lang)
+        });
+    }
+}
+
+fn in_pvp_safe_zone(world: &WorldConfig, config: &GameplayConfig, x: f32, y: f32) -> bool {
+    distance(x, y, world.spawn_x, world.spawn_y) < config.pvp_safe_radius
+}
+
+fn pvp_zone_at(world: &WorldConfig, x: f32, y: f32) -> Option<&PvpZone> {
+    world
+        .pvp_zones
+        .iter()
+        .find(|zone| distance(x, y, zone.x, zone.y) <= zone.radius)
+}
+
+/// Players can hurt each other when both are flagged or both stand in a PvP zone, and
+/// neither is in the safe area around the spawn campfire.
+fn pvp_allowed(
+    world: &WorldConfig,
+    config: &GameplayConfig,
+    attacker: &Player,
+    victim: &Player,
+) -> bool {
+    if attacker.id == victim.id
+        || in_pvp_safe_zone(world, config, attacker.x, attacker.y)
+        || in_pvp_safe_zone(world, config, victim.x, victim.y)
+    {
+        return false;
+    }
+    (attacker.pvp && victim.pvp)
+        || (pvp_zone_at(world, attacker.x, attacker.y).is_some()
+            && pvp_zone_at(world, victim.x, victim.y).is_some())
+}
+
+fn update_pvp_zone(player: &mut Player, state: &GameState, world: &WorldConfig) {
+    let zone = pvp_zone_at(world, player.x, player.y).map(|zone| zone.name.clone());
+    if zone == player.pvp_zone {
+        return;
+    }
+    let lang = player_language(state, &player.id);
+    let text = match &zone {
+        Some(name) => message_pvp_zone_entered(name, lang),
+        None => message_pvp_zone_left(lang).to_string(),
+    };
+    player.pvp_zone = zone;
+    if let Some(sender) = state.clients.get(&player.id) {
+        let _ = sender.send(ServerMessage::System { text });
+    }
+}
+
+fn handle_pvp_toggle(state: &mut GameState, sid: &str, enabled: bool) {
+    let now_ms = now_millis();
+    let lang = player_language(state, sid);
+    let text = {
+        let player = match state.players.get_mut(sid) {
+            Some(player) => player,
+            None => return,
+        };
+        if player.pvp == enabled {
+            return;
+        }
+        if now_ms - player.pvp_changed_ms < state.config.pvp_toggle_cooldown_ms
+            || (!enabled
+                && now_ms - player.last_pvp_combat_ms < state.config.pvp_toggle_cooldown_ms)
+        {
+            message_pvp_cooldown(lang)
+        } else {
+            player.pvp = enabled;
+            player.pvp_changed_ms = now_ms;
+            if enabled {
+                message_pvp_enabled(lang)
+            } else {
+                message_pvp_disabled(lang)
+            }
+        }
+    };
+    send_system_message(state, sid, text.to_string());
+}
+
+/// Delivers a chat line on its channel, or runs it as a slash command.
+fn handle_chat(
+    state: &mut GameState,
+    sid: &str,
+    text: &str,
+    channel: ChatChannel,
+    chunk_size: i32,
+    filter: &WordFilter,
+) {
+    let now_ms = now_millis();
+    let lang = player_language(state, sid);
+    let sender_name = player_name(state, sid);
+    let (command, rest) = match text.strip_prefix('/') {
+        Some(command_line) => match command_line.split_once(char::is_whitespace) {
+            Some((command, rest)) => (Some(command.to_lowercase()), rest.trim()),
+            None => (Some(command_line.to_lowercase()), ""),
+        },
+        None => (None, text),
+    };
+
+    // Lookups and reports still work while muted; anything other players would see
+    // counts towards the rate limit.
+    let exempt = matches!(command.as_deref(), Some("who" | "help" | "report"));
+    if !exempt {
+        let limited = match state.players.get_mut(sid) {
+            Some(player) => check_chat_rate(player, now_ms, &state.config),
+            None => return,
+        };
+        if let Some((remaining_ms, new_mute)) = limited {
+            let secs = (remaining_ms + 999) / 1000;
+            let text = if new_mute {
+                message_chat_rate_muted(secs, lang)
+            } else {
+                message_chat_muted(secs, lang)
+            };
+            send_system_message(state, sid, text);
+            return;
+        }
+    }
+
+    let channel = match command.as_deref() {
+        None => channel,
+        Some("l" | "local") => ChatChannel::Local,
+        Some("s" | "shout" | "global") => ChatChannel::Global,
+        Some("p" | "party") => ChatChannel::Party,
+        Some("g") => ChatChannel::Guild,
+        Some("w" | "whisper" | "tell") => {
+            handle_whisper(state, sid, &sender_name, rest, filter);
+            return;
+        }
+        Some("me") => {
+            if !rest.is_empty() {
+                log_chat(
+                    state,
+                    sid,
+                    &sender_name,
+                    ChatChannel::Local,
+                    None,
+                    rest,
+                    now_ms,
+                );
+                let censored = filter.censor(rest);
+                send_local(state, sid, chunk_size, |_| ServerMessage::Chat {
+                    from: sender_name.clone(),
+                    text: censored.clone(),
+                    channel: ChatChannel::Local,
+                    to: None,
+                    emote: true,
+                });
+            }
+            return;
+        }
+        Some("roll") => {
+            let max = rest.parse::<u32>().unwrap_or(100).clamp(2, 1_000_000);
+            let roll = rand::thread_rng().gen_range(1..=max);
+            send_local(state, sid, chunk_size, |lang| ServerMessage::System {
+                text: message_roll(&sender_name, roll, max, lang),
+            });
+            return;
+        }
+        Some("who") => {
+            let mut names: Vec<String> = state
+                .players
+                .values()
+                .map(|player| player.name.clone())
+                .collect();
+            names.sort_by_key(|name| name.to_lowercase());
+            send_system_message(state, sid, message_who(&names, lang));
+            return;
+        }
+        Some("report") => {
+            handle_report(state, sid, &sender_name, rest, now_ms);
+            return;
+        }
+        Some("help") => {
+            send_system_message(state, sid, message_chat_help(lang).to_string());
+            return;
+        }
+        Some(command) => {
+            send_system_message(state, sid, message_unknown_command(command, lang));
+            return;
+        }
+    };
+    if rest.is_empty() {
+        return;
+    }
+    let recipients: Vec<String> = match channel {
+        ChatChannel::Local => Vec::new(),
+        ChatChannel::Global => state.clients.keys().cloned().collect(),
+        ChatChannel::Party => match state.party_of.get(sid).and_then(|id| state.parties.get(id)) {
+            Some(party) => party.members.clone(),
+            None => {
+                send_system_message(state, sid, message_party_none(lang).to_string());
+                return;
+            }
+        },
+        ChatChannel::Guild => match player_guild(state, sid).and_then(|id| state.guilds.get(&id)) {
+            Some(guild) => guild
+                .members
+                .iter()
+                .map(|member| member.id.clone())
+                .collect(),
+            None => {
+                send_system_message(state, sid, message_guild_none(lang).to_string());
+                return;
+            }
+        },
+        ChatChannel::Whisper => {
+            send_system_message(state, sid, message_whisper_usage(lang).to_string());
+            return;
+        }
+    };
+    log_chat(state, sid, &sender_name, channel, None, rest, now_ms);
+    let msg = ServerMessage::Chat {
+        from: sender_name.clone(),
+        text: filter.censor(rest),
+        channel,
+        to: None,
+        emote: false,
+    };
+    if channel == ChatChannel::Local {
+        send_local(state, sid, chunk_size, |_| msg.clone());
+        return;
+    }
+    for id in recipients {
+        if let Some(sender) = state.clients.get(&id) {
+            let _ = sender.send(msg.clone());
+        }
+    }
+}
+
+/// Counts a message against the sender's rate limit. Returns the remaining mute
+/// time, and whether this message triggered it, when the message must be dropped.
+/// Each offence within `CHAT_STRIKE_RESET_MS` of the last one mutes for longer.
+fn check_chat_rate(
+    player: &mut Player,
+    now_ms: i64,
+    config: &GameplayConfig,
+) -> Option<(i64, bool)> {
+    if player.muted_until_ms > now_ms {
+        return Some((player.muted_until_ms - now_ms, false));
+    }
+    while player
+        .chat_times
+        .front()
+        .is_some_and(|at| now_ms - at >= config.chat_rate_window_ms)
+    {
+        player.chat_times.pop_front();
+    }
+    player.chat_times.push_back(now_ms);
+    if player.chat_times.len() <= config.chat_rate_limit {
+        return None;
+    }
+    player.chat_times.clear();
+    if now_ms - player.last_strike_ms > config.chat_strike_reset_ms {
+        player.chat_strikes = 0;
+    }
+    player.chat_strikes += 1;
+    player.last_strike_ms = now_ms;
+    let step = (player.chat_strikes as usize - 1).min(config.chat_mute_steps_ms.len() - 1);
+    let mute_ms = config.chat_mute_steps_ms[step];
+    player.muted_until_ms = now_ms + mute_ms;
+    Some((mute_ms, true))
+}
+
+fn log_chat(
+    state: &mut GameState,
+    sid: &str,
+    from: &str,
+    channel: ChatChannel,
+    to: Option<&str>,
+    text: &str,
+    now_ms: i64,
+) {
+    if state.chat_log.len() >= state.config.chat_log_size {
+        state.chat_log.pop_front();
+    }
+    state.chat_log.push_back(ChatLogEntry {
+        from_id: sid.to_string(),
+        from: from.to_string(),
+        channel,
+        to: to.map(str::to_string),
+        text: text.to_string(),
+        at_ms: now_ms,
+    });
+}
+
+/// Sends to everyone whose loaded area covers the sender's chunk.
+fn send_local(
+    state: &GameState,
+    sid: &str,
+    chunk_size: i32,
+    msg_for: impl Fn(Language) -> ServerMessage,
+) {
+    let center = match state.players.get(sid) {
+        Some(player) => chunk_coord_for_position(player.x, player.y, chunk_size),
+        None => return,
+    };
+    for (id, sender) in &state.clients {
+        let in_range = state.players.get(id).is_some_and(|player| {
+            chunk_in_radius(
+                chunk_coord_for_position(player.x, player.y, chunk_size),
+                center,
+                state.config.entity_visibility_radius,
+            )
+        });
+        if in_range {
+            let _ = sender.send(msg_for(player_language(state, id)));
+        }
+    }
+}
+
+/// Splits `<name> <rest>` where the name may contain spaces: the longest candidate
+/// name that prefixes the text wins.
+fn split_name_prefix(
+    candidates: impl Iterator<Item = (String, String)>,
+    text: &str,
+) -> Option<(String, String, &str)> {
+    candidates
+        .filter(|(_, name)| {
+            let len = name.len();
+            text.get(..len)
+                .is_some_and(|prefix| prefix.to_lowercase() == name.to_lowercase())
+                && (text.len() == len || text[len..].starts_with(char::is_whitespace))
+        })
+        .max_by_key(|(_, name)| name.len())
+        .map(|(id, name)| {
+            let rest = text[name.len()..].trim();
+            (id, name, rest)
+        })
+}
+
+fn handle_whisper(
+    state: &mut GameState,
+    sid: &str,
+    sender_name: &str,
+    rest: &str,
+    filter: &WordFilter,
+) {
+    let lang = player_language(state, sid);
+    let candidates = state
+        .players
+        .values()
+        .map(|player| (player.id.clone(), player.name.clone()));
+    let (target_id, target_name, text) = match split_name_prefix(candidates, rest) {
+        Some(target) if !target.2.is_empty() => target,
+        _ => {
+            send_system_message(state, sid, message_whisper_usage(lang).to_string());
+            return;
+        }
+    };
+    log_chat(
+        state,
+        sid,
+        sender_name,
+        ChatChannel::Whisper,
+        Some(&target_name),
+        text,
+        now_millis(),
+    );
+    let text = filter.censor(text);
+    if let Some(sender) = state.clients.get(&target_id) {
+        let _ = sender.send(ServerMessage::Chat {
+            from: sender_name.to_string(),
+            text: text.clone(),
+            channel: ChatChannel::Whisper,
+            to: None,
+            emote: false,
+        });
+    }
+    if let Some(sender) = state.clients.get(sid) {
+        let _ = sender.send(ServerMessage::Chat {
+            from: sender_name.to_string(),
+            text,
+            channel: ChatChannel::Whisper,
+            to: Some(target_name),
+            emote: false,
+        });
+    }
+}
+
+/// Moderator commands typed into chat. Returns false when the text is not one or the
+/// sender is not an admin, so it falls through to normal chat handling.
+fn handle_gm_command(app_state: &AppState, state: &mut GameState, sid: &str, text: &str) -> bool {
+    let (command, rest) = match text.strip_prefix('/') {
+        Some(line) => match line.split_once(char::is_whitespace) {
+            Some((command, rest)) => (command.to_lowercase(), rest.trim()),
+            None => (line.to_lowercase(), ""),
+        },
+        None => return false,
+    };
+    if !matches!(
+        command.as_str(),
+        "tp" | "give" | "spawn" | "heal" | "kick" | "godmode" | "time"
+    ) {
+        return false;
+    }
+    let actor_name = match state.players.get(sid) {
+        Some(player) if player.role == PlayerRole::Admin => player.name.clone(),
+        _ => return false,
+    };
+    let lang = player_language(state, sid);
+    let result = match command.as_str() {
+        "tp" => gm_teleport(app_state, state, sid, rest, lang),
+        "give" => gm_give(app_state, state, sid, rest, lang),
+        "spawn" => gm_spawn(app_state, state, sid, rest, lang),
+        "heal" => gm_heal(state, sid, rest, lang),
+        "kick" => gm_kick(state, rest, lang),
+        "godmode" => gm_godmode(state, sid, lang),
+        _ => gm_time(app_state, state, rest, lang),
+    };
+    match result {
+        Ok((target_id, reply)) => {
+            info!("gm {} ran /{} {}", actor_name, command, rest);
+            state.audit_writes.push(AuditDoc {
+                actor_id: sid.to_string(),
+                actor_name,
+                command,
+                args: rest.to_string(),
+                target_id,
+                created_ms: now_millis(),
+            });
+            send_system_message(state, sid, reply);
+        }
+        Err(reply) => send_system_message(state, sid, reply),
+    }
+    true
+}
+
+type GmResult = Result<(Option<String>, String), String>;
+
+/// Finds the online player a command targets, falling back to the sender when no name
+/// was given.
+fn gm_target(state: &GameState, sid: &str, name: &str, lang: Language) -> Result<String, String> {
+    if name.is_empty() {
+        return Ok(sid.to_string());
+    }
+    find_player_by_name(state, name).ok_or_else(|| message_gm_player_not_found(name, lang))
+}
+
+/// `/tp x y`, `/tp name x y` or `/tp name` (go to that player).
+fn gm_teleport(
+    app_state: &AppState,
+    state: &mut GameState,
+    sid: &str,
+    rest: &str,
+    lang: Language,
+) -> GmResult {
+    let words: Vec<&str> = rest.split_whitespace().collect();
+    let coords = match words.as_slice() {
+        [.., x, y] => x.parse::<i32>().ok().zip(y.parse::<i32>().ok()),
+        _ => None,
+    };
+    let (target_id, x, y) = match coords {
+        Some((tile_x, tile_y)) => {
+            let target_id = gm_target(state, sid, &words[..words.len() - 2].join(" "), lang)?;
+            let (x, y) = tile_anchor_position(tile_x, tile_y);
+            (target_id, x, y)
+        }
+        None if !words.is_empty() => {
+            let other_id = gm_target(state, sid, &words.join(" "), lang)?;
+            match state.players.get(&other_id) {
+                Some(other) => (sid.to_string(), other.x, other.y),
+                None => return Err(message_gm_player_not_found(rest, lang)),
+            }
+        }
+        None => return Err(message_gm_usage(lang).to_string()),
+    };
+    if !can_walk(&state.structure_tiles, &app_state.noise, x, y) {
+        return Err(message_gm_blocked(lang).to_string());
+    }
+    let player = match state.players.get_mut(&target_id) {
+        Some(player) => player,
+        None => return Err(message_gm_player_not_found(rest, lang)),
+    };
+    player.x = x;
+    player.y = y;
+    player.in_boat = false;
+    player.boat_id = None;
+    player.fishing_clicks = 0;
+    player.fishing_target = 0;
+    let (tile_x, tile_y) = entity_foot_tile(x, y);
+    let text = message_gm_teleported(&player.name, tile_x, tile_y, lang);
+    Ok((Some(target_id), text))
+}
+
+/// `/give item [count] [name]`; a negative count takes items away.
+fn gm_give(
+    app_state: &AppState,
+    state: &mut GameState,
+    sid: &str,
+    rest: &str,
+    lang: Language,
+) -> GmResult {
+    let data = app_state.data();
+    let (item_id, rest) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let rest = rest.trim();
+    if item_id.is_empty() {
+        return Err(message_gm_usage(lang).to_string());
+    }
+    if !data.items.contains_key(item_id) {
+        return Err(message_gm_unknown_item(item_id, lang));
+    }
+    let (count_text, name) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let (count, name) = match count_text.parse::<i32>() {
+        Ok(count) => (count, name.trim()),
+        Err(_) => (1, rest),
+    };
+    if count == 0 {
+        return Err(message_gm_usage(lang).to_string());
+    }
+    let target_id = gm_target(state, sid, name, lang)?;
+    let player = match state.players.get_mut(&target_id) {
+        Some(player) => player,
+        None => return Err(message_gm_player_not_found(name, lang)),
+    };
+    adjust_item(&mut player.inventory, item_id, count);
+    let item_name = localize_item_name(data.as_ref(), item_id, lang);
+    let text = message_gm_gave(&item_name, count, &player.name, lang);
+    Ok((Some(target_id), text))
+}
+
+/// `/spawn monster kind [count]` around the sender.
+fn gm_spawn(
+    app_state: &AppState,
+    state: &mut GameState,
+    sid: &str,
+    rest: &str,
+    lang: Language,
+) -> GmResult {
+    let data = app_state.data();
+    let words: Vec<&str> = rest.split_whitespace().collect();
+    let (kind, count) = match words.as_slice() {
+        ["monster", kind] => (*kind, 1),
+        ["monster", kind, count] => match count.parse::<i32>() {
+            Ok(count) => (*kind, count.clamp(1, state.config.gm_max_spawn)),
+            Err(_) => return Err(message_gm_usage(lang).to_string()),
+        },
+        _ => return Err(message_gm_usage(lang).to_string()),
+    };
+    let def = match data.monsters.get(kind) {
+        Some(def) => def.clone(),
+        None => return Err(message_gm_unknown_monster(kind, lang)),
+    };
+    let (px, py) = match state.players.get(sid) {
+        Some(player) => (player.x, player.y),
+        None => return Err(message_gm_usage(lang).to_string()),
+    };
+    let mut rng = rand::thread_rng();
+    let mut spawned = 0;
+    for _ in 0..count * 8 {
+        if spawned == count {
+            break;
+        }
+        let (tile_x, tile_y) = entity_foot_tile(
+            px + rng.gen_range(-3.0..=3.0),
+            py + rng.gen_range(-3.0..=3.0),
+        );
+        let (x, y) = tile_anchor_position(tile_x, tile_y);
+        if !can_walk(&state.structure_tiles, &app_state.noise, x, y) {
+            continue;
+        }
+        let home = chunk_coord_for_position(x, y, app_state.world.chunk_size);
+        spawn_monster(state, &def, x, y, home);
+        spawned += 1;
+    }
+    if spawned == 0 {
+        return Err(message_gm_blocked(lang).to_string());
+    }
+    let monster_name = localize_monster_name(data.as_ref(), &def.id, lang);
+    Ok((None, message_gm_spawned(&monster_name, spawned, lang)))
+}
+
+fn gm_heal(state: &mut GameState, sid: &str, rest: &str, lang: Language) -> GmResult {
+    let target_id = gm_target(state, sid, rest, lang)?;
+    let player = match state.players.get_mut(&target_id) {
+        Some(player) => player,
+        None => return Err(message_gm_player_not_found(rest, lang)),
+    };
+    player.hp = state.config.max_hp;
+    let text = message_gm_healed(&player.name, lang);
+    Ok((Some(target_id), text))
+}
+
+/// `/kick name [reason]`.
+fn gm_kick(state: &mut GameState, rest: &str, lang: Language) -> GmResult {
+    let candidates = state
+        .players
+        .values()
+        .map(|player| (player.id.clone(), player.name.clone()));
+    let (target_id, target_name, reason) = match split_name_prefix(candidates, rest) {
+        Some(target) => target,
+        None if rest.is_empty() => return Err(message_gm_usage(lang).to_string()),
+        None => return Err(message_gm_player_not_found(rest, lang)),
+    };
+    kick_player(state, &target_id, reason);
+    Ok((Some(target_id), message_gm_kicked(&target_name, lang)))
+}
+
+fn gm_godmode(state: &mut GameState, sid: &str, lang: Language) -> GmResult {
+    let player = match state.players.get_mut(sid) {
+        Some(player) => player,
+        None => return Err(message_gm_usage(lang).to_string()),
+    };
+    player.god_mode = !player.god_mode;
+    Ok((None, message_gm_godmode(player.god_mode, lang).to_string()))
+}
+
+/// `/time` shows the clock, `/time 6.5`, `/time day`, `/time night` set it, and
+/// `/time reset` goes back to the real cycle.
+fn gm_time(app_state: &AppState, state: &mut GameState, rest: &str, lang: Language) -> GmResult {
+    let now_ms = now_millis();
+    let day_length = app_state.world.day_length_ms.max(1);
+    let target = match rest.to_lowercase().as_str() {
+        "" => None,
+        "reset" => {
+            state.time_offset_ms = 0;
+            None
+        }
+        "day" | "noon" => Some(0.5),
+        "night" | "midnight" => Some(0.0),
+        "dawn" | "morning" => Some(app_state.config.day_start),
+        "dusk" | "evening" => Some(app_state.config.night_start),
+        hours => match hours.parse::<f32>() {
+            Ok(hours) if (0.0..24.0).contains(&hours) => Some(hours / 24.0),
+            _ => return Err(message_gm_usage(lang).to_string()),
+        },
+    };
+    if let Some(target) = target {
+        let wanted_ms = (target * day_length as f32) as i64;
+        state.time_offset_ms = (wanted_ms - now_ms).rem_euclid(day_length);
+    }
+    let time = time_of_day(state, &app_state.world, now_ms);
+    if !rest.is_empty() {
+        broadcast_message_inline(state, world_time_message(state, &app_state.world, now_ms));
+    }
+    Ok((None, message_gm_time(time, lang)))
+}
+
+/// `/report <name> [reason]` stores the reported player's recent lines, and the
+/// reporter's, for moderators to review.
+fn handle_report(state: &mut GameState, sid: &str, reporter_name: &str, rest: &str, now_ms: i64) {
+    let lang = player_language(state, sid);
+    let on_cooldown = state
+        .players
+        .get(sid)
+        .is_some_and(|player| now_ms - player.last_report_ms < state.config.report_cooldown_ms);
+    if on_cooldown {
+        send_system_message(state, sid, message_report_cooldown(lang).to_string());
+        return;
+    }
+    let mut candidates: Vec<(String, String)> = state
+        .players
+        .values()
+        .map(|player| (player.id.clone(), player.name.clone()))
+        .collect();
+    candidates.extend(
+        state
+            .chat_log
+            .iter()
+            .map(|entry| (entry.from_id.clone(), entry.from.clone())),
+    );
+    let (target_id, target_name, reason) = match split_name_prefix(candidates.into_iter(), rest) {
+        Some(target) if target.0 != sid => target,
+        _ => {
+            send_system_message(state, sid, message_report_usage(lang).to_string());
+            return;
+        }
+    };
+    let mut context: Vec<ChatLogEntry> = state
+        .chat_log
+        .iter()
+        .rev()
+        .filter(|entry| entry.from_id == target_id || entry.from_id == sid)
+        .take(state.config.report_context_lines)
+        .cloned()
+        .collect();
+    context.reverse();
+    state.report_writes.push(ReportDoc {
+        reporter_id: sid.to_string(),
+        reporter_name: reporter_name.to_string(),
+        target_id,
+        target_name: target_name.clone(),
+        reason: reason.chars().take(200).collect(),
+        created_ms: now_ms,
+        context,
+    });
+    if let Some(player) = state.players.get_mut(sid) {
+        player.last_report_ms = now_ms;
+    }
+    send_system_message(state, sid, message_report_sent(&target_name, lang));
+}
+
+fn find_player_by_name(state: &GameState, name: &str) -> Option<String> {
+    let name = name.trim();
+    state
+        .players
+        .values()
+        .find(|player| player.name.eq_ignore_ascii_case(name))
+        .map(|player| player.id.clone())
+}
+
+fn player_name(state: &GameState, id: &str) -> String {
+    state
+        .players
+        .get(id)
+        .map(|player| player.name.clone())
+        .unwrap_or_else(|| "Wanderer".to_string())
+}
+
+fn handle_party_invite(state: &mut GameState, sid: &str, name: &str) {
+    let now_ms = now_millis();
+    let lang = player_language(state, sid);
+    let target = match find_player_by_name(state, name) {
+        Some(target) if target != sid => target,
+        _ => {
+            send_system_message(state, sid, message_player_not_found(name, lang));
+            return;
+        }
+    };
+    let target_name = player_name(state, &target);
+    if state.party_of.contains_key(&target) {
+        send_system_message(
+            state,
+            sid,
+            message_party_already_grouped(&target_name, lang),
+        );
+        return;
+    }
+    if let Some(party) = state.party_of.get(sid).and_then(|id| state.parties.get(id)) {
+        if party.leader != sid {
+            send_system_message(state, sid, message_party_not_leader(lang).to_string());
+            return;
+        }
+        if party.members.len() >= state.config.max_party_size {
+            send_system_message(state, sid, message_party_full(lang).to_string());
+            return;
+        }
+    }
+    state.party_invites.insert(
+        target.clone(),
+        PartyInvite {
+            from: sid.to_string(),
+            expires_ms: now_ms + state.config.party_invite_ttl_ms,
+        },
+    );
+    let from = player_name(state, sid);
+    if let Some(sender) = state.clients.get(&target) {
+        let _ = sender.send(ServerMessage::PartyInvite { from });
+    }
+    send_system_message(state, sid, message_party_invited(&target_name, lang));
+}
+
+fn handle_party_accept(state: &mut GameState, sid: &str) {
+    let now_ms = now_millis();
+    let lang = player_language(state, sid);
+    let invite = match state.party_invites.remove(sid) {
+        Some(invite) if invite.expires_ms > now_ms && state.players.contains_key(&invite.from) => {
+            invite
+        }
+        _ => {
+            send_system_message(state, sid, message_party_no_invite(lang).to_string());
+            return;
+        }
+    };
+    if state.party_of.contains_key(sid) {
+        remove_from_party(state, sid);
+    }
+    let party_id = match state.party_of.get(&invite.from) {
+        Some(party_id) => *party_id,
+        None => {
+            let party_id = state.next_id();
+            state.parties.insert(
+                party_id,
+                Party {
+                    id: party_id,
+                    leader: invite.from.clone(),
+                    members: vec![invite.from.clone()],
+                    loot: LootMode::default(),
+                    next_loot: 0,
+                },
+            );
+            state.party_of.insert(invite.from.clone(), party_id);
+            party_id
+        }
+    };
+    let joined = match state.parties.get_mut(&party_id) {
+        Some(party) if party.members.len() < state.config.max_party_size => {
+            party.members.push(sid.to_string());
+            true
+        }
+        _ => false,
+    };
+    if !joined {
+        send_system_message(state, sid, message_party_full(lang).to_string());
+        return;
+    }
+    state.party_of.insert(sid.to_string(), party_id);
+    let name = player_name(state, sid);
+    send_party_notice(state, party_id, |lang| message_party_joined(&name, lang));
+    send_party_update(state, party_id);
+}
+
+/// Drops a player from their party, handing leadership on and disbanding the
+/// party once a single member is left.
+fn remove_from_party(state: &mut GameState, sid: &str) {
+    let party_id = match state.party_of.remove(sid) {
+        Some(party_id) => party_id,
+        None => return,
+    };
+    if let Some(sender) = state.clients.get(sid) {
+        let _ = sender.send(ServerMessage::Party { party: None });
+    }
+    let remaining = match state.parties.get_mut(&party_id) {
+        Some(party) => {
+            party.members.retain(|member| member != sid);
+            if party.leader == sid {
+                if let Some(next) = party.members.first() {
+                    party.leader = next.clone();
+                }
+            }
+            party.members.clone()
+        }
+        None => return,
+    };
+    if remaining.len() > 1 {
+        let name = player_name(state, sid);
+        send_party_notice(state, party_id, |lang| {
+            message_party_member_left(&name, lang)
+        });
+        send_party_update(state, party_id);
+        return;
+    }
+    state.parties.remove(&party_id);
+    for member in remaining {
+        state.party_of.remove(&member);
+        if let Some(sender) = state.clients.get(&member) {
+            let _ = sender.send(ServerMessage::Party { party: None });
+        }
+        let lang = player_language(state, &member);
+        send_system_message(state, &member, message_party_disbanded(lang).to_string());
+    }
+}
+
+fn handle_party_kick(state: &mut GameState, sid: &str, name: &str) {
+    let lang = player_language(state, sid);
+    let (leader, members) = match state.party_of.get(sid).and_then(|id| state.parties.get(id)) {
+        Some(party) => (party.leader.clone(), party.members.clone()),
+        None => {
+            send_system_message(state, sid, message_party_none(lang).to_string());
+            return;
+        }
+    };
+    if leader != sid {
+        send_system_message(state, sid, message_party_not_leader(lang).to_string());
+        return;
+    }
+    let name = name.trim();
+    let target = members
+        .iter()
+        .find(|member| *member != sid && player_name(state, member).eq_ignore_ascii_case(name))
+        .cloned();
+    let target = match target {
+        Some(target) => target,
+        None => {
+            send_system_message(state, sid, message_player_not_found(name, lang));
+            return;
+        }
+    };
+    remove_from_party(state, &target);
+    let target_lang = player_language(state, &target);
+    send_system_message(
+        state,
+        &target,
+        message_party_kicked(target_lang).to_string(),
+    );
+}
+
+fn handle_party_loot(state: &mut GameState, sid: &str, mode: LootMode) {
+    let lang = player_language(state, sid);
+    let party_id = match state.party_of.get(sid) {
+        Some(party_id) => *party_id,
+        None => {
+            send_system_message(state, sid, message_party_none(lang).to_string());
+            return;
+        }
+    };
+    match state.parties.get_mut(&party_id) {
+        Some(party) if party.leader == sid => {
+            party.loot = mode;
+            party.next_loot = 0;
+        }
+        _ => {
+            send_system_message(state, sid, message_party_not_leader(lang).to_string());
+            return;
+        }
+    }
+    send_party_notice(state, party_id, |lang| {
+        message_party_loot(mode, lang).to_string()
+    });
+    send_party_update(state, party_id);
+}
+
+fn send_party_notice(state: &mut GameState, party_id: u64, text_for: impl Fn(Language) -> String) {
+    let members = match state.parties.get(&party_id) {
+        Some(party) => party.members.clone(),
+        None => return,
+    };
+    for member in members {
+        let lang = player_language(state, &member);
+        send_system_message(state, &member, text_for(lang));
+    }
+}
+
+fn party_info(state: &GameState, party: &Party) -> PartyInfo {
+    PartyInfo {
+        id: party.id,
+        leader: party.leader.clone(),
+        loot: party.loot,
+        members: party
+            .members
+            .iter()
+            .filter_map(|id| state.players.get(id))
+            .map(|player| PartyMemberInfo {
+                id: player.id.clone(),
+                name: player.name.clone(),
+                x: player.x,
+                y: player.y,
+                hp: player.hp,
+            })
+            .collect(),
+    }
+}
+
+fn send_party_update(state: &GameState, party_id: u64) {
+    let party = match state.parties.get(&party_id) {
+        Some(party) => party,
+        None => return,
+    };
+    let info = party_info(state, party);
+    for member in &party.members {
+        if let Some(sender) = state.clients.get(member) {
+            let _ = sender.send(ServerMessage::Party {
+                party: Some(info.clone()),
+            });
+        }
+    }
+}
+
+/// Pushes member positions and health to every party once a second, regardless of
+/// entity visibility, and drops invites nobody answered.
+fn update_parties(state: &mut GameState, now_ms: i64) {
+    state
+        .party_invites
+        .retain(|_, invite| invite.expires_ms > now_ms);
+    if now_ms - state.last_party_update_ms < state.config.party_update_ms {
+        return;
+    }
+    state.last_party_update_ms = now_ms;
+    let party_ids: Vec<u64> = state.parties.keys().copied().collect();
+    for party_id in party_ids {
+        send_party_update(state, party_id);
+    }
+}
+
+fn member_position(state: &GameState, award_to: Option<&Player>, id: &str) -> Option<(f32, f32)> {
+    match award_to {
+        Some(player) if player.id == id => Some((player.x, player.y)),
+        _ => state.players.get(id).map(|player| (player.x, player.y)),
+    }
+}
+
+/// Party members of `id` (including `id`) close enough to (x, y) to share in a kill.
+fn party_members_near(
+    state: &GameState,
+    award_to: Option<&Player>,
+    id: &str,
+    x: f32,
+    y: f32,
+) -> Vec<String> {
+    let members = match state
+        .party_of
+        .get(id)
+        .and_then(|party_id| state.parties.get(party_id))
+    {
+        Some(party) => party.members.clone(),
+        None => return vec![id.to_string()],
+    };
+    members
+        .into_iter()
+        .filter(|member| {
+            member == id
+                || member_position(state, award_to, member).is_some_and(|(mx, my)| {
+                    distance(mx, my, x, y) <= state.config.party_share_range
+                })
+        })
+        .collect()
+}
+
+/// Reassigns a drop won by `winner` according to their party's loot mode.
+fn party_loot_recipient(
+    state: &mut GameState,
+    award_to: Option<&Player>,
+    winner: &str,
+    x: f32,
+    y: f32,
+    rng: &mut impl Rng,
+) -> String {
+    let party_id = match state.party_of.get(winner) {
+        Some(party_id) => *party_id,
+        None => return winner.to_string(),
+    };
+    let mode = match state.parties.get(&party_id) {
+        Some(party) => party.loot,
+        None => return winner.to_string(),
+    };
+    let eligible = party_members_near(state, award_to, winner, x, y);
+    match (mode, state.parties.get_mut(&party_id)) {
+        (LootMode::RoundRobin, Some(party)) => {
+            let pick = eligible[party.next_loot % eligible.len()].clone();
+            party.next_loot += 1;
+            pick
+        }
+        (LootMode::Random, _) => eligible[rng.gen_range(0..eligible.len())].clone(),
+        _ => winner.to_string(),
+    }
+}
+
+fn guild_rank_allows(guild: &GuildDoc, player_id: &str, permission: GuildPermission) -> bool {
+    match guild.rank_of(player_id) {
+        Some(GuildRank::Leader) => true,
+        Some(GuildRank::Officer) => guild.officer_permissions.contains(&permission),
+        Some(GuildRank::Member) => guild.member_permissions.contains(&permission),
+        None => false,
+    }
+}
+
+/// Whether a player may demolish a structure: they own it, or it belongs
+/// to their guild and their rank grants the permission.
+fn can_manage_structure(
+    state: &GameState,
+    player_id: &str,
+    structure: &StructureTile,
+    permission: GuildPermission,
+) -> bool {
+    if structure.owner_id == player_id {
+        return true;
+    }
+    match (&structure.guild_id, state.guild_of.get(player_id)) {
+        (Some(structure_guild), Some(player_guild)) if structure_guild == player_guild => state
+            .guilds
+            .get(player_guild)
+            .is_some_and(|guild| guild_rank_allows(guild, player_id, permission)),
+        _ => false,
+    }
+}
+
+fn normalize_guild_tag(input: &str) -> Option<String> {
+    let tag = input.trim().to_ascii_uppercase();
+    let valid = (2..=4).contains(&tag.len()) && tag.chars().all(|ch| ch.is_ascii_alphanumeric());
+    valid.then_some(tag)
+}
+
+fn normalize_guild_name(input: &str, config: &GameplayConfig) -> Option<String> {
+    let cleaned: String = input.trim().chars().filter(|ch| !ch.is_control()).collect();
+    let cleaned = cleaned.trim();
+    let len = cleaned.chars().count();
+    (3..=config.max_guild_name_chars)
+        .contains(&len)
+        .then(|| cleaned.to_string())
+}
+
+fn player_guild(state: &GameState, player_id: &str) -> Option<String> {
+    state.guild_of.get(player_id).cloned()
+}
+
+/// Keeps a member's cached name and the player's visible guild tag in sync, e.g.
+/// after they connect or rename.
+fn refresh_guild_member(state: &mut GameState, player_id: &str) {
+    let guild_id = player_guild(state, player_id);
+    let tag = guild_id
+        .as_ref()
+        .and_then(|id| state.guilds.get(id))
+        .map(|guild| guild.tag.clone());
+    let name = match state.players.get_mut(player_id) {
+        Some(player) => {
+            player.guild_tag = tag;
+            player.name.clone()
+        }
+        None => return,
+    };
+    let guild = match guild_id.and_then(|id| state.guilds.get_mut(&id)) {
+        Some(guild) => guild,
+        None => return,
+    };
+    if let Some(member) = guild
+        .members
+        .iter_mut()
+        .find(|member| member.id == player_id)
+    {
+        if member.name != name {
+            member.name = name;
+            let doc = guild.clone();
+            state.guild_writes.push(GuildWrite::Save(doc));
+        }
+    }
+}
+
+fn guild_info(state: &GameState, guild: &GuildDoc) -> GuildInfo {
+    GuildInfo {
+        id: guild.id.clone(),
+        name: guild.name.clone(),
+        tag: guild.tag.clone(),
+        members: guild
+            .members
+            .iter()
+            .map(|member| GuildMemberInfo {
+                id: member.id.clone(),
+                name: member.name.clone(),
+                rank: member.rank,
+                online: state.players.contains_key(&member.id),
+            })
+            .collect(),
+        officer_permissions: guild.officer_permissions.clone(),
+        member_permissions: guild.member_permissions.clone(),
+    }
+}
+
+fn send_guild_update(state: &GameState, guild_id: &str) {
+    let guild = match state.guilds.get(guild_id) {
+        Some(guild) => guild,
+        None => return,
+    };
+    let info = guild_info(state, guild);
+    for member in &guild.members {
+        if let Some(sender) = state.clients.get(&member.id) {
+            let _ = sender.send(ServerMessage::Guild {
+                guild: Some(info.clone()),
+            });
+        }
+    }
+}
+
+fn send_guild_notice(state: &mut GameState, guild_id: &str, text_for: impl Fn(Language) -> String) {
+    let members: Vec<String> = match state.guilds.get(guild_id) {
+        Some(guild) => guild
+            .members
+            .iter()
+            .map(|member| member.id.clone())
+            .collect(),
+        None => return,
+    };
+    for member in members {
+        let lang = player_language(state, &member);
+        send_system_message(state, &member, text_for(lang));
+    }
+}
+
+fn save_guild(state: &mut GameState, guild_id: &str) {
+    if let Some(guild) = state.guilds.get(guild_id) {
+        let doc = guild.clone();
+        state.guild_writes.push(GuildWrite::Save(doc));
+    }
+}
+
+fn handle_guild_create(state: &mut GameState, sid: &str, name: &str, tag: &str) {
+    let lang = player_language(state, sid);
+    if state.guild_of.contains_key(sid) {
+        send_system_message(state, sid, message_guild_already_member(lang).to_string());
+        return;
+    }
+    let name = match normalize_guild_name(name, &state.config) {
+        Some(name) => name,
+        None => {
+            send_system_message(state, sid, message_guild_name_invalid(lang).to_string());
+            return;
+        }
+    };
+    let tag = match normalize_guild_tag(tag) {
+        Some(tag) => tag,
+        None => {
+            send_system_message(state, sid, message_guild_tag_invalid(lang).to_string());
+            return;
+        }
+    };
+    if state
+        .guilds
+        .values()
+        .any(|guild| guild.tag == tag || guild.name.eq_ignore_ascii_case(&name))
+    {
+        send_system_message(state, sid, message_guild_taken(lang).to_string());
+        return;
+    }
+    let guild = GuildDoc {
+        id: Uuid::new_v4().to_string(),
+        name: name.clone(),
+        tag,
+        members: vec![GuildMember {
+            id: sid.to_string(),
+            name: player_name(state, sid),
+            rank: GuildRank::Leader,
+        }],
+        officer_permissions: default_officer_permissions(),
+        member_permissions: default_member_permissions(),
+    };
+    let guild_id = guild.id.clone();
+    state.guild_of.insert(sid.to_string(), guild_id.clone());
+    state.guilds.insert(guild_id.clone(), guild);
+    save_guild(state, &guild_id);
+    refresh_guild_member(state, sid);
+    send_system_message(state, sid, message_guild_created(&name, lang));
+    send_guild_update(state, &guild_id);
+}
+
+fn handle_guild_invite(state: &mut GameState, sid: &str, name: &str) {
+    let now_ms = now_millis();
+    let lang = player_language(state, sid);
+    let guild_id = match player_guild(state, sid) {
+        Some(guild_id) => guild_id,
+        None => {
+            send_system_message(state, sid, message_guild_none(lang).to_string());
+            return;
+        }
+    };
+    let (allowed, guild_name) = match state.guilds.get(&guild_id) {
+        Some(guild) => (
+            guild_rank_allows(guild, sid, GuildPermission::Invite),
+            guild.name.clone(),
+        ),
+        None => return,
+    };
+    if !allowed {
+        send_system_message(state, sid, message_guild_no_permission(lang).to_string());
+        return;
+    }
+    let target = match find_player_by_name(state, name) {
+        Some(target) if target != sid => target,
+        _ => {
+            send_system_message(state, sid, message_player_not_found(name, lang));
+            return;
+        }
+    };
+    let target_name = player_name(state, &target);
+    if state.guild_of.contains_key(&target) {
+        send_system_message(state, sid, message_guild_player_taken(&target_name, lang));
+        return;
+    }
+    state.guild_invites.insert(
+        target.clone(),
+        GuildInvite {
+            guild_id,
+            expires_ms: now_ms + state.config.guild_invite_ttl_ms,
+        },
+    );
+    let from = player_name(state, sid);
+    if let Some(sender) = state.clients.get(&target) {
+        let _ = sender.send(ServerMessage::GuildInvite {
+            from,
+            guild: guild_name,
+        });
+    }
+    send_system_message(state, sid, message_guild_invited(&target_name, lang));
+}
+
+fn handle_guild_accept(state: &mut GameState, sid: &str) {
+    let now_ms = now_millis();
+    let lang = player_language(state, sid);
+    if state.guild_of.contains_key(sid) {
+        send_system_message(state, sid, message_guild_already_member(lang).to_string());
+        return;
+    }
+    let invite = match state.guild_invites.remove(sid) {
+        Some(invite) if invite.expires_ms > now_ms => invite,
+        _ => {
+            send_system_message(state, sid, message_guild_no_invite(lang).to_string());
+            return;
+        }
+    };
+    let name = player_name(state, sid);
+    let guild = match state.guilds.get_mut(&invite.guild_id) {
+        Some(guild) if guild.members.len() < state.config.max_guild_members => guild,
+        _ => {
+            send_system_message(state, sid, message_guild_no_invite(lang).to_string());
+            return;
+        }
+    };
+    guild.members.push(GuildMember {
+        id: sid.to_string(),
+        name: name.clone(),
+        rank: GuildRank::Member,
+    });
+    state
+        .guild_of
+        .insert(sid.to_string(), invite.guild_id.clone());
+    save_guild(state, &invite.guild_id);
+    refresh_guild_member(state, sid);
+    send_guild_notice(state, &invite.guild_id, |lang| {
+        message_guild_joined(&name, lang)
+    });
+    send_guild_update(state, &invite.guild_id);
+}
+
+/// Removes a member; a departing leader hands over to the highest-ranked member
+/// left, and the last member leaving disbands the guild and returns its claimed
+/// structures to their builders.
+fn remove_from_guild(state: &mut GameState, player_id: &str) {
+    let guild_id = match state.guild_of.remove(player_id) {
+        Some(guild_id) => guild_id,
+        None => return,
+    };
+    if let Some(player) = state.players.get_mut(player_id) {
+        player.guild_tag = None;
+    }
+    if let Some(sender) = state.clients.get(player_id) {
+        let _ = sender.send(ServerMessage::Guild { guild: None });
+    }
+    let guild = match state.guilds.get_mut(&guild_id) {
+        Some(guild) => guild,
+        None => return,
+    };
+    let name = guild
+        .members
+        .iter()
+        .find(|member| member.id == player_id)
+        .map(|member| member.name.clone())
+        .unwrap_or_default();
+    guild.members.retain(|member| member.id != player_id);
+    if !guild.members.is_empty() {
+        if !guild
+            .members
+            .iter()
+            .any(|member| member.rank == GuildRank::Leader)
+        {
+            if let Some(next) = guild.members.iter_mut().max_by_key(|member| member.rank) {
+                next.rank = GuildRank::Leader;
+            }
+        }
+        save_guild(state, &guild_id);
+        send_guild_notice(state, &guild_id, |lang| {
+            message_guild_member_left(&name, lang)
+        });
+        send_guild_update(state, &guild_id);
+        return;
+    }
+
+    state.guilds.remove(&guild_id);
+    state
+        .guild_writes
+        .push(GuildWrite::Delete(guild_id.clone()));
+    let mut released = HashSet::new();
+    for structure in state.structure_tiles.values_mut() {
+        if structure.guild_id.as_deref() == Some(guild_id.as_str()) {
+            structure.guild_id = None;
+            released.insert(structure.id);
+        }
+    }
+    for id in released {
+        state
+            .guild_writes
+            .push(GuildWrite::StructureGuild { id, guild_id: None });
+    }
+}
+
+fn handle_guild_kick(state: &mut GameState, sid: &str, name: &str) {
+    let lang = player_language(state, sid);
+    let guild_id = match player_guild(state, sid) {
+        Some(guild_id) => guild_id,
+        None => {
+            send_system_message(state, sid, message_guild_none(lang).to_string());
+            return;
+        }
+    };
+    let guild = match state.guilds.get(&guild_id) {
+        Some(guild) => guild,
+        None => return,
+    };
+    let target = guild
+        .members
+        .iter()
+        .find(|member| member.id != sid && member.name.eq_ignore_ascii_case(name.trim()))
+        .map(|member| (member.id.clone(), member.rank));
+    let (target, target_rank) = match target {
+        Some(target) => target,
+        None => {
+            send_system_message(state, sid, message_player_not_found(name, lang));
+            return;
+        }
+    };
+    let allowed = guild_rank_allows(guild, sid, GuildPermission::Kick)
+        && guild.rank_of(sid).is_some_and(|rank| rank > target_rank);
+    if !allowed {
+        send_system_message(state, sid, message_guild_no_permission(lang).to_string());
+        return;
+    }
+    remove_from_guild(state, &target);
+    let target_lang = player_language(state, &target);
+    send_system_message(
+        state,
+        &target,
+        message_guild_kicked(target_lang).to_string(),
+    );
+}
+
+fn handle_guild_rank(state: &mut GameState, sid: &str, name: &str, rank: GuildRank) {
+    let lang = player_language(state, sid);
+    let guild_id = match player_guild(state, sid) {
+        Some(guild_id) => guild_id,
+        None => {
+            send_system_message(state, sid, message_guild_none(lang).to_string());
+            return;
+        }
+    };
+    let guild = match state.guilds.get_mut(&guild_id) {
+        Some(guild) => guild,
+        None => return,
+    };
+    let actor_rank = guild.rank_of(sid).unwrap_or(GuildRank::Member);
+    let target_index = guild
+        .members
+        .iter()
+        .position(|member| member.id != sid && member.name.eq_ignore_ascii_case(name.trim()));
+    let target_index = match target_index {
+        Some(index) => index,
+        None => {
+            send_system_message(state, sid, message_player_not_found(name, lang));
+            return;
+        }
+    };
+    let target_rank = guild.members[target_index].rank;
+    // Only the leader can hand over leadership; everyone else can only move members
+    // strictly below their own rank to another rank below it.
+    let allowed = if rank == GuildRank::Leader {
+        actor_rank == GuildRank::Leader
+    } else {
+        guild_rank_allows(guild, sid, GuildPermission::Promote)
+            && actor_rank > target_rank
+            && actor_rank > rank
+    };
+    if !allowed {
+        send_system_message(state, sid, message_guild_no_permission(lang).to_string());
+        return;
+    }
+    if rank == GuildRank::Leader {
+        if let Some(actor) = guild.members.iter_mut().find(|member| member.id == sid) {
+            actor.rank = GuildRank::Officer;
+        }
+    }
+    guild.members[target_index].rank = rank;
+    let target_name = guild.members[target_index].name.clone();
+    save_guild(state, &guild_id);
+    send_guild_notice(state, &guild_id, |lang| {
+        message_guild_rank_changed(&target_name, rank, lang)
+    });
+    send_guild_update(state, &guild_id);
+}
+
+fn handle_guild_permission(
+    state: &mut GameState,
+    sid: &str,
+    rank: GuildRank,
+    permission: GuildPermission,
+    allowed: bool,
+) {
+    let lang = player_language(state, sid);
+    let guild_id = match player_guild(state, sid) {
+        Some(guild_id) => guild_id,
+        None => {
+            send_system_message(state, sid, message_guild_none(lang).to_string());
+            return;
+        }
+    };
+    let guild = match state.guilds.get_mut(&guild_id) {
+        Some(guild) if guild.rank_of(sid) == Some(GuildRank::Leader) => guild,
+        _ => {
+            send_system_message(state, sid, message_guild_no_permission(lang).to_string());
+            return;
+        }
+    };
+    let permissions = match rank {
+        GuildRank::Officer => &mut guild.officer_permissions,
+        GuildRank::Member => &mut guild.member_permissions,
+        GuildRank::Leader => return,
+    };
+    permissions.retain(|existing| *existing != permission);
+    if allowed {
+        permissions.push(permission);
+    }
+    save_guild(state, &guild_id);
+    send_guild_update(state, &guild_id);
+}
+
+/// Hands a structure the player built over to their guild.
+fn handle_guild_claim(state: &mut GameState, sid: &str, x: i32, y: i32) {
+    let lang = player_language(state, sid);
+    let guild_id = match player_guild(state, sid) {
+        Some(guild_id) => guild_id,
+        None => {
+            send_system_message(state, sid, message_guild_none(lang).to_string());
+            return;
+        }
+    };
+    let structure = match state.structure_tiles.get(&TileCoord { x, y }) {
+        Some(structure) => structure.clone(),
+        None => {
+            send_system_message(state, sid, message_nothing_to_claim(lang).to_string());
+            return;
+        }
+    };
+    if structure.guild_id.as_deref() == Some(guild_id.as_str()) {
+        send_system_message(state, sid, message_guild_already_claimed(lang).to_string());
+        return;
+    }
+    if structure.owner_id != sid {
+        send_system_message(state, sid, message_claim_own_only(lang).to_string());
+        return;
+    }
+    for tile in state.structure_tiles.values_mut() {
+        if tile.id == structure.id {
+            tile.guild_id = Some(guild_id.clone());
+        }
+    }
+    state.guild_writes.push(GuildWrite::StructureGuild {
+        id: structure.id,
+        guild_id: Some(guild_id.clone()),
+    });
+    let guild_name = state
+        .guilds
+        .get(&guild_id)
+        .map(|guild| guild.name.clone())
+        .unwrap_or_default();
+    send_system_message(state, sid, message_structure_claimed(&guild_name, lang));
+}
+
+fn try_ranged_attack(
+    player: &mut Player,
+    state: &mut GameState,
+    data: &GameData,
+    now_ms: i64,
+    input: &InputState,
+    world: &WorldConfig,
+) -> bool {
+    let (weapon, ammo_id) = match best_ranged_weapon(&player.inventory, data) {
+        Some(result) => result,
+        None => return false,
+    };
+
+    if now_ms - player.last_attack_ms < weapon.cooldown_ms {
+        return false;
+    }
+
+    let target_pos = input
+        .target_id
+        .and_then(|id| state.monsters.get(&id))
+        .map(|monster| (monster.x, monster.y))
+        .or_else(|| {
+            input
+                .target_player
+                .as_ref()
+                .and_then(|id| state.players.get(id))
+                .filter(|victim| victim.hp > 0 && pvp_allowed(world, &state.config, player, victim))
+                .map(|victim| (victim.x, victim.y))
+        });
+    // Failed shots still use up the cooldown so a held attack key does not repeat the
+    // message every tick.
+    let aim = match target_pos {
+        Some((tx, ty)) => {
+            let lang = player_language(state, &player.id);
+            if distance(player.x, player.y, tx, ty) > weapon.range {
+                player.last_attack_ms = now_ms;
+                send_system_message(
+                    state,
+                    &player.id,
+                    message_target_out_of_range(lang).to_string(),
+                );
+                return false;
+            }
+            if !has_line_of_sight(state, data, world.chunk_size, player.x, player.y, tx, ty) {
+                player.last_attack_ms = now_ms;
+                send_system_message(state, &player.id, message_no_clear_shot(lang).to_string());
+                return false;
+            }
+            Some((tx - player.x, ty - player.y))
+        }
+        None => input.aim,
+    };
+
+    if !consume_item(&mut player.inventory, &ammo_id, 1) {
+        player.last_attack_ms = now_ms;
+        let lang = player_language(state, &player.id);
+        let item_name = localize_item_name(data, &ammo_id, lang);
+        send_system_message(state, &player.id, message_out_of(&item_name, lang));
+        return false;
+    }
+
+    let speed = weapon.projectile_speed.unwrap_or(7.0);
+    let (face_x, face_y) = aim
+        .filter(|(x, y)| x * x + y * y > 0.0001)
+        .unwrap_or((player.face_x, player.face_y));
+    let dir_len = (face_x * face_x + face_y * face_y).sqrt();
+    let (dir_x, dir_y) = if dir_len > 0.01 {
+        (face_x / dir_len, face_y / dir_len)
+    } else {
+        (1.0, 0.0)
+    };
+
+    let proj_id = state.next_id();
+    state.projectiles.insert(
+        proj_id,
+        Projectile {
+            id: proj_id,
+            kind: ammo_id,
+            x: player.x + dir_x * 0.6,
+            y: player.y + dir_y * 0.6,
+            vx: dir_x * speed,
+            vy: dir_y * speed,
+            ttl_ms: 1200,
+            damage: weapon.damage,
+            owner: ProjectileOwner::Player(player.id.clone()),
+            hits: Faction::Monsters,
+        },
+    );
+
+    true
+}
+
+fn handle_monster_death(
+    state: &mut GameState,
+    monster_id: u64,
+    data: &GameData,
+    mut award_to: Option<&mut Player>,
+    now_ms: i64,
+) {
+    let monster = match state.monsters.remove(&monster_id) {
+        Some(monster) => monster,
+        None => return,
+    };
+    release_monster_home(&mut state.chunk_monsters, monster.home);
+    // Bosses run on their own timer and don't hold back the chunk's population.
+    if !monster.boss {
+        state
+            .chunk_respawn_at
+            .insert(monster.home, now_ms + state.config.monster_respawn_ms);
+    }
+    let def = match data.monsters.get(&monster.kind) {
+        Some(def) => def,
+        None => return,
+    };
+
+    let mut contributors: Vec<(String, i32)> = monster
+        .damage_by
+        .iter()
+        .filter(|(_, damage)| **damage > 0)
+        .map(|(id, damage)| (id.clone(), *damage))
+        .collect();
+    contributors.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    if contributors.is_empty() {
+        let nearby = match award_to.as_deref() {
+            Some(player) => Some(player.id.clone()),
+            None => state
+                .players
+                .values()
+                .find(|player| distance(player.x, player.y, monster.x, monster.y) < 2.0)
+                .map(|player| player.id.clone()),
+        };
+        contributors.extend(nearby.map(|id| (id, 1)));
+    }
+
+    // Everyone who hurt the monster, and their party members nearby, gets quest
+    // credit for the kill if they are on a quest that asks for it.
+    let wanted: Vec<&str> = data
+        .quests_by_npc
+        .values()
+        .filter(|quest| quest.kills.iter().any(|kill| kill.monster == monster.kind))
+        .map(|quest| quest.id.as_str())
+        .collect();
+    if !wanted.is_empty() {
+        let mut credited: Vec<String> = Vec::new();
+        for (id, _) in &contributors {
+            for member in party_members_near(state, award_to.as_deref(), id, monster.x, monster.y) {
+                if !credited.contains(&member) {
+                    credited.push(member);
+                }
+            }
+        }
+        for id in credited {
+            let player = match award_to.as_deref_mut() {
+                Some(player) if player.id == id => Some(player),
+                _ => state.players.get_mut(&id),
+            };
+            if let Some(player) = player.filter(|player| {
+                wanted
+                    .iter()
+                    .any(|quest_id| player.active_quests.contains(*quest_id))
+            }) {
+                *player.quest_kills.entry(monster.kind.clone()).or_insert(0) += 1;
+            }
+        }
+    }
+
+    if def.boss {
+        let names: Vec<String> = contributors
+            .iter()
+            .filter_map(|(id, _)| match award_to.as_deref() {
+                Some(player) if player.id == *id => Some(player.name.clone()),
+                _ => state.players.get(id).map(|player| player.name.clone()),
+            })
+            .collect();
+        let names = names.join(", ");
+        broadcast_localized(state, |lang| {
+            message_boss_defeated(&localize_monster_name(data, &def.id, lang), &names, lang)
+        });
+    }
+
+    // Each dropped item goes to one contributor, picked with odds proportional to the
+    // damage they dealt; the winner's party loot mode may then hand it to a teammate.
+    let total_damage: i32 = contributors.iter().map(|(_, damage)| damage).sum();
+    if total_damage <= 0 {
+        return;
+    }
+    let mut rng = rand::thread_rng();
+    let mut awarded: Vec<(String, String, i32)> = Vec::new();
+    for drop in def.drop.iter().chain(def.loot.iter()) {
+        if drop.chance.is_some_and(|chance| rng.gen::<f32>() > chance) {
+            continue;
+        }
+        for _ in 0..drop.count {
+            let mut roll = rng.gen_range(0..total_damage);
+            let mut winner = &contributors[0].0;
+            for (id, damage) in &contributors {
+                if roll < *damage {
+                    winner = id;
+                    break;
+                }
+                roll -= damage;
+            }
+            let winner = party_loot_recipient(
+                state,
+                award_to.as_deref(),
+                winner,
+                monster.x,
+                monster.y,
+                &mut rng,
+            );
+            match awarded
+                .iter_mut()
+                .find(|(player_id, item_id, _)| *player_id == winner && *item_id == drop.id)
+            {
+                Some(entry) => entry.2 += 1,
+                None => awarded.push((winner.clone(), drop.id.clone(), 1)),
+            }
+        }
+    }
+
+    for (player_id, item_id, count) in awarded {
+        let given = match award_to.as_deref_mut() {
+            Some(player) if player.id == player_id => {
+                add_item(&mut player.inventory, &item_id, count);
+                true
+            }
+            _ => match state.players.get_mut(&player_id) {
+                Some(player) => {
+                    add_item(&mut player.inventory, &item_id, count);
+                    true
+                }
+                None => false,
+            },
+        };
+        if given {
+            let lang = player_language(state, &player_id);
+            let item_name = localize_item_name(data, &item_id, lang);
+            send_system_message(
+                state,
+                &player_id,
+                message_picked_up(&item_name, count, lang),
+            );
+        }
+    }
+}
+
+fn handle_npc_interaction(
+    player: &mut Player,
+    npc: &NpcDef,
+    state: &mut GameState,
+    data: &GameData,
+) {
+    let lang = player_language(state, &player.id);
+    let npc_name = localize_npc_name(npc, lang);
+    let npc_dialog = localize_npc_dialog(npc, lang);
+    if let Some(quest) = data.quests_by_npc.get(&npc.id) {
+        if player.completed_quests.contains(&quest.id) {
+            send_dialog(state, &player.id, &npc_name, &message_thanks_again(lang));
+            return;
+        }
+
+        if has_items(&player.inventory, &quest.requires)
+            && has_kills(&player.quest_kills, &quest.kills)
+        {
+            remove_items(&mut player.inventory, &quest.requires);
+            for kill in &quest.kills {
+                if let Some(count) = player.quest_kills.get_mut(&kill.monster) {
+                    *count -= kill.count;
+                    if *count <= 0 {
+                        player.quest_kills.remove(&kill.monster);
+                    }
+                }
+            }
+            for reward in &quest.rewards {
+                add_item(&mut player.inventory, &reward.id, reward.count);
+            }
+            player.completed_quests.insert(quest.id.clone());
+            player.active_quests.remove(&quest.id);
+            send_dialog(
+                state,
+                &player.id,
+                &npc_name,
+                &message_quest_complete(
+                    &localize_quest_name(quest, lang),
+                    &localize_quest_description(quest, lang),
+                    lang,
+                ),
+            );
+            for reward in &quest.rewards {
+                let lang = player_language(state, &player.id);
+                let item_name = localize_item_name(data, &reward.id, lang);
+                send_system_message(
+                    state,
+                    &player.id,
+                    message_reward(&item_name, reward.count, lang),
+                );
+            }
+        } else {
+            if !quest.kills.is_empty() {
+                player.active_quests.insert(quest.id.clone());
+            }
+            let mut needs = Vec::new();
+            for req in &quest.requires {
+                let have = player.inventory.get(&req.id).copied().unwrap_or(0);
+                let lang = player_language(state, &player.id);
+                let item_name = localize_item_name(data, &req.id, lang);
+                needs.push(format!("{} {}/{}", item_name, have, req.count));
+            }
+            for kill in &quest.kills {
+                let have = player.quest_kills.get(&kill.monster).copied().unwrap_or(0);
+                let monster_name = localize_monster_name(data, &kill.monster, lang);
+                needs.push(message_kill_progress(
+                    &monster_name,
+                    have.min(kill.count),
+                    kill.count,
+                    lang,
+                ));
+            }
+            send_dialog(
+                state,
+                &player.id,
+                &npc_name,
+                &message_quest_needs(
+                    &localize_quest_name(quest, lang),
+                    &localize_quest_description(quest, lang),
+                    &needs.join(", "),
+                    lang,
+                ),
+            );
+        }
+    } else {
+        send_dialog(state, &player.id, &npc_name, &npc_dialog);
+    }
+}
+
+fn send_dialog(state: &mut GameState, player_id: &str, title: &str, text: &str) {
+    if let Some(sender) = state.clients.get(player_id) {
+        let _ = sender.send(ServerMessage::Dialog {
+            title: title.to_string(),
+            text: text.to_string(),
+        });
+    }
+}
+
+fn send_system_message(state: &mut GameState, player_id: &str, text: String) {
+    if let Some(sender) = state.clients.get(player_id) {
+        let _ = sender.send(ServerMessage::System { text });
+    }
+}
+
+fn find_nearby_resource<'a>(
+    player: &Player,
+    state: &'a mut GameState,
+    data: &'a GameData,
+) -> Option<(&'a mut ResourceNode, &'a ResourceDef)> {
+    for resources in state.resources.values_mut() {
+        for res in resources.iter_mut() {
+            if res.hp <= 0 {
+                continue;
+            }
+            let dist = distance(player.x, player.y, res.x as f32 + 0.5, res.y as f32 + 0.5);
+            if dist <= state.config.gather_range {
+                if let Some(def) = data.resources.get(&res.kind) {
+                    return Some((res, def));
+                }
+            }
+        }
+    }
+    None
+}
+
+fn find_nearby_npc<'a>(
+    player: &Player,
+    data: &'a GameData,
+    config: &GameplayConfig,
+) -> Option<&'a NpcDef> {
+    for npc in &data.npcs {
+        let dist = distance(player.x, player.y, npc.x, npc.y);
+        if dist <= config.interact_range {
+            return Some(npc);
+        }
+    }
+    None
+}
+
+fn find_nearby_boat(player: &Player, state: &GameState) -> Option<Boat> {
+    for boat in state.boats.values() {
+        if distance(player.x, player.y, boat.x, boat.y) <= state.config.interact_range {
+            return Some(boat.clone());
+        }
+    }
+    None
+}
+
+fn best_tool_power(
+    inventory: &HashMap<String, i32>,
+    data: &GameData,
+    tool: &str,
+) -> Option<i32> {
+    let mut best = None;
+    for (item_id, count) in inventory {
+        if *count <= 0 {
+            continue;
+        }
+        if let Some(def) = data.items.get(item_id) {
+            if def.tool.as_deref() == Some(tool) {
+                let power = def.power.unwrap_or(1);
+                if best.map_or(true, |current| power > current) {
+                    best = Some(power);
+                }
+            }
+        }
+    }
+    best
+}
+
+fn best_melee_weapon(inventory: &HashMap<String, i32>, data: &GameData) -> Option<WeaponStats> {
+    let mut best: Option<WeaponStats> = None;
+    for (item_id, count) in inventory {
+        if *count <= 0 {
+            continue;
+        }
+        if let Some(def) = data.items.get(item_id) {
+            if let Some(weapon) = &def.weapon {
+                if weapon.kind == "melee" {
+                    if best
+                        .as_ref()
+                        .map_or(true, |current| weapon.damage > current.damage)
+                    {
+                        best = Some(weapon.clone());
+                    }
+                }
+            }
+        }
+    }
+    best
+}
+
+fn best_ranged_weapon(
+    inventory: &HashMap<String, i32>,
+    data: &GameData,
+) -> Option<(WeaponStats, String)> {
+    let mut best: Option<(WeaponStats, String)> = None;
+    for (item_id, count) in inventory {
+        if *count <= 0 {
+            continue;
+        }
+        if let Some(def) = data.items.get(item_id) {
+            if let Some(weapon) = &def.weapon {
+                if weapon.kind == "ranged" {
+                    let ammo_id = def.ammo_for.clone().unwrap_or_else(|| "arrow".to_string());
+                    if best
+                        .as_ref()
+                        .map_or(true, |current| weapon.damage > current.0.damage)
+                    {
+                        best = Some((weapon.clone(), ammo_id));
+                    }
+                }
+            }
+        }
+    }
+    best
+}
+
+fn has_items(inventory: &HashMap<String, i32>, items: &[ItemStack]) -> bool {
+    items.iter().all(|item| inventory.get(&item.id).copied().unwrap_or(0) >= item.count)
+}
+
+fn has_kills(kills: &HashMap<String, i32>, required: &[KillRequirement]) -> bool {
+    required
+        .iter()
+        .all(|kill| kills.get(&kill.monster).copied().unwrap_or(0) >= kill.count)
+}
+
+fn remove_items(inventory: &mut HashMap<String, i32>, items: &[ItemStack]) -> bool {
+    if !has_items(inventory, items) {
+        return false;
+    }
+    for item in items {
+        consume_item(inventory, &item.id, item.count);
+    }
+    true
+}
+
+fn add_item(inventory: &mut HashMap<String, i32>, item_id: &str, count: i32) {
+    let entry = inventory.entry(item_id.to_string()).or_insert(0);
+    *entry += count;
+}
+
+fn consume_item(inventory: &mut HashMap<String, i32>, item_id: &str, count: i32) -> bool {
+    let entry = inventory.entry(item_id.to_string()).or_insert(0);
+    if *entry < count {
+        return false;
+    }
+    *entry -= count;
+    true
+}
+
+fn inventory_hash(inventory: &HashMap<String, i32>) -> u64 {
+    use std::collections::hash_map::DefaultHasher;
+    let mut entries: Vec<_> = inventory.iter().collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    let mut hasher = DefaultHasher::new();
+    for (id, count) in entries {
+        id.hash(&mut hasher);
+        count.hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+fn build_inventory_items(
+    inventory: &HashMap<String, i32>,
+    data: &GameData,
+    lang: Language,
+) -> Vec<InventoryItem> {
+    let mut items = Vec::new();
+    for (id, count) in inventory {
+        if *count <= 0 {
+            continue;
+        }
+        let (name, heal, plants) = match data.items.get(id) {
+            Some(def) => (
+                localize_item_name(data, &def.id, lang),
+                def.heal,
+                def.plants.clone(),
+            ),
+            None => (id.clone(), None, None),
+        };
+        items.push(InventoryItem {
+            id: id.clone(),
+            name,
+            count: *count,
+            heal,
+            plants,
+        });
+    }
+    items.sort_by(|a, b| a.name.cmp(&b.name));
+    items
+}
+
+fn player_language(state: &GameState, player_id: &str) -> Language {
+    state.locales.get(player_id).copied().unwrap_or(Language::En)
+}
+
+fn localize_item_name(data: &GameData, item_id: &str, lang: Language) -> String {
+    if lang != Language::De {
+        return data
+            .items
+            .get(item_id)
+            .map(|def| def.name.clone())
+            .unwrap_or_else(|| item_id.to_string());
+    }
+    let localized = match item_id {
+        "wood" => "Holz",
+        "apple" => "Apfel",
+        "stone" => "Stein",
+        "boar_leg" => "Wildschweinkeule",
+        "rabbit_leg" => "Kaninchenkeule",
+        "slime_core" => "Schleimkern",
+        "wolf_pelt" => "Wolfsfell",
+        "arrow" => "Pfeil",
+        "basic_axe" => "Holzaxt",
+        "fine_axe" => "Gute Axt",
+        "basic_pick" => "Holzspitzhacke",
+        "basic_shovel" => "Schaufel",
+        "fishing_rod" => "Angel",
+        "fish" => "Fisch",
+        "rusty_sword" => "Rostiges Schwert",
+        "iron_sword" => "Eisenschwert",
+        "bow" => "Bogen",
+        "sapling" => "Setzling",
+        "wheat_seeds" => "Weizensamen",
+        "wheat" => "Weizen",
+        _ => return data
+            .items
+            .get(item_id)
+            .map(|def| def.name.clone())
+            .unwrap_or_else(|| item_id.to_string()),
+    };
+    localized.to_string()
+}
+
+fn localize_resource_name(data: &GameData, resource_id: &str, lang: Language) -> String {
+    if lang != Language::De {
+        return data
+            .resources
+            .get(resource_id)
+            .map(|def| def.name.clone())
+            .unwrap_or_else(|| resource_id.to_string());
+    }
+    let localized = match resource_id {
+        "tree" => "Baum",
+        "apple_tree" => "Apfelbaum",
+        "pine_tree" => "Kiefer",
+        "palm_tree" => "Palme",
+        "rock" => "Fels",
+        "wheat" => "Weizen",
+        _ => return data
+            .resources
+            .get(resource_id)
+            .map(|def| def.name.clone())
+            .unwrap_or_else(|| resource_id.to_string()),
+    };
+    localized.to_string()
+}
+
+fn localize_monster_name(data: &GameData, monster_id: &str, lang: Language) -> String {
+    if lang != Language::De {
+        return data
+            .monsters
+            .get(monster_id)
+            .map(|def| def.name.clone())
+            .unwrap_or_else(|| monster_id.to_string());
+    }
+    let localized = match monster_id {
+        "boar" => "Wildschwein",
+        "rabbit" => "Kaninchen",
+        "slime" => "Schleim",
+        "wolf" => "Wolf",
+        "goblin_archer" => "Goblin-Bogenschütze",
+        "forest_troll" => "Waldtroll",
+        _ => return data
+            .monsters
+            .get(monster_id)
+            .map(|def| def.name.clone())
+            .unwrap_or_else(|| monster_id.to_string()),
+    };
+    localized.to_string()
+}
+
+fn localize_tool_name(tool: &str, lang: Language) -> String {
+    if lang != Language::De {
+        return tool.to_string();
+    }
+    let localized = match tool {
+        "axe" => "Axt",
+        "pick" => "Spitzhacke",
+        "shovel" => "Schaufel",
+        _ => tool,
+    };
+    localized.to_string()
+}
+
+fn npcs_public(data: &GameData, lang: Language) -> Vec<NpcPublic> {
+    data.npcs
+        .iter()
+        .map(|npc| NpcPublic {
+            id: npc.id.clone(),
+            name: localize_npc_name(npc, lang),
+            x: npc.x,
+            y: npc.y,
+            dialog: localize_npc_dialog(npc, lang),
+        })
+        .collect()
+}
+
+fn localize_npc_name(npc: &NpcDef, lang: Language) -> String {
+    if lang != Language::De {
+        return npc.name.clone();
+    }
+    let localized = match npc.id.as_str() {
+        "npc_logger" => "Edda die Holzfällerin",
+        "npc_hunter" => "Bram der Jäger",
+        "npc_jan" => "Jan der Angler",
+        _ => return npc.name.clone(),
+    };
+    localized.to_string()
+}
 
-        for boat in state.boats.values_mut() {
-            if now_ms - boat.last_saved_ms >= SAVE_INTERVAL_MS {
-                boat.last_saved_ms = now_ms;
-                boats_to_save.push(BoatDoc {
-                    id: boat.id as i64,
-                    x: boat.x,
-                    y: boat.y,
-                    owner_id: boat.owner_id.clone(),
-                });
-            }
-        }
+fn localize_npc_dialog(npc: &NpcDef, lang: Language) -> String {
+    if lang != Language::De {
+        return npc.dialog.clone();
     }
+    let localized = match npc.id.as_str() {
+        "npc_logger" => "Pfadwege halten uns in Bewegung. Bring mir Holz und Stein, dann baue ich eine Schaufel.",
+        "npc_hunter" => "Wildschweine streifen durchs Dickicht. Bring mir ihre Keulen.",
+        "npc_jan" => "Ich wollte mal Forellen mit der Pfanne jagen. Seitdem rede ich lieber mit Fischen. Bring mir 20 Kaninchenkeulen, dann bekommst du meine Angel.",
+        _ => return npc.dialog.clone(),
+    };
+    localized.to_string()
+}
 
-    for doc in to_save {
-        let _ = app_state.store.save_player(&doc).await;
+fn localize_quest_name(quest: &QuestDef, lang: Language) -> String {
+    if lang != Language::De {
+        return quest.name.clone();
     }
-    for doc in boats_to_save {
-        let _ = app_state.store.update_boat(&doc).await;
+    let localized = match quest.id.as_str() {
+        "quest_shovel" => "Pfadbereiter",
+        "quest_hunter" => "Wildschweinkeulen",
+        "quest_fishing" => "Jans Angel",
+        _ => return quest.name.clone(),
+    };
+    localized.to_string()
+}
+
+fn localize_quest_description(quest: &QuestDef, lang: Language) -> String {
+    if lang != Language::De {
+        return quest.description.clone();
     }
+    let localized = match quest.id.as_str() {
+        "quest_shovel" => "Bring 6 Holz und 4 Stein, damit Edda eine Schaufel bauen kann.",
+        "quest_hunter" => "Bring 3 Wildschweinkeulen zu Bram.",
+        "quest_fishing" => "Bring 20 Kaninchenkeulen zu Jan.",
+        _ => return quest.description.clone(),
+    };
+    localized.to_string()
+}
 
-    Ok(())
+fn message_build_success(lang: Language, kind: &str) -> String {
+    match lang {
+        Language::De => match kind {
+            "craft_basic_axe" => "Du stellst eine Holzaxt her.".to_string(),
+            "craft_basic_pick" => "Du stellst eine Holzspitzhacke her.".to_string(),
+            "craft_arrows" => "Du stellst Pfeile her.".to_string(),
+            "hut_wood" => "Du baust eine Holzhütte.".to_string(),
+            "house_stone" => "Du baust ein Steinhaus.".to_string(),
+            "bridge_wood" => "Du baust eine Holzbrücke.".to_string(),
+            "bridge_stone" => "Du baust eine Steinbrücke.".to_string(),
+            "path" => "Du legst einen Pfad an.".to_string(),
+            "road" => "Du baust eine Straße.".to_string(),
+            "boat" => "Du baust ein Boot.".to_string(),
+            _ => "Unbekannte Bauoption.".to_string(),
+        },
+        Language::En => match kind {
+            "craft_basic_axe" => "You craft a wooden axe.".to_string(),
+            "craft_basic_pick" => "You craft a wooden pickaxe.".to_string(),
+            "craft_arrows" => "You craft arrows.".to_string(),
+            "hut_wood" => "You build a wooden hut.".to_string(),
+            "house_stone" => "You build a stone house.".to_string(),
+            "bridge_wood" => "You build a wooden bridge.".to_string(),
+            "bridge_stone" => "You build a stone bridge.".to_string(),
+            "path" => "You lay down a path.".to_string(),
+            "road" => "You build a road.".to_string(),
+            "boat" => "You build a boat.".to_string(),
+            _ => "Unknown build option.".to_string(),
+        },
+    }
 }
 
-fn update_player_movement(
-    player: &mut Player,
-    input: InputState,
-    structure_tiles: &HashMap<TileCoord, StructureTile>,
-    noise: &WorldNoise,
-    dt: f32,
-) {
-    player.last_input_seq = input.seq;
-    let mut dx = input.dir_x;
-    let mut dy = input.dir_y;
-    let input_len = (dx * dx + dy * dy).sqrt();
-    if input_len > 0.01 {
-        dx /= input_len;
-        dy /= input_len;
-        if let (Some(expected_x), Some(expected_y)) = (input.expected_x, input.expected_y) {
-            let corr_x = expected_x - player.x;
-            let corr_y = expected_y - player.y;
-            let corr_dist = (corr_x * corr_x + corr_y * corr_y).sqrt();
-            if corr_dist > 0.01 && corr_dist <= EXPECTED_POS_CORRECTION_RANGE {
-                let corr_weight =
-                    (corr_dist / EXPECTED_POS_CORRECTION_RANGE) * EXPECTED_POS_CORRECTION_WEIGHT;
-                dx += (corr_x / corr_dist) * corr_weight;
-                dy += (corr_y / corr_dist) * corr_weight;
-                let combined_len = (dx * dx + dy * dy).sqrt();
-                if combined_len > 0.01 {
-                    dx /= combined_len;
-                    dy /= combined_len;
-                }
-            }
+fn message_bridge_span_error(lang: Language) -> &'static str {
+    match lang {
+        Language::De => {
+            "Brücken müssen 1-4 Wasserfelder überspannen und an beiden Enden Land haben."
         }
+        Language::En => "Bridges must span 1-4 water tiles with land on both ends.",
     }
-    if (dx * dx + dy * dy).sqrt() > 0.01 {
-        player.face_x = dx;
-        player.face_y = dy;
+}
+
+fn message_need_shovel(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "Du brauchst eine Schaufel.",
+        Language::En => "You need a shovel.",
     }
+}
 
-    let next_x = player.x + dx * PLAYER_SPEED * dt;
-    let next_y = player.y + dy * PLAYER_SPEED * dt;
+fn message_not_enough_materials(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "Du hast nicht genug Materialien.",
+        Language::En => "You don't have enough materials.",
+    }
+}
 
-    if player.in_boat {
-        if can_sail(structure_tiles, noise, next_x, player.y) {
-            player.x = next_x;
-        } else if can_walk(structure_tiles, noise, next_x, player.y) {
-            player.x = next_x;
-            player.in_boat = false;
-            player.boat_id = None;
-        }
-        if player.in_boat {
-            if can_sail(structure_tiles, noise, player.x, next_y) {
-                player.y = next_y;
-            } else if can_walk(structure_tiles, noise, player.x, next_y) {
-                player.y = next_y;
-                player.in_boat = false;
-                player.boat_id = None;
-            }
-        }
-    } else {
-        if can_walk(structure_tiles, noise, next_x, player.y) {
-            player.x = next_x;
-        }
-        if can_walk(structure_tiles, noise, player.x, next_y) {
-            player.y = next_y;
-        }
+fn message_spot_occupied(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "Der Platz ist bereits belegt.",
+        Language::En => "That spot is already occupied.",
     }
 }
 
-fn apply_player_regen(player: &mut Player, now_ms: i64) {
-    if player.hp >= MAX_HP {
-        player.last_regen_ms = now_ms;
-        return;
+fn message_clear_resource(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "Entferne zuerst die Ressource.",
+        Language::En => "Clear the resource first.",
     }
+}
 
-    if now_ms - player.last_regen_ms >= PLAYER_REGEN_INTERVAL_MS {
-        player.hp = (player.hp + 1).min(MAX_HP);
-        player.last_regen_ms = now_ms;
+fn message_build_on_land(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "Das kannst du nur an Land bauen.",
+        Language::En => "You can only build that on land.",
     }
 }
 
-fn handle_player_actions(
-    player: &mut Player,
-    input: InputState,
-    now_ms: i64,
-    state: &mut GameState,
-    world: &WorldConfig,
-    noise: &WorldNoise,
-    data: &GameData,
-) {
-    if input.gather && now_ms - player.last_gather_ms >= 400 {
-        let lang = player_language(state, &player.id);
-        if player.in_boat {
-            player.last_gather_ms = now_ms;
-            if player
-                .inventory
-                .get("fishing_rod")
-                .copied()
-                .unwrap_or(0)
-                <= 0
-            {
-                send_system_message(
-                    state,
-                    &player.id,
-                    message_need_fishing_rod(lang).to_string(),
-                );
-            } else {
-                if player.fishing_target <= 0 {
-                    player.fishing_target = rand::thread_rng().gen_range(FISH_MIN_CLICKS..=FISH_MAX_CLICKS);
-                    player.fishing_clicks = 0;
-                }
-                player.fishing_clicks += 1;
-                if player.fishing_clicks >= player.fishing_target {
-                    player.fishing_clicks = 0;
-                    player.fishing_target =
-                        rand::thread_rng().gen_range(FISH_MIN_CLICKS..=FISH_MAX_CLICKS);
-                    add_item(&mut player.inventory, "fish", 1);
-                    let item_name = localize_item_name(data, "fish", lang);
-                    send_system_message(
-                        state,
-                        &player.id,
-                        message_fishing_catch(&item_name, lang),
-                    );
-                } else {
-                    send_system_message(state, &player.id, message_fishing_wait(lang).to_string());
-                }
-            }
-        } else {
-            let mut messages = Vec::new();
-            let mut resource_update: Option<(ResourceNodePublic, String)> = None;
-            let mut did_gather = false;
+fn message_build_on_water(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "Das kannst du nur auf dem Wasser bauen.",
+        Language::En => "You can only build that on water.",
+    }
+}
 
-            {
-                if let Some((resource, def)) = find_nearby_resource(player, state, data) {
-                    did_gather = true;
-                    let tool_power = best_tool_power(&player.inventory, data, &def.tool);
-                    let power = tool_power.or_else(|| {
-                        if def.tool == "axe" {
-                            Some(1)
-                        } else {
-                            None
-                        }
-                    });
-                    if let Some(mut power) = power {
-                        if resource.kind == "rock" {
-                            power = (power as f32 / resource.size.max(1) as f32).ceil() as i32;
-                        }
-                        resource.hp -= power;
-                        if resource.hp <= 0 {
-                            resource.hp = 0;
-                            resource.respawn_at_ms = Some(now_ms + def.respawn_ms);
-                            let yield_multiplier = resource.size.max(1);
-                            for drop in &def.drops {
-                                let count = drop.count * yield_multiplier;
-                                add_item(&mut player.inventory, &drop.id, count);
-                                let item_name =
-                                    localize_item_name(data, &drop.id, lang);
-                                messages.push(message_collected(&item_name, count, lang));
-                            }
-                            resource_update = Some((
-                                ResourceNodePublic::from(resource.clone()),
-                                "removed".to_string(),
-                            ));
-                        } else {
-                            let resource_name =
-                                localize_resource_name(data, &resource.kind, lang);
-                            messages.push(message_hit_resource(&resource_name, resource.hp, lang));
-                        }
-                    } else {
-                        let tool_name = localize_tool_name(&def.tool, lang);
-                        messages.push(message_need_tool(&tool_name, lang));
-                    }
-                }
-            }
+fn message_need_water(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "Du musst am Wasser stehen, um ein Boot zu bauen.",
+        Language::En => "You need to stand by water to build a boat.",
+    }
+}
 
-            if did_gather {
-                player.last_gather_ms = now_ms;
-            }
-            for text in messages {
-                send_system_message(state, &player.id, text);
-            }
-            if let Some((resource, state_label)) = resource_update {
-                let chunk = chunk_coord_for_tile(resource.x, resource.y, world.chunk_size);
-                if let Some(sender) = state.clients.get(&player.id) {
-                    let _ = sender.send(ServerMessage::ResourceUpdate {
-                        resource: resource.clone(),
-                        state: state_label.clone(),
-                    });
-                }
-                send_to_players_in_chunk(
-                    state,
-                    world.chunk_size,
-                    chunk,
-                    ServerMessage::ResourceUpdate {
-                        resource,
-                        state: state_label,
-                    },
-                );
-            }
-        }
+fn message_unknown_build(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "Unbekannte Bauoption.",
+        Language::En => "Unknown build option.",
     }
+}
 
-    if input.attack {
-        if let Some(weapon) = best_melee_weapon(&player.inventory, data) {
-            if now_ms - player.last_attack_ms >= weapon.cooldown_ms {
-                if attack_monster_melee(player, state, &weapon, data) {
-                    player.last_attack_ms = now_ms;
-                } else if try_ranged_attack(player, state, data, now_ms) {
-                    player.last_attack_ms = now_ms;
-                }
-            }
-        } else if try_ranged_attack(player, state, data, now_ms) {
-            player.last_attack_ms = now_ms;
-        }
+fn message_need_fishing_rod(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "Du brauchst eine Angel, um zu fischen.",
+        Language::En => "You need a fishing rod to fish.",
     }
+}
 
-    if input.interact && now_ms - player.last_interact_ms >= 500 {
-        if !player.in_boat {
-            if let Some(boat) = find_nearby_boat(player, state) {
-                if state.players.values().any(|other| {
-                    other.in_boat && other.boat_id == Some(boat.id) && other.id != player.id
-                }) {
-                    let lang = player_language(state, &player.id);
-                    send_system_message(
-                        state,
-                        &player.id,
-                        message_boat_occupied(lang).to_string(),
-                    );
-                } else {
-                    player.x = boat.x;
-                    player.y = boat.y;
-                    player.in_boat = true;
-                    player.boat_id = Some(boat.id);
-                    let lang = player_language(state, &player.id);
-                    send_system_message(
-                        state,
-                        &player.id,
-                        message_board_boat(lang).to_string(),
-                    );
-                    if let Some(sender) = state.clients.get(&player.id) {
-                        let _ = sender.send(ServerMessage::EntitiesUpdate {
-                            players: vec![PlayerPublic::from(&*player)],
-                            monsters: Vec::new(),
-                            projectiles: Vec::new(),
-                            boats: state
-                                .boats
-                                .get(&boat.id)
-                                .map(|entry| vec![BoatPublic::from(entry)])
-                                .unwrap_or_default(),
-                        });
-                    }
-                }
-                player.last_interact_ms = now_ms;
-            } else if let Some(npc) = find_nearby_npc(player, data) {
-                handle_npc_interaction(player, npc, state, data);
-                player.last_interact_ms = now_ms;
-            }
-        } else if let Some(npc) = find_nearby_npc(player, data) {
-            handle_npc_interaction(player, npc, state, data);
-            player.last_interact_ms = now_ms;
-        }
+fn message_fishing_wait(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "Noch kein Biss.",
+        Language::En => "Nothing bites yet.",
     }
+}
 
-    if player.hp <= 0 {
-        player.hp = MAX_HP;
-        let (spawn_x, spawn_y) = spawn_near_campfire(world, noise);
-        player.x = spawn_x;
-        player.y = spawn_y;
-        let lang = player_language(state, &player.id);
-        send_system_message(state, &player.id, message_wake_up(lang).to_string());
+fn message_fishing_catch(item_name: &str, lang: Language) -> String {
+    match lang {
+        Language::De => format!("Du ziehst einen {} aus dem Wasser.", item_name),
+        Language::En => format!("You reel in a {}.", item_name),
     }
 }
 
-fn sync_boat_position(player: &mut Player, state: &mut GameState, noise: &WorldNoise) {
-    let boat_id = match player.boat_id {
-        Some(id) => id,
-        None => return,
-    };
-    let boat = match state.boats.get_mut(&boat_id) {
-        Some(boat) => boat,
-        None => return,
-    };
-    let (tile_x, tile_y) = entity_foot_tile(player.x, player.y);
-    if tile_at(noise, tile_x, tile_y) == TILE_WATER {
-        boat.x = player.x;
-        boat.y = player.y;
+fn message_board_boat(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "Du steigst ins Boot.",
+        Language::En => "You climb into the boat.",
     }
 }
 
-fn update_monsters(
-    state: &mut GameState,
-    now_ms: i64,
-    dt: f32,
-    noise: &WorldNoise,
-    data: &GameData,
-) {
-    let structure_tiles = &state.structure_tiles;
-    let player_positions: Vec<(String, f32, f32)> = state
-        .players
-        .values()
-        .map(|p| (p.id.clone(), p.x, p.y))
-        .collect();
+fn message_boat_occupied(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "Dieses Boot ist bereits besetzt.",
+        Language::En => "That boat is already occupied.",
+    }
+}
 
-    let mut damage_events: Vec<(String, i32, String)> = Vec::new();
-    for monster in state.monsters.values_mut() {
-        let def = match data.monsters.get(&monster.kind) {
-            Some(def) => def,
-            None => continue,
-        };
-        let mut target = None;
-        let mut target_pos = None;
-        let mut nearest_dist = f32::MAX;
-        for (id, px, py) in &player_positions {
-            let dist = distance(*px, *py, monster.x, monster.y);
-            if dist < nearest_dist {
-                nearest_dist = dist;
-                target = Some(id.clone());
-                target_pos = Some((*px, *py));
-            }
-        }
+fn message_nothing_to_remove(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "Nichts zum Entfernen.",
+        Language::En => "Nothing to remove.",
+    }
+}
 
-        match def.behavior {
-            MonsterBehavior::Aggressive => {
-                if let Some(target_id) = target {
-                    if nearest_dist <= MONSTER_AGGRO_RANGE {
-                        monster.target = Some(target_id.clone());
-                        let (tx, ty) = target_pos.unwrap_or((monster.x, monster.y));
-                        move_towards(
-                            monster,
-                            tx,
-                            ty,
-                            def.speed,
-                            dt,
-                            structure_tiles,
-                            noise,
-                        );
-
-                        if nearest_dist <= MONSTER_ATTACK_RANGE
-                            && now_ms - monster.last_attack_ms >= 800
-                        {
-                            damage_events
-                                .push((target_id.clone(), def.damage, monster.kind.clone()));
-                            monster.last_attack_ms = now_ms;
-                        }
-                    } else {
-                        wander(
-                            monster,
-                            now_ms,
-                            def.speed,
-                            dt,
-                            structure_tiles,
-                            noise,
-                        );
-                    }
-                } else {
-                    wander(
-                        monster,
-                        now_ms,
-                        def.speed,
-                        dt,
-                        structure_tiles,
-                        noise,
-                    );
-                }
-            }
-            MonsterBehavior::Timid => {
-                monster.target = None;
-                if let Some((tx, ty)) = target_pos {
-                    if nearest_dist <= MONSTER_AGGRO_RANGE {
-                        move_away(
-                            monster,
-                            tx,
-                            ty,
-                            def.speed,
-                            dt,
-                            structure_tiles,
-                            noise,
-                            now_ms,
-                            monster.kind == "rabbit",
-                        );
-                        continue;
-                    }
-                }
-                monster.flee_dir = None;
-                wander(
-                    monster,
-                    now_ms,
-                    def.speed,
-                    dt,
-                    structure_tiles,
-                    noise,
-                );
-            }
-        }
+fn message_remove_own_only(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "Du kannst nur deine eigenen Gebäude oder die deiner Gilde entfernen.",
+        Language::En => "You can only remove your own or your guild's buildings.",
     }
+}
 
-    for (player_id, damage, monster_id) in damage_events {
-        let lang = player_language(state, &player_id);
-        let monster_name = localize_monster_name(data, &monster_id, lang);
-        let message = if let Some(player) = state.players.get_mut(&player_id) {
-            player.hp -= damage;
-            Some((
-                player.id.clone(),
-                message_monster_hits_you(&monster_name, player.hp.max(0), lang),
-            ))
-        } else {
-            None
-        };
-        if let Some((target_id, text)) = message {
-            send_system_message(state, &target_id, text);
-        }
+fn message_structure_removed(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "Gebäude entfernt.",
+        Language::En => "Structure removed.",
     }
 }
 
-fn update_projectiles(state: &mut GameState, _now_ms: i64, dt: f32, data: &GameData) {
-    let mut to_remove = Vec::new();
-    let mut killed = HashSet::new();
+fn message_too_far(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "Diese Stelle ist zu weit entfernt.",
+        Language::En => "That spot is too far away.",
+    }
+}
 
-    {
-        let (monsters, projectiles) = (&mut state.monsters, &mut state.projectiles);
-        for (id, projectile) in projectiles.iter_mut() {
-            projectile.x += projectile.vx * dt;
-            projectile.y += projectile.vy * dt;
-            projectile.ttl_ms -= (dt * 1000.0) as i64;
-            if projectile.ttl_ms <= 0 {
-                to_remove.push(*id);
-                continue;
-            }
+fn message_cannot_plant_here(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "Das kannst du hier nicht pflanzen.",
+        Language::En => "You can't plant that here.",
+    }
+}
 
-            let mut hit = None;
-            for (monster_id, monster) in monsters.iter_mut() {
-                let dist = distance(projectile.x, projectile.y, monster.x, monster.y);
-                if dist < 0.5 {
-                    monster.hp -= projectile.damage;
-                    hit = Some(*monster_id);
-                    break;
-                }
-            }
+fn message_planted(resource_name: &str, lang: Language) -> String {
+    match lang {
+        Language::De => format!("Gepflanzt: {}", resource_name),
+        Language::En => format!("Planted {}", resource_name),
+    }
+}
 
-            if let Some(monster_id) = hit {
-                to_remove.push(*id);
-                if let Some(monster) = monsters.get(&monster_id) {
-                    if monster.hp <= 0 {
-                        killed.insert(monster_id);
-                    }
-                }
-            }
-        }
+fn message_wake_up(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "Du wachst am Lagerfeuer auf.",
+        Language::En => "You wake up by the campfire.",
+    }
+}
+
+fn message_collected(item_name: &str, count: i32, lang: Language) -> String {
+    match lang {
+        Language::De => format!("Gesammelt: {} x{}", item_name, count),
+        Language::En => format!("Collected {} x{}", item_name, count),
+    }
+}
+
+fn message_hit_resource(resource_name: &str, hp: i32, lang: Language) -> String {
+    match lang {
+        Language::De => format!("Getroffen: {} ({})", resource_name, hp),
+        Language::En => format!("Hit {} ({})", resource_name, hp),
+    }
+}
+
+fn message_need_tool(tool_name: &str, lang: Language) -> String {
+    match lang {
+        Language::De => format!("Du brauchst eine {}.", tool_name),
+        Language::En => format!("You need a {}.", tool_name),
+    }
+}
+
+fn message_hit_monster(monster_name: &str, hp: i32, lang: Language) -> String {
+    match lang {
+        Language::De => format!("Getroffen: {} ({})", monster_name, hp),
+        Language::En => format!("Hit {} ({})", monster_name, hp),
+    }
+}
 
-        for id in &to_remove {
-            projectiles.remove(id);
-        }
+fn message_monster_hits_you(monster_name: &str, hp: i32, lang: Language) -> String {
+    match lang {
+        Language::De => format!("{} trifft dich ({})", monster_name, hp),
+        Language::En => format!("{} hits you ({})", monster_name, hp),
     }
+}
 
-    for monster_id in killed {
-        handle_monster_death(state, monster_id, data, None);
+fn message_boss_spawned(boss_name: &str, x: i32, y: i32, lang: Language) -> String {
+    match lang {
+        Language::De => format!("{} ist bei ({}, {}) aufgetaucht!", boss_name, x, y),
+        Language::En => format!("{} has appeared near ({}, {})!", boss_name, x, y),
     }
 }
 
-fn update_resources(state: &mut GameState, now_ms: i64, data: &GameData, chunk_size: i32) {
-    let mut respawned = Vec::new();
-    let mut grown = Vec::new();
-    for resources in state.resources.values_mut() {
-        for res in resources.iter_mut() {
-            if res.hp <= 0 {
-                if let Some(respawn_at) = res.respawn_at_ms {
-                    if now_ms >= respawn_at {
-                        if let Some(def) = data.resources.get(&res.kind) {
-                            if state
-                                .structure_tiles
-                                .contains_key(&TileCoord { x: res.x, y: res.y })
-                            {
-                                res.respawn_at_ms = Some(now_ms + def.respawn_ms);
-                                continue;
-                            }
-                            res.hp = def.hp;
-                            res.respawn_at_ms = None;
-                            if is_tree_kind(&res.kind) {
-                                let (s, next) = tree_spawn_state(now_ms as u64, res.x, res.y, now_ms);
-                                res.size = s;
-                                res.next_growth_ms = next;
-                            } else if res.kind == "rock" {
-                                let size_roll = noise_hash01(now_ms as u64, res.x, res.y);
-                                if size_roll > 0.8 {
-                                    res.size = 3.min(ROCK_MAX_SIZE);
-                                } else if size_roll > 0.5 {
-                                    res.size = 2.min(ROCK_MAX_SIZE);
-                                } else {
-                                    res.size = 1;
-                                }
-                                res.next_growth_ms = None;
-                            } else {
-                                res.size = 1;
-                                res.next_growth_ms = None;
-                            }
-                            respawned.push(ResourceNodePublic::from(res.clone()));
-                        }
-                    }
-                }
-            } else if is_tree_kind(&res.kind) {
-                if res.size < TREE_MAX_SIZE {
-                    if let Some(next_growth) = res.next_growth_ms {
-                        if now_ms >= next_growth {
-                            res.size += 1;
-                            res.next_growth_ms = if res.size < TREE_MAX_SIZE {
-                                let jitter = noise_hash01(now_ms as u64, res.x, res.y);
-                                let delay =
-                                    (TREE_GROW_INTERVAL_MS as f32 * (0.4 + jitter * 1.2)) as i64;
-                                Some(now_ms + delay)
-                            } else {
-                                None
-                            };
-                            grown.push(ResourceNodePublic::from(res.clone()));
-                        }
-                    }
-                } else {
-                    res.next_growth_ms = None;
-                }
-            }
-        }
+fn message_boss_defeated(boss_name: &str, names: &str, lang: Language) -> String {
+    match (lang, names.is_empty()) {
+        (Language::De, true) => format!("{} wurde besiegt!", boss_name),
+        (Language::De, false) => format!("{} wurde von {} besiegt!", boss_name, names),
+        (Language::En, true) => format!("{} has been defeated!", boss_name),
+        (Language::En, false) => format!("{} has been defeated by {}!", boss_name, names),
     }
+}
 
-    if !respawned.is_empty() {
-        for res in respawned {
-            let chunk = chunk_coord_for_tile(res.x, res.y, chunk_size);
-            send_to_players_in_chunk(
-                state,
-                chunk_size,
-                chunk,
-                ServerMessage::ResourceUpdate {
-                    resource: res,
-                    state: "spawned".to_string(),
-                },
-            );
-        }
+fn message_boss_departed(boss_name: &str, lang: Language) -> String {
+    match lang {
+        Language::De => format!("{} ist verschwunden.", boss_name),
+        Language::En => format!("{} has wandered off.", boss_name),
     }
+}
 
-    if !grown.is_empty() {
-        for res in grown {
-            let chunk = chunk_coord_for_tile(res.x, res.y, chunk_size);
-            send_to_players_in_chunk(
-                state,
-                chunk_size,
-                chunk,
-                ServerMessage::ResourceUpdate {
-                    resource: res,
-                    state: "grown".to_string(),
-                },
-            );
-        }
+fn message_boss_phase(boss_name: &str, lang: Language) -> String {
+    match lang {
+        Language::De => format!("{} wird rasend!", boss_name),
+        Language::En => format!("{} becomes enraged!", boss_name),
     }
 }
 
-fn attack_monster_melee(
-    player: &mut Player,
-    state: &mut GameState,
-    weapon: &WeaponStats,
-    data: &GameData,
-) -> bool {
-    let lang = player_language(state, &player.id);
-    let mut target_id = None;
-    let mut nearest_dist = f32::MAX;
-    for (id, monster) in state.monsters.iter() {
-        let dist = distance(player.x, player.y, monster.x, monster.y);
-        if dist < weapon.range && dist < nearest_dist {
-            nearest_dist = dist;
-            target_id = Some(*id);
-        }
+fn message_target_out_of_range(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "Das Ziel ist zu weit entfernt.",
+        Language::En => "That target is out of range.",
     }
+}
 
-    if let Some(monster_id) = target_id {
-        let mut message = None;
-        let mut killed = false;
-        if let Some(monster) = state.monsters.get_mut(&monster_id) {
-            monster.hp -= weapon.damage;
-            let monster_name = localize_monster_name(data, &monster.kind, lang);
-            message = Some(message_hit_monster(&monster_name, monster.hp.max(0), lang));
-            if monster.hp <= 0 {
-                killed = true;
-            }
-        }
-        if let Some(text) = message {
-            send_system_message(state, &player.id, text);
-        }
-        if killed {
-            handle_monster_death(state, monster_id, data, Some(player));
-        }
-        return true;
+fn message_no_clear_shot(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "Du hast keine freie Schussbahn.",
+        Language::En => "You don't have a clear shot.",
     }
-    false
 }
 
-fn try_ranged_attack(
-    player: &mut Player,
-    state: &mut GameState,
-    data: &GameData,
-    now_ms: i64,
-) -> bool {
-    let (weapon, ammo_id) = match best_ranged_weapon(&player.inventory, data) {
-        Some(result) => result,
-        None => return false,
-    };
+fn message_pvp_kill(killer: &str, victim: &str, lang: Language) -> String {
+    match lang {
+        Language::De => format!("{} hat {} besiegt.", killer, victim),
+        Language::En => format!("{} has defeated {}.", killer, victim),
+    }
+}
 
-    if now_ms - player.last_attack_ms < weapon.cooldown_ms {
-        return false;
+fn message_player_not_found(name: &str, lang: Language) -> String {
+    match lang {
+        Language::De => format!("Kein Spieler namens {} ist online.", name),
+        Language::En => format!("No player named {} is online.", name),
     }
+}
 
-    if !consume_item(&mut player.inventory, &ammo_id, 1) {
-        let lang = player_language(state, &player.id);
-        let item_name = localize_item_name(data, &ammo_id, lang);
-        send_system_message(state, &player.id, message_out_of(&item_name, lang));
-        return false;
+fn message_unknown_command(command: &str, lang: Language) -> String {
+    match lang {
+        Language::De => format!(
+            "Unbekannter Befehl /{}. /help listet alle Befehle.",
+            command
+        ),
+        Language::En => format!("Unknown command /{}. Try /help.", command),
     }
+}
 
-    let speed = weapon.projectile_speed.unwrap_or(7.0);
-    let dir_len = (player.face_x * player.face_x + player.face_y * player.face_y).sqrt();
-    let (dir_x, dir_y) = if dir_len > 0.01 {
-        (player.face_x / dir_len, player.face_y / dir_len)
-    } else {
-        (1.0, 0.0)
-    };
+fn message_chat_help(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "Befehle: /l (lokal), /s (global), /p (Gruppe), /g (Gilde), /w Name Nachricht, /me Aktion, /roll [max], /who, /report Name Grund",
+        Language::En => "Commands: /l (local), /s (global), /p (party), /g (guild), /w name message, /me action, /roll [max], /who, /report name reason",
+    }
+}
 
-    let proj_id = state.next_id();
-    state.projectiles.insert(
-        proj_id,
-        Projectile {
-            id: proj_id,
-            x: player.x + dir_x * 0.6,
-            y: player.y + dir_y * 0.6,
-            vx: dir_x * speed,
-            vy: dir_y * speed,
-            ttl_ms: 1200,
-            damage: weapon.damage,
-        },
-    );
+fn message_chat_rate_muted(secs: i64, lang: Language) -> String {
+    match lang {
+        Language::De => format!(
+            "Du schreibst zu schnell und bist für {} Sekunden stummgeschaltet.",
+            secs
+        ),
+        Language::En => format!(
+            "You are sending messages too fast and have been muted for {} seconds.",
+            secs
+        ),
+    }
+}
 
-    true
+fn message_chat_muted(secs: i64, lang: Language) -> String {
+    match lang {
+        Language::De => format!("Du bist noch {} Sekunden stummgeschaltet.", secs),
+        Language::En => format!("You are muted for another {} seconds.", secs),
+    }
 }
 
-fn handle_monster_death(
-    state: &mut GameState,
-    monster_id: u64,
-    data: &GameData,
-    mut award_to: Option<&mut Player>,
-) {
-    if let Some(monster) = state.monsters.remove(&monster_id) {
-        if let Some(def) = data.monsters.get(&monster.kind) {
-            if let Some(drop) = &def.drop {
-                if let Some(player) = award_to.as_deref_mut() {
-                    add_item(&mut player.inventory, &drop.id, drop.count);
-                    let player_id = player.id.clone();
-                    let lang = player_language(state, &player_id);
-                    let item_name = localize_item_name(data, &drop.id, lang);
-                    send_system_message(
-                        state,
-                        &player_id,
-                        message_picked_up(&item_name, drop.count, lang),
-                    );
-                } else {
-                    let mut awarded_to = None;
-                    for player in state.players.values_mut() {
-                        let dist = distance(player.x, player.y, monster.x, monster.y);
-                        if dist < 2.0 {
-                            add_item(&mut player.inventory, &drop.id, drop.count);
-                            awarded_to = Some(player.id.clone());
-                            break;
-                        }
-                    }
-                    if let Some(player_id) = awarded_to {
-                        let lang = player_language(state, &player_id);
-                        let item_name = localize_item_name(data, &drop.id, lang);
-                        send_system_message(
-                            state,
-                            &player_id,
-                            message_picked_up(&item_name, drop.count, lang),
-                        );
-                    }
-                }
-            }
-        }
+fn message_name_not_allowed(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "Dieser Name ist nicht erlaubt.",
+        Language::En => "That name isn't allowed.",
     }
 }
 
-fn handle_npc_interaction(player: &mut Player, npc: &NpcDef, state: &mut GameState, data: &GameData) {
-    let lang = player_language(state, &player.id);
-    let npc_name = localize_npc_name(npc, lang);
-    let npc_dialog = localize_npc_dialog(npc, lang);
-    if let Some(quest) = data.quests_by_npc.get(&npc.id) {
-        if player.completed_quests.contains(&quest.id) {
-            send_dialog(state, &player.id, &npc_name, &message_thanks_again(lang));
-            return;
-        }
+fn message_report_usage(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "Melden: /report Name Grund (der Spieler muss online sein oder kürzlich geschrieben haben).",
+        Language::En => "Report with /report name reason (the player must be online or have chatted recently).",
+    }
+}
 
-        if has_items(&player.inventory, &quest.requires) {
-            remove_items(&mut player.inventory, &quest.requires);
-            for reward in &quest.rewards {
-                add_item(&mut player.inventory, &reward.id, reward.count);
-            }
-            player.completed_quests.insert(quest.id.clone());
-            send_dialog(
-                state,
-                &player.id,
-                &npc_name,
-                &message_quest_complete(
-                    &localize_quest_name(quest, lang),
-                    &localize_quest_description(quest, lang),
-                    lang,
-                ),
-            );
-            for reward in &quest.rewards {
-                let lang = player_language(state, &player.id);
-                let item_name = localize_item_name(data, &reward.id, lang);
-                send_system_message(
-                    state,
-                    &player.id,
-                    message_reward(&item_name, reward.count, lang),
-                );
-            }
-        } else {
-            let mut needs = Vec::new();
-            for req in &quest.requires {
-                let have = player.inventory.get(&req.id).copied().unwrap_or(0);
-                let lang = player_language(state, &player.id);
-                let item_name = localize_item_name(data, &req.id, lang);
-                needs.push(format!("{} {}/{}", item_name, have, req.count));
-            }
-            send_dialog(
-                state,
-                &player.id,
-                &npc_name,
-                &message_quest_needs(
-                    &localize_quest_name(quest, lang),
-                    &localize_quest_description(quest, lang),
-                    &needs.join(", "),
-                    lang,
-                ),
-            );
-        }
-    } else {
-        send_dialog(state, &player.id, &npc_name, &npc_dialog);
+fn message_gm_usage(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "GM-Befehle: /tp X Y, /tp [Name] X Y, /tp Name, /give Item [Anzahl] [Name], /spawn monster Art [Anzahl], /heal [Name], /kick Name [Grund], /godmode, /time [Stunde|day|night|reset]",
+        Language::En => "GM commands: /tp x y, /tp [name] x y, /tp name, /give item [count] [name], /spawn monster kind [count], /heal [name], /kick name [reason], /godmode, /time [hour|day|night|reset]",
+    }
+}
+
+fn message_gm_player_not_found(name: &str, lang: Language) -> String {
+    match lang {
+        Language::De => format!("Kein Spieler namens {} ist online.", name),
+        Language::En => format!("No player named {} is online.", name),
     }
 }
 
-fn send_dialog(state: &mut GameState, player_id: &str, title: &str, text: &str) {
-    if let Some(sender) = state.clients.get(player_id) {
-        let _ = sender.send(ServerMessage::Dialog {
-            title: title.to_string(),
-            text: text.to_string(),
-        });
+fn message_gm_blocked(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "Dort ist kein begehbarer Boden.",
+        Language::En => "There is no walkable ground there.",
     }
 }
 
-fn send_system_message(state: &mut GameState, player_id: &str, text: String) {
-    if let Some(sender) = state.clients.get(player_id) {
-        let _ = sender.send(ServerMessage::System { text });
+fn message_gm_teleported(name: &str, x: i32, y: i32, lang: Language) -> String {
+    match lang {
+        Language::De => format!("{} nach {}, {} teleportiert.", name, x, y),
+        Language::En => format!("Teleported {} to {}, {}.", name, x, y),
     }
 }
 
-fn find_nearby_resource<'a>(
-    player: &Player,
-    state: &'a mut GameState,
-    data: &'a GameData,
-) -> Option<(&'a mut ResourceNode, &'a ResourceDef)> {
-    for resources in state.resources.values_mut() {
-        for res in resources.iter_mut() {
-            if res.hp <= 0 {
-                continue;
-            }
-            let dist = distance(player.x, player.y, res.x as f32 + 0.5, res.y as f32 + 0.5);
-            if dist <= GATHER_RANGE {
-                if let Some(def) = data.resources.get(&res.kind) {
-                    return Some((res, def));
-                }
-            }
-        }
+fn message_gm_unknown_item(item: &str, lang: Language) -> String {
+    match lang {
+        Language::De => format!("Unbekannter Gegenstand {}.", item),
+        Language::En => format!("Unknown item {}.", item),
     }
-    None
 }
 
-fn find_nearby_npc<'a>(player: &Player, data: &'a GameData) -> Option<&'a NpcDef> {
-    for npc in &data.npcs {
-        let dist = distance(player.x, player.y, npc.x, npc.y);
-        if dist <= INTERACT_RANGE {
-            return Some(npc);
-        }
+fn message_gm_gave(item: &str, count: i32, name: &str, lang: Language) -> String {
+    match (lang, count < 0) {
+        (Language::De, false) => format!("{} x{} an {} gegeben.", item, count, name),
+        (Language::De, true) => format!("{} x{} von {} entfernt.", item, -count, name),
+        (Language::En, false) => format!("Gave {} x{} to {}.", item, count, name),
+        (Language::En, true) => format!("Removed {} x{} from {}.", item, -count, name),
     }
-    None
 }
 
-fn find_nearby_boat(player: &Player, state: &GameState) -> Option<Boat> {
-    for boat in state.boats.values() {
-        if distance(player.x, player.y, boat.x, boat.y) <= INTERACT_RANGE {
-            return Some(boat.clone());
-        }
+fn message_gm_unknown_monster(kind: &str, lang: Language) -> String {
+    match lang {
+        Language::De => format!("Unbekanntes Monster {}.", kind),
+        Language::En => format!("Unknown monster {}.", kind),
     }
-    None
 }
 
-fn best_tool_power(
-    inventory: &HashMap<String, i32>,
-    data: &GameData,
-    tool: &str,
-) -> Option<i32> {
-    let mut best = None;
-    for (item_id, count) in inventory {
-        if *count <= 0 {
-            continue;
-        }
-        if let Some(def) = data.items.get(item_id) {
-            if def.tool.as_deref() == Some(tool) {
-                let power = def.power.unwrap_or(1);
-                if best.map_or(true, |current| power > current) {
-                    best = Some(power);
-                }
-            }
-        }
+fn message_gm_spawned(monster: &str, count: i32, lang: Language) -> String {
+    match lang {
+        Language::De => format!("{} x{} erschaffen.", monster, count),
+        Language::En => format!("Spawned {} x{}.", monster, count),
     }
-    best
 }
 
-fn best_melee_weapon(inventory: &HashMap<String, i32>, data: &GameData) -> Option<WeaponStats> {
-    let mut best: Option<WeaponStats> = None;
-    for (item_id, count) in inventory {
-        if *count <= 0 {
-            continue;
-        }
-        if let Some(def) = data.items.get(item_id) {
-            if let Some(weapon) = &def.weapon {
-                if weapon.kind == "melee" {
-                    if best
-                        .as_ref()
-                        .map_or(true, |current| weapon.damage > current.damage)
-                    {
-                        best = Some(weapon.clone());
-                    }
-                }
-            }
-        }
+fn message_gm_healed(name: &str, lang: Language) -> String {
+    match lang {
+        Language::De => format!("{} vollständig geheilt.", name),
+        Language::En => format!("Healed {} to full HP.", name),
     }
-    best
 }
 
-fn best_ranged_weapon(
-    inventory: &HashMap<String, i32>,
-    data: &GameData,
-) -> Option<(WeaponStats, String)> {
-    let mut best: Option<(WeaponStats, String)> = None;
-    for (item_id, count) in inventory {
-        if *count <= 0 {
-            continue;
-        }
-        if let Some(def) = data.items.get(item_id) {
-            if let Some(weapon) = &def.weapon {
-                if weapon.kind == "ranged" {
-                    let ammo_id = def.ammo_for.clone().unwrap_or_else(|| "arrow".to_string());
-                    if best
-                        .as_ref()
-                        .map_or(true, |current| weapon.damage > current.0.damage)
-                    {
-                        best = Some((weapon.clone(), ammo_id));
-                    }
-                }
-            }
-        }
+fn message_gm_kicked(name: &str, lang: Language) -> String {
+    match lang {
+        Language::De => format!("{} wurde vom Server geworfen.", name),
+        Language::En => format!("Kicked {} from the server.", name),
     }
-    best
 }
 
-fn has_items(inventory: &HashMap<String, i32>, items: &[ItemStack]) -> bool {
-    items.iter().all(|item| inventory.get(&item.id).copied().unwrap_or(0) >= item.count)
+fn message_gm_godmode(enabled: bool, lang: Language) -> &'static str {
+    match (lang, enabled) {
+        (Language::De, true) => "Unverwundbarkeit an.",
+        (Language::De, false) => "Unverwundbarkeit aus.",
+        (Language::En, true) => "God mode on.",
+        (Language::En, false) => "God mode off.",
+    }
 }
 
-fn remove_items(inventory: &mut HashMap<String, i32>, items: &[ItemStack]) -> bool {
-    if !has_items(inventory, items) {
-        return false;
+fn message_gm_time(time: f32, lang: Language) -> String {
+    let minutes = (time * 24.0 * 60.0) as i32;
+    match lang {
+        Language::De => format!("Es ist {:02}:{:02} Uhr.", minutes / 60, minutes % 60),
+        Language::En => format!("It is {:02}:{:02}.", minutes / 60, minutes % 60),
     }
-    for item in items {
-        consume_item(inventory, &item.id, item.count);
+}
+
+fn message_report_sent(name: &str, lang: Language) -> String {
+    match lang {
+        Language::De => format!("Danke. Deine Meldung zu {} wurde gespeichert.", name),
+        Language::En => format!("Thanks. Your report about {} has been recorded.", name),
     }
-    true
 }
 
-fn add_item(inventory: &mut HashMap<String, i32>, item_id: &str, count: i32) {
-    let entry = inventory.entry(item_id.to_string()).or_insert(0);
-    *entry += count;
+fn message_report_cooldown(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "Bitte warte etwas, bevor du erneut meldest.",
+        Language::En => "Please wait a moment before sending another report.",
+    }
 }
 
-fn consume_item(inventory: &mut HashMap<String, i32>, item_id: &str, count: i32) -> bool {
-    let entry = inventory.entry(item_id.to_string()).or_insert(0);
-    if *entry < count {
-        return false;
+fn message_whisper_usage(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "Flüstern: /w Name Nachricht (der Spieler muss online sein).",
+        Language::En => "Whisper with /w name message (the player must be online).",
     }
-    *entry -= count;
-    true
 }
 
-fn inventory_hash(inventory: &HashMap<String, i32>) -> u64 {
-    use std::collections::hash_map::DefaultHasher;
-    let mut entries: Vec<_> = inventory.iter().collect();
-    entries.sort_by(|a, b| a.0.cmp(b.0));
-    let mut hasher = DefaultHasher::new();
-    for (id, count) in entries {
-        id.hash(&mut hasher);
-        count.hash(&mut hasher);
+fn message_roll(name: &str, roll: u32, max: u32, lang: Language) -> String {
+    match lang {
+        Language::De => format!("{} würfelt {} (1-{}).", name, roll, max),
+        Language::En => format!("{} rolls {} (1-{}).", name, roll, max),
     }
-    hasher.finish()
 }
 
-fn build_inventory_items(
-    inventory: &HashMap<String, i32>,
-    data: &GameData,
-    lang: Language,
-) -> Vec<InventoryItem> {
-    let mut items = Vec::new();
-    for (id, count) in inventory {
-        if *count <= 0 {
-            continue;
-        }
-        let (name, heal) = match data.items.get(id) {
-            Some(def) => (localize_item_name(data, &def.id, lang), def.heal),
-            None => (id.clone(), None),
-        };
-        items.push(InventoryItem {
-            id: id.clone(),
-            name,
-            count: *count,
-            heal,
-        });
+fn message_who(names: &[String], lang: Language) -> String {
+    match lang {
+        Language::De => format!("{} Spieler online: {}", names.len(), names.join(", ")),
+        Language::En => format!("Online ({}): {}", names.len(), names.join(", ")),
     }
-    items.sort_by(|a, b| a.name.cmp(&b.name));
-    items
 }
 
-fn player_language(state: &GameState, player_id: &str) -> Language {
-    state.locales.get(player_id).copied().unwrap_or(Language::En)
+fn message_party_invited(name: &str, lang: Language) -> String {
+    match lang {
+        Language::De => format!("Du hast {} in deine Gruppe eingeladen.", name),
+        Language::En => format!("You invited {} to your party.", name),
+    }
 }
 
-fn localize_item_name(data: &GameData, item_id: &str, lang: Language) -> String {
-    if lang != Language::De {
-        return data
-            .items
-            .get(item_id)
-            .map(|def| def.name.clone())
-            .unwrap_or_else(|| item_id.to_string());
+fn message_party_already_grouped(name: &str, lang: Language) -> String {
+    match lang {
+        Language::De => format!("{} ist bereits in einer Gruppe.", name),
+        Language::En => format!("{} is already in a party.", name),
     }
-    let localized = match item_id {
-        "wood" => "Holz",
-        "apple" => "Apfel",
-        "stone" => "Stein",
-        "boar_leg" => "Wildschweinkeule",
-        "rabbit_leg" => "Kaninchenkeule",
-        "slime_core" => "Schleimkern",
-        "arrow" => "Pfeil",
-        "basic_axe" => "Holzaxt",
-        "fine_axe" => "Gute Axt",
-        "basic_pick" => "Holzspitzhacke",
-        "basic_shovel" => "Schaufel",
-        "fishing_rod" => "Angel",
-        "fish" => "Fisch",
-        "rusty_sword" => "Rostiges Schwert",
-        "iron_sword" => "Eisenschwert",
-        "bow" => "Bogen",
-        _ => return data
-            .items
-            .get(item_id)
-            .map(|def| def.name.clone())
-            .unwrap_or_else(|| item_id.to_string()),
-    };
-    localized.to_string()
 }
 
-fn localize_resource_name(data: &GameData, resource_id: &str, lang: Language) -> String {
-    if lang != Language::De {
-        return data
-            .resources
-            .get(resource_id)
-            .map(|def| def.name.clone())
-            .unwrap_or_else(|| resource_id.to_string());
+fn message_party_not_leader(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "Nur die Gruppenleitung kann das tun.",
+        Language::En => "Only the party leader can do that.",
     }
-    let localized = match resource_id {
-        "tree" => "Baum",
-        "apple_tree" => "Apfelbaum",
-        "pine_tree" => "Kiefer",
-        "palm_tree" => "Palme",
-        "rock" => "Fels",
-        _ => return data
-            .resources
-            .get(resource_id)
-            .map(|def| def.name.clone())
-            .unwrap_or_else(|| resource_id.to_string()),
-    };
-    localized.to_string()
 }
 
-fn localize_monster_name(data: &GameData, monster_id: &str, lang: Language) -> String {
-    if lang != Language::De {
-        return data
-            .monsters
-            .get(monster_id)
-            .map(|def| def.name.clone())
-            .unwrap_or_else(|| monster_id.to_string());
+fn message_party_full(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "Die Gruppe ist voll.",
+        Language::En => "The party is full.",
+    }
+}
+
+fn message_party_no_invite(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "Du hast keine offene Gruppeneinladung.",
+        Language::En => "You have no pending party invite.",
+    }
+}
+
+fn message_party_none(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "Du bist in keiner Gruppe.",
+        Language::En => "You are not in a party.",
+    }
+}
+
+fn message_party_joined(name: &str, lang: Language) -> String {
+    match lang {
+        Language::De => format!("{} ist der Gruppe beigetreten.", name),
+        Language::En => format!("{} joined the party.", name),
+    }
+}
+
+fn message_party_member_left(name: &str, lang: Language) -> String {
+    match lang {
+        Language::De => format!("{} hat die Gruppe verlassen.", name),
+        Language::En => format!("{} left the party.", name),
+    }
+}
+
+fn message_party_left(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "Du hast die Gruppe verlassen.",
+        Language::En => "You left the party.",
     }
-    let localized = match monster_id {
-        "boar" => "Wildschwein",
-        "rabbit" => "Kaninchen",
-        _ => return data
-            .monsters
-            .get(monster_id)
-            .map(|def| def.name.clone())
-            .unwrap_or_else(|| monster_id.to_string()),
-    };
-    localized.to_string()
 }
 
-fn localize_tool_name(tool: &str, lang: Language) -> String {
-    if lang != Language::De {
-        return tool.to_string();
+fn message_party_kicked(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "Du wurdest aus der Gruppe entfernt.",
+        Language::En => "You were removed from the party.",
     }
-    let localized = match tool {
-        "axe" => "Axt",
-        "pick" => "Spitzhacke",
-        "shovel" => "Schaufel",
-        _ => tool,
-    };
-    localized.to_string()
 }
 
-fn localize_npc_name(npc: &NpcDef, lang: Language) -> String {
-    if lang != Language::De {
-        return npc.name.clone();
+fn message_party_disbanded(lang: Language) -> &'static str {
+    match lang {
+        Language::De => "Die Gruppe wurde aufgelöst.",
+        Language::En => "The party has disbanded.",
     }
-    let localized = match npc.id.as_str() {
-        "npc_logger" => "Edda die Holzfällerin",
-        "npc_hunter" => "Bram der Jäger",
-        "npc_jan" => "Jan der Angler",
-        _ => return npc.name.clone(),
-    };
-    localized.to_string()
 }
 
-fn localize_npc_dialog(npc: &NpcDef, lang: Language) -> String {
-    if lang != Language::De {
-        return npc.dialog.clone();
+fn message_party_loot(mode: LootMode, lang: Language) -> &'static str {
+    match (mode, lang) {
+        (LootMode::Damage, Language::De) => "Beute geht jetzt nach verursachtem Schaden.",
+        (LootMode::Damage, Language::En) => "Loot now goes by damage dealt.",
+        (LootMode::RoundRobin, Language::De) => "Beute wird jetzt reihum verteilt.",
+        (LootMode::RoundRobin, Language::En) => "Loot is now shared round robin.",
+        (LootMode::Random, Language::De) => "Beute wird jetzt zufällig verteilt.",
+        (LootMode::Random, Language::En) => "Loot is now handed out at random.",
     }
-    let localized = match npc.id.as_str() {
-        "npc_logger" => "Pfadwege halten uns in Bewegung. Bring mir Holz und Stein, dann baue ich eine Schaufel.",
-        "npc_hunter" => "Wildschweine streifen durchs Dickicht. Bring mir ihre Keulen.",
-        "npc_jan" => "Ich wollte mal Forellen mit der Pfanne jagen. Seitdem rede ich lieber mit Fischen. Bring mir 20 Kaninchenkeulen, dann bekommst du meine Angel.",
-        _ => return npc.dialog.clone(),
-    };
-    localized.to_string()
 }
 
-fn localize_quest_name(quest: &QuestDef, lang: Language) -> String {
-    if lang != Language::De {
-        return quest.name.clone();
+fn message_kill_progress(monster: &str, have: i32, need: i32, lang: Language) -> String {
+    match lang {
+        Language::De => format!("{} besiegt {}/{}", monster, have, need),
+        Language::En => format!("{} defeated {}/{}", monster, have, need),
     }
-    let localized = match quest.id.as_str() {
-        "quest_lumber" => "Holz für das Lager",
-        "quest_shovel" => "Pfadbereiter",
-        "quest_hunter" => "Wildschweinkeulen",
-        "quest_fishing" => "Jans Angel",
-        _ => return quest.name.clone(),
-    };
-    localized.to_string()
 }
 
-fn localize_quest_description(quest: &QuestDef, lang: Language) -> String {
-    if lang != Language::De {
-        return quest.description.clone();
+fn message_guild_rank_name(rank: GuildRank, lang: Language) -> &'static str {
+    match (rank, lang) {
+        (GuildRank::Leader, Language::De) => "Gildenleitung",
+        (GuildRank::Leader, Language::En) => "leader",
+        (GuildRank::Officer, Language::De) => "Offizier",
+        (GuildRank::Officer, Language::En) => "officer",
+        (GuildRank::Member, Language::De) => "Mitglied",
+        (GuildRank::Member, Language::En) => "member",
     }
-    let localized = match quest.id.as_str() {
-        "quest_lumber" => "Bring 5 Holz zu Edda.",
-        "quest_shovel" => "Bring 6 Holz und 4 Stein, damit Edda eine Schaufel bauen kann.",
-        "quest_hunter" => "Bring 3 Wildschweinkeulen zu Bram.",
-        "quest_fishing" => "Bring 20 Kaninchenkeulen zu Jan.",
-        _ => return quest.description.clone(),
-    };
-    localized.to_string()
 }
 
-fn message_build_success(lang: Language, kind: &str) -> String {
+fn message_guild_created(name: &str, lang: Language) -> String {
     match lang {
-        Language::De => match kind {
-            "craft_basic_axe" => "Du stellst eine Holzaxt her.".to_string(),
-            "craft_basic_pick" => "Du stellst eine Holzspitzhacke her.".to_string(),
-            "craft_arrows" => "Du stellst Pfeile her.".to_string(),
-            "hut_wood" => "Du baust eine Holzhütte.".to_string(),
-            "house_stone" => "Du baust ein Steinhaus.".to_string(),
-            "bridge_wood" => "Du baust eine Holzbrücke.".to_string(),
-            "bridge_stone" => "Du baust eine Steinbrücke.".to_string(),
-            "path" => "Du legst einen Pfad an.".to_string(),
-            "road" => "Du baust eine Straße.".to_string(),
-            "boat" => "Du baust ein Boot.".to_string(),
-            _ => "Unbekannte Bauoption.".to_string(),
-        },
-        Language::En => match kind {
-            "craft_basic_axe" => "You craft a wooden axe.".to_string(),
-            "craft_basic_pick" => "You craft a wooden pickaxe.".to_string(),
-            "craft_arrows" => "You craft arrows.".to_string(),
-            "hut_wood" => "You build a wooden hut.".to_string(),
-            "house_stone" => "You build a stone house.".to_string(),
-            "bridge_wood" => "You build a wooden bridge.".to_string(),
-            "bridge_stone" => "You build a stone bridge.".to_string(),
-            "path" => "You lay down a path.".to_string(),
-            "road" => "You build a road.".to_string(),
-            "boat" => "You build a boat.".to_string(),
-            _ => "Unknown build option.".to_string(),
-        },
+        Language::De => format!("Gilde {} gegründet.", name),
+        Language::En => format!("Founded the guild {}.", name),
     }
 }
 
-fn message_bridge_span_error(lang: Language) -> &'static str {
+fn message_guild_name_invalid(lang: Language) -> &'static str {
     match lang {
-        Language::De => {
-            "Brücken müssen 1-4 Wasserfelder überspannen und an beiden Enden Land haben."
-        }
-        Language::En => "Bridges must span 1-4 water tiles with land on both ends.",
+        Language::De => "Gildennamen brauchen 3 bis 24 Zeichen.",
+        Language::En => "Guild names need 3 to 24 characters.",
     }
 }
 
-fn message_need_shovel(lang: Language) -> &'static str {
+fn message_guild_tag_invalid(lang: Language) -> &'static str {
     match lang {
-        Language::De => "Du brauchst eine Schaufel.",
-        Language::En => "You need a shovel.",
+        Language::De => "Gildenkürzel brauchen 2 bis 4 Buchstaben oder Ziffern.",
+        Language::En => "Guild tags need 2 to 4 letters or digits.",
     }
 }
 
-fn message_not_enough_materials(lang: Language) -> &'static str {
+fn message_guild_taken(lang: Language) -> &'static str {
     match lang {
-        Language::De => "Du hast nicht genug Materialien.",
-        Language::En => "You don't have enough materials.",
+        Language::De => "Dieser Gildenname oder dieses Kürzel ist vergeben.",
+        Language::En => "That guild name or tag is taken.",
     }
 }
 
-fn message_spot_occupied(lang: Language) -> &'static str {
+fn message_guild_already_member(lang: Language) -> &'static str {
     match lang {
-        Language::De => "Der Platz ist bereits belegt.",
-        Language::En => "That spot is already occupied.",
+        Language::De => "Du bist bereits in einer Gilde.",
+        Language::En => "You are already in a guild.",
     }
 }
 
-fn message_clear_resource(lang: Language) -> &'static str {
+fn message_guild_none(lang: Language) -> &'static str {
     match lang {
-        Language::De => "Entferne zuerst die Ressource.",
-        Language::En => "Clear the resource first.",
+        Language::De => "Du bist in keiner Gilde.",
+        Language::En => "You are not in a guild.",
     }
 }
 
-fn message_build_on_land(lang: Language) -> &'static str {
+fn message_guild_no_permission(lang: Language) -> &'static str {
     match lang {
-        Language::De => "Das kannst du nur an Land bauen.",
-        Language::En => "You can only build that on land.",
+        Language::De => "Dein Gildenrang erlaubt das nicht.",
+        Language::En => "Your guild rank doesn't allow that.",
     }
 }
 
-fn message_build_on_water(lang: Language) -> &'static str {
+fn message_guild_player_taken(name: &str, lang: Language) -> String {
     match lang {
-        Language::De => "Das kannst du nur auf dem Wasser bauen.",
-        Language::En => "You can only build that on water.",
+        Language::De => format!("{} ist bereits in einer Gilde.", name),
+        Language::En => format!("{} is already in a guild.", name),
     }
 }
 
-fn message_need_water(lang: Language) -> &'static str {
+fn message_guild_invited(name: &str, lang: Language) -> String {
     match lang {
-        Language::De => "Du musst am Wasser stehen, um ein Boot zu bauen.",
-        Language::En => "You need to stand by water to build a boat.",
+        Language::De => format!("Du hast {} in die Gilde eingeladen.", name),
+        Language::En => format!("You invited {} to the guild.", name),
     }
 }
 
-fn message_unknown_build(lang: Language) -> &'static str {
+fn message_guild_no_invite(lang: Language) -> &'static str {
     match lang {
-        Language::De => "Unbekannte Bauoption.",
-        Language::En => "Unknown build option.",
+        Language::De => "Du hast keine offene Gildeneinladung.",
+        Language::En => "You have no pending guild invite.",
     }
 }
 
-fn message_need_fishing_rod(lang: Language) -> &'static str {
+fn message_guild_joined(name: &str, lang: Language) -> String {
     match lang {
-        Language::De => "Du brauchst eine Angel, um zu fischen.",
-        Language::En => "You need a fishing rod to fish.",
+        Language::De => format!("{} ist der Gilde beigetreten.", name),
+        Language::En => format!("{} joined the guild.", name),
     }
 }
 
-fn message_fishing_wait(lang: Language) -> &'static str {
+fn message_guild_member_left(name: &str, lang: Language) -> String {
     match lang {
-        Language::De => "Noch kein Biss.",
-        Language::En => "Nothing bites yet.",
+        Language::De => format!("{} hat die Gilde verlassen.", name),
+        Language::En => format!("{} left the guild.", name),
     }
 }
 
-fn message_fishing_catch(item_name: &str, lang: Language) -> String {
+fn message_guild_left(lang: Language) -> &'static str {
     match lang {
-        Language::De => format!("Du ziehst einen {} aus dem Wasser.", item_name),
-        Language::En => format!("You reel in a {}.", item_name),
+        Language::De => "Du hast die Gilde verlassen.",
+        Language::En => "You left the guild.",
     }
 }
 
-fn message_board_boat(lang: Language) -> &'static str {
+fn message_guild_kicked(lang: Language) -> &'static str {
     match lang {
-        Language::De => "Du steigst ins Boot.",
-        Language::En => "You climb into the boat.",
+        Language::De => "Du wurdest aus der Gilde entfernt.",
+        Language::En => "You were removed from the guild.",
     }
 }
 
-fn message_boat_occupied(lang: Language) -> &'static str {
+fn message_guild_rank_changed(name: &str, rank: GuildRank, lang: Language) -> String {
+    let rank = message_guild_rank_name(rank, lang);
     match lang {
-        Language::De => "Dieses Boot ist bereits besetzt.",
-        Language::En => "That boat is already occupied.",
+        Language::De => format!("{} hat jetzt den Rang {}.", name, rank),
+        Language::En => format!("{} is now a guild {}.", name, rank),
     }
 }
 
-fn message_nothing_to_remove(lang: Language) -> &'static str {
+fn message_nothing_to_claim(lang: Language) -> &'static str {
     match lang {
-        Language::De => "Nichts zum Entfernen.",
-        Language::En => "Nothing to remove.",
+        Language::De => "Hier gibt es kein Gebäude.",
+        Language::En => "There is no building here.",
     }
 }
 
-fn message_remove_own_only(lang: Language) -> &'static str {
+fn message_claim_own_only(lang: Language) -> &'static str {
     match lang {
-        Language::De => "Du kannst nur deine eigenen Gebäude entfernen.",
-        Language::En => "You can only remove your own buildings.",
+        Language::De => "Du kannst nur deine eigenen Gebäude an die Gilde übergeben.",
+        Language::En => "You can only hand your own buildings to the guild.",
     }
 }
 
-fn message_structure_removed(lang: Language) -> &'static str {
+fn message_guild_already_claimed(lang: Language) -> &'static str {
     match lang {
-        Language::De => "Gebäude entfernt.",
-        Language::En => "Structure removed.",
+        Language::De => "Dieses Gebäude gehört bereits deiner Gilde.",
+        Language::En => "That building already belongs to your guild.",
     }
 }
 
-fn message_wake_up(lang: Language) -> &'static str {
+fn message_structure_claimed(guild: &str, lang: Language) -> String {
     match lang {
-        Language::De => "Du wachst am Lagerfeuer auf.",
-        Language::En => "You wake up by the campfire.",
+        Language::De => format!("Das Gebäude gehört jetzt der Gilde {}.", guild),
+        Language::En => format!("The building now belongs to {}.", guild),
     }
 }
 
-fn message_collected(item_name: &str, count: i32, lang: Language) -> String {
+fn message_pvp_enabled(lang: Language) -> &'static str {
     match lang {
-        Language::De => format!("Gesammelt: {} x{}", item_name, count),
-        Language::En => format!("Collected {} x{}", item_name, count),
+        Language::De => "PvP aktiviert. Andere markierte Spieler können dich angreifen.",
+        Language::En => "PvP enabled. Other flagged players can attack you.",
     }
 }
 
-fn message_hit_resource(resource_name: &str, hp: i32, lang: Language) -> String {
+fn message_pvp_disabled(lang: Language) -> &'static str {
     match lang {
-        Language::De => format!("Getroffen: {} ({})", resource_name, hp),
-        Language::En => format!("Hit {} ({})", resource_name, hp),
+        Language::De => "PvP deaktiviert.",
+        Language::En => "PvP disabled.",
     }
 }
 
-fn message_need_tool(tool_name: &str, lang: Language) -> String {
+fn message_pvp_cooldown(lang: Language) -> &'static str {
     match lang {
-        Language::De => format!("Du brauchst eine {}.", tool_name),
-        Language::En => format!("You need a {}.", tool_name),
+        Language::De => "Du kannst PvP gerade nicht umschalten.",
+        Language::En => "You can't change your PvP flag yet.",
     }
 }
 
-fn message_hit_monster(monster_name: &str, hp: i32, lang: Language) -> String {
+fn message_pvp_zone_entered(zone_name: &str, lang: Language) -> String {
     match lang {
-        Language::De => format!("Getroffen: {} ({})", monster_name, hp),
-        Language::En => format!("Hit {} ({})", monster_name, hp),
+        Language::De => format!("Du betrittst {}. Hier ist PvP erlaubt!", zone_name),
+        Language::En => format!("You enter {}. PvP is allowed here!", zone_name),
     }
 }
 
-fn message_monster_hits_you(monster_name: &str, hp: i32, lang: Language) -> String {
+fn message_pvp_zone_left(lang: Language) -> &'static str {
     match lang {
-        Language::De => format!("{} trifft dich ({})", monster_name, hp),
-        Language::En => format!("{} hits you ({})", monster_name, hp),
+        Language::De => "Du verlässt die PvP-Zone.",
+        Language::En => "You leave the PvP zone.",
     }
 }
 
@@ -2930,6 +7485,135 @@ fn move_towards(
     }
 }
 
+/// A* node allowance shared by every monster in one tick, plus the per-search limits.
+struct PathBudget {
+    remaining: usize,
+    max_nodes: usize,
+    retry_ms: i64,
+}
+
+/// Picks the point a chasing monster should steer at: the target itself when the line
+/// is clear, otherwise the next tile of a cached A* path, falling back to the target.
+fn next_waypoint(
+    monster: &mut Monster,
+    tx: f32,
+    ty: f32,
+    structure_tiles: &HashMap<TileCoord, StructureTile>,
+    noise: &WorldNoise,
+    now_ms: i64,
+    path_budget: &mut PathBudget,
+) -> (f32, f32) {
+    let goal = entity_foot_tile(tx, ty);
+    if line_walkable(structure_tiles, noise, monster.x, monster.y, tx, ty) {
+        monster.path.clear();
+        monster.path_goal = None;
+        return (tx, ty);
+    }
+
+    let stale = monster.path.is_empty() || monster.path_goal != Some(goal);
+    if stale && now_ms >= monster.path_retry_ms && path_budget.remaining > 0 {
+        let start = entity_foot_tile(monster.x, monster.y);
+        let limit = path_budget.max_nodes.min(path_budget.remaining);
+        let (path, expanded) = find_path(structure_tiles, noise, start, goal, limit);
+        path_budget.remaining = path_budget.remaining.saturating_sub(expanded);
+        monster.path_goal = Some(goal);
+        match path {
+            Some(path) => monster.path = path,
+            None => {
+                monster.path.clear();
+                monster.path_retry_ms = now_ms + path_budget.retry_ms;
+            }
+        }
+    }
+
+    while let Some(&(wx, wy)) = monster.path.last() {
+        let (cx, cy) = (wx as f32 + 0.5, wy as f32 + 0.5);
+        if distance(monster.x, monster.y, cx, cy) > 0.15 {
+            return (cx, cy);
+        }
+        monster.path.pop();
+    }
+    (tx, ty)
+}
+
+fn line_walkable(
+    structure_tiles: &HashMap<TileCoord, StructureTile>,
+    noise: &WorldNoise,
+    ax: f32,
+    ay: f32,
+    bx: f32,
+    by: f32,
+) -> bool {
+    let steps = (distance(ax, ay, bx, by) / 0.5).ceil().max(1.0) as i32;
+    (1..=steps).all(|i| {
+        let t = i as f32 / steps as f32;
+        can_walk(
+            structure_tiles,
+            noise,
+            ax + (bx - ax) * t,
+            ay + (by - ay) * t,
+        )
+    })
+}
+
+/// Grid A* over walkable tiles. Returns the path as tiles from goal back to the first
+/// step (so callers can pop waypoints off the end) and the number of nodes expanded.
+fn find_path(
+    structure_tiles: &HashMap<TileCoord, StructureTile>,
+    noise: &WorldNoise,
+    start: (i32, i32),
+    goal: (i32, i32),
+    max_nodes: usize,
+) -> (Option<Vec<(i32, i32)>>, usize) {
+    let walkable = |(x, y): (i32, i32)| {
+        let (px, py) = tile_anchor_position(x, y);
+        can_walk(structure_tiles, noise, px, py)
+    };
+    if start == goal || !walkable(goal) {
+        return (None, 0);
+    }
+    let heuristic = |(x, y): (i32, i32)| (x - goal.0).abs() + (y - goal.1).abs();
+
+    let mut open = BinaryHeap::new();
+    let mut came_from: HashMap<(i32, i32), (i32, i32)> = HashMap::new();
+    let mut cost: HashMap<(i32, i32), i32> = HashMap::new();
+    open.push(Reverse((heuristic(start), 0, start)));
+    cost.insert(start, 0);
+    let mut expanded = 0;
+    while let Some(Reverse((_, g, tile))) = open.pop() {
+        if tile == goal {
+            let mut path = vec![tile];
+            let mut current = tile;
+            while let Some(&prev) = came_from.get(&current) {
+                if prev == start {
+                    break;
+                }
+                path.push(prev);
+                current = prev;
+            }
+            return (Some(path), expanded);
+        }
+        if g > cost.get(&tile).copied().unwrap_or(i32::MAX) {
+            continue;
+        }
+        expanded += 1;
+        if expanded >= max_nodes {
+            break;
+        }
+        for (dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+            let next = (tile.0 + dx, tile.1 + dy);
+            let next_cost = g + 1;
+            if next_cost >= cost.get(&next).copied().unwrap_or(i32::MAX) || !walkable(next) {
+                continue;
+            }
+            cost.insert(next, next_cost);
+            came_from.insert(next, tile);
+            open.push(Reverse((next_cost + heuristic(next), next_cost, next)));
+        }
+    }
+    (None, expanded)
+}
+
 fn move_away(
     monster: &mut Monster,
     tx: f32,
@@ -3049,9 +7733,33 @@ fn entity_foot_tile(x: f32, y: f32) -> (i32, i32) {
 }
 
 fn tile_anchor_position(x: i32, y: i32) -> (f32, f32) {
-    (x as f32 + ENTITY_FOOT_OFFSET_X, y as f32 + ENTITY_FOOT_OFFSET_Y)
+    (
+        x as f32 + ENTITY_FOOT_OFFSET_X,
+        y as f32 + ENTITY_FOOT_OFFSET_Y,
+    )
+}
+
+fn is_blocking_structure(kind: &str) -> bool {
+    matches!(
+        kind,
+        "hut_wood"
+            | "hut_wood_root"
+            | "hut_wood_block"
+            | "house_stone"
+            | "house_stone_root"
+            | "house_stone_block"
+    )
 }
 
+fn blocks_projectile(structure_tiles: &HashMap<TileCoord, StructureTile>, x: f32, y: f32) -> bool {
+    let (tile_x, tile_y) = entity_foot_tile(x, y);
+    structure_tiles
+        .get(&TileCoord {
+            x: tile_x,
+            y: tile_y,
+        })
+        .is_some_and(|structure| is_blocking_structure(&structure.kind))
+}
 
 fn can_walk(
     structure_tiles: &HashMap<TileCoord, StructureTile>,
@@ -3061,11 +7769,7 @@ fn can_walk(
 ) -> bool {
     let (tile_x, tile_y) = entity_foot_tile(x, y);
     if let Some(structure) = structure_tiles.get(&TileCoord { x: tile_x, y: tile_y }) {
-        if matches!(
-            structure.kind.as_str(),
-            "hut_wood" | "hut_wood_root" | "hut_wood_block" | "house_stone" | "house_stone_root"
-                | "house_stone_block"
-        ) {
+        if is_blocking_structure(&structure.kind) {
             return false;
         }
         if structure.kind.starts_with("bridge_") {
@@ -3086,14 +7790,7 @@ fn can_sail(
         return false;
     }
     if let Some(structure) = structure_tiles.get(&TileCoord { x: tile_x, y: tile_y }) {
-        if structure.kind.starts_with("bridge_") {
-            return false;
-        }
-        if matches!(
-            structure.kind.as_str(),
-            "hut_wood" | "hut_wood_root" | "hut_wood_block" | "house_stone" | "house_stone_root"
-                | "house_stone_block"
-        ) {
+        if structure.kind.starts_with("bridge_") || is_blocking_structure(&structure.kind) {
             return false;
         }
     }
@@ -3117,18 +7814,49 @@ fn is_tree_kind(kind: &str) -> bool {
     matches!(kind, "tree" | "apple_tree" | "pine_tree" | "palm_tree")
 }
 
-fn tree_spawn_state(seed: u64, x: i32, y: i32, now_ms: i64) -> (i32, Option<i64>) {
+fn growth_max_size(data: &GameData, config: &GameplayConfig, kind: &str) -> Option<i32> {
+    if is_tree_kind(kind) {
+        Some(config.tree_max_size)
+    } else {
+        data.resources.get(kind).and_then(|def| def.max_size)
+    }
+}
+
+fn growth_delay(
+    data: &GameData,
+    config: &GameplayConfig,
+    kind: &str,
+    seed: u64,
+    x: i32,
+    y: i32,
+) -> i64 {
+    let interval = data
+        .resources
+        .get(kind)
+        .and_then(|def| def.grow_ms)
+        .unwrap_or(config.tree_grow_interval_ms);
+    let jitter = noise_hash01(seed, x, y);
+    (interval as f32 * (0.4 + jitter * 1.2)) as i64
+}
+
+fn tree_spawn_state(
+    config: &GameplayConfig,
+    seed: u64,
+    x: i32,
+    y: i32,
+    now_ms: i64,
+) -> (i32, Option<i64>) {
     let roll = noise_hash01(seed.wrapping_add(5555), x, y);
     let size = if roll > 0.7 {
-        TREE_MAX_SIZE
+        config.tree_max_size
     } else if roll > 0.4 {
-        2
+        2.min(config.tree_max_size)
     } else {
         1
     };
-    let next_growth_ms = if size < TREE_MAX_SIZE {
+    let next_growth_ms = if size < config.tree_max_size {
         let jitter = noise_hash01(seed.wrapping_add(9999), x, y);
-        let delay = (TREE_GROW_INTERVAL_MS as f32 * (0.4 + jitter * 1.2)) as i64;
+        let delay = (config.tree_grow_interval_ms as f32 * (0.4 + jitter * 1.2)) as i64;
         Some(now_ms + delay)
     } else {
         None
@@ -3137,14 +7865,15 @@ fn tree_spawn_state(seed: u64, x: i32, y: i32, now_ms: i64) -> (i32, Option<i64>
 }
 
 fn generate_resources(
-    seed: u64,
     now_ms: i64,
     coord: ChunkCoord,
     world: &WorldConfig,
     noise: &WorldNoise,
     data: &GameData,
+    config: &GameplayConfig,
     structure_tiles: &HashMap<TileCoord, StructureTile>,
 ) -> Vec<ResourceNode> {
+    let seed = world.seed;
     let chunk_size = world.chunk_size;
     let campfire_x = world.spawn_x.round() as i32;
     let campfire_y = world.spawn_y.round() as i32;
@@ -3219,15 +7948,15 @@ fn generate_resources(
                     let mut size = 1;
                     let mut next_growth_ms = None;
                     if is_tree_kind(kind) {
-                        let (s, next) = tree_spawn_state(seed, wx, wy, now_ms);
+                        let (s, next) = tree_spawn_state(config, seed, wx, wy, now_ms);
                         size = s;
                         next_growth_ms = next;
                     } else if kind == "rock" {
                         let size_roll = noise_hash01(seed.wrapping_add(2024), wx, wy);
                         if size_roll > 0.8 {
-                            size = 3.min(ROCK_MAX_SIZE);
+                            size = 3.min(config.rock_max_size);
                         } else if size_roll > 0.5 {
-                            size = 2.min(ROCK_MAX_SIZE);
+                            size = 2.min(config.rock_max_size);
                         }
                     }
                     resources.push(ResourceNode {
@@ -3239,75 +7968,333 @@ fn generate_resources(
                         respawn_at_ms: None,
                         size,
                         next_growth_ms,
+                        owner_id: None,
+                        seed_id: None,
                     });
                 }
             }
         }
     }
-    resources
+    resources
+}
+
+fn spawn_monsters_for_chunk(
+    state: &mut GameState,
+    seed: u64,
+    coord: ChunkCoord,
+    world: &WorldConfig,
+    noise: &WorldNoise,
+    data: &GameData,
+    night: bool,
+) {
+    let land = chunk_land_fraction(noise, world.chunk_size, coord);
+    state.chunk_land.insert(coord, land);
+    let monster_defs = spawnable_monster_defs(data, night);
+    if monster_defs.is_empty() {
+        return;
+    }
+    let base = hash_u64(seed ^ (coord.x as u64).wrapping_mul(0xD1B54A32) ^ coord.y as u64);
+    let target = chunk_population_target(&state.config, land, night);
+    let count = ((base % 3) as i32 + if night { 1 } else { 0 }).min(target);
+    for i in 0..count {
+        let local_seed = hash_u64(base.wrapping_add(i as u64));
+        try_spawn_monster(state, &monster_defs, local_seed, coord, world, noise);
+    }
+}
+
+fn update_monster_population(
+    state: &mut GameState,
+    now_ms: i64,
+    world: &WorldConfig,
+    noise: &WorldNoise,
+    data: &GameData,
+) {
+    if now_ms - state.last_population_ms < state.config.population_check_ms {
+        return;
+    }
+    state.last_population_ms = now_ms;
+    let night = state.night;
+    despawn_out_of_phase_monsters(state, world.chunk_size, data, night);
+    let monster_defs = spawnable_monster_defs(data, night);
+    if monster_defs.is_empty() {
+        return;
+    }
+
+    let chunks: Vec<ChunkCoord> = state.spawned_chunks.iter().copied().collect();
+    let mut rng = rand::thread_rng();
+    for coord in chunks {
+        if state.monsters.len() >= state.config.max_monsters {
+            break;
+        }
+        let respawn_at = state.chunk_respawn_at.get(&coord).copied().unwrap_or(0);
+        if now_ms < respawn_at {
+            continue;
+        }
+        let count = state.chunk_monsters.get(&coord).copied().unwrap_or(0);
+        let land = state.chunk_land.get(&coord).copied().unwrap_or(0.0);
+        if count >= chunk_population_target(&state.config, land, night) {
+            continue;
+        }
+        try_spawn_monster(state, &monster_defs, rng.gen(), coord, world, noise);
+        state
+            .chunk_respawn_at
+            .insert(coord, now_ms + state.config.monster_respawn_ms);
+    }
+}
+
+// Kinds that are out of phase stop spawning right away, but live ones only
+// leave once nobody can see them and nobody has hit them, so a fight that runs
+// past dawn is never cut short.
+fn despawn_out_of_phase_monsters(
+    state: &mut GameState,
+    chunk_size: i32,
+    data: &GameData,
+    night: bool,
+) {
+    let centers: Vec<ChunkCoord> = state
+        .players
+        .values()
+        .map(|player| chunk_coord_for_position(player.x, player.y, chunk_size))
+        .collect();
+    let radius = state.config.entity_visibility_radius;
+    let chunk_monsters = &mut state.chunk_monsters;
+    state.monsters.retain(|_, monster| {
+        let in_phase = data
+            .monsters
+            .get(&monster.kind)
+            .map(|def| def.active.allows(night))
+            .unwrap_or(true);
+        if in_phase || monster.boss || monster.target.is_some() || !monster.damage_by.is_empty() {
+            return true;
+        }
+        let coord = chunk_coord_for_position(monster.x, monster.y, chunk_size);
+        let seen = centers
+            .iter()
+            .any(|center| chunk_in_radius(*center, coord, radius));
+        if !seen {
+            release_monster_home(chunk_monsters, monster.home);
+        }
+        seen
+    });
+}
+
+fn release_monster_home(chunk_monsters: &mut HashMap<ChunkCoord, i32>, home: ChunkCoord) {
+    if let Some(count) = chunk_monsters.get_mut(&home) {
+        *count -= 1;
+        if *count <= 0 {
+            chunk_monsters.remove(&home);
+        }
+    }
+}
+
+fn spawnable_monster_defs(data: &GameData, night: bool) -> Vec<&MonsterDef> {
+    let mut monster_defs: Vec<&MonsterDef> = data
+        .monsters
+        .values()
+        .filter(|def| !def.boss && def.active.allows(night))
+        .collect();
+    monster_defs.sort_by(|a, b| a.id.cmp(&b.id));
+    monster_defs
+}
+
+fn chunk_land_fraction(noise: &WorldNoise, chunk_size: i32, coord: ChunkCoord) -> f32 {
+    let step = (chunk_size / 4).max(1);
+    let mut samples = 0;
+    let mut land = 0;
+    for ly in (0..chunk_size).step_by(step as usize) {
+        for lx in (0..chunk_size).step_by(step as usize) {
+            samples += 1;
+            if tile_at(noise, coord.x * chunk_size + lx, coord.y * chunk_size + ly) != TILE_WATER {
+                land += 1;
+            }
+        }
+    }
+    land as f32 / samples.max(1) as f32
 }
 
-fn spawn_monsters_for_chunk(
+fn chunk_population_target(config: &GameplayConfig, land_fraction: f32, night: bool) -> i32 {
+    let base = if night {
+        config.night_chunk_population
+    } else {
+        config.day_chunk_population
+    };
+    ((base * land_fraction).round() as i32).min(config.max_monsters_per_chunk)
+}
+
+fn try_spawn_monster(
     state: &mut GameState,
-    seed: u64,
+    monster_defs: &[&MonsterDef],
+    roll_seed: u64,
     coord: ChunkCoord,
     world: &WorldConfig,
     noise: &WorldNoise,
+) -> bool {
+    let chunk_size = world.chunk_size;
+    let lx = (roll_seed % chunk_size as u64) as i32;
+    let ly = ((roll_seed >> 8) % chunk_size as u64) as i32;
+    let wx = coord.x * chunk_size + lx;
+    let wy = coord.y * chunk_size + ly;
+    let tile = tile_at(noise, wx, wy);
+    if tile == TILE_WATER {
+        return false;
+    }
+    let candidates: Vec<&MonsterDef> = monster_defs
+        .iter()
+        .copied()
+        .filter(|def| {
+            def.biomes.is_empty() || def.biomes.iter().any(|biome| biome == tile_name(tile))
+        })
+        .collect();
+    let total_weight: u32 = candidates.iter().map(|def| def.spawn_weight.max(1)).sum();
+    if total_weight == 0 {
+        return false;
+    }
+    let mut roll = ((roll_seed >> 16) % total_weight as u64) as u32;
+    let mut chosen = candidates[0];
+    for def in &candidates {
+        let weight = def.spawn_weight.max(1);
+        if roll < weight {
+            chosen = def;
+            break;
+        }
+        roll -= weight;
+    }
+
+    let (spawn_x, spawn_y) = tile_anchor_position(wx, wy);
+    if in_spawn_safe_zone(state, world, spawn_x, spawn_y) {
+        return false;
+    }
+    if state.players.values().any(|player| {
+        distance(player.x, player.y, spawn_x, spawn_y)
+            < state.config.monster_spawn_min_player_distance
+    }) {
+        return false;
+    }
+    let in_chunk = state.chunk_monsters.get(&coord).copied().unwrap_or(0);
+    if in_chunk >= state.config.max_monsters_per_chunk {
+        return false;
+    }
+
+    spawn_monster(state, chosen, spawn_x, spawn_y, coord);
+    true
+}
+
+fn spawn_monster(state: &mut GameState, def: &MonsterDef, x: f32, y: f32, home: ChunkCoord) -> u64 {
+    let monster_id = state.next_id();
+    *state.chunk_monsters.entry(home).or_default() += 1;
+    let hp = if state.night {
+        ((def.hp as f32 * def.night_hp_factor).round() as i32).max(1)
+    } else {
+        def.hp
+    };
+    state.monsters.insert(
+        monster_id,
+        Monster {
+            id: monster_id,
+            kind: def.id.clone(),
+            x,
+            y,
+            hp,
+            boss: def.boss,
+            target: None,
+            wander_dir: (0.0, 0.0),
+            wander_until_ms: 0,
+            last_attack_ms: 0,
+            last_special_ms: 0,
+            phase: 0,
+            flee_dir: None,
+            flee_next_sample_ms: 0,
+            home,
+            spawn_x: x,
+            spawn_y: y,
+            provoked_until_ms: 0,
+            returning: false,
+            path: Vec::new(),
+            path_goal: None,
+            path_retry_ms: 0,
+            damage_by: HashMap::new(),
+        },
+    );
+    monster_id
+}
+
+fn update_boss_spawns(
+    state: &mut GameState,
+    now_ms: i64,
+    world: &WorldConfig,
+    noise: &WorldNoise,
     data: &GameData,
 ) {
-    let mut monster_defs: Vec<&MonsterDef> = data.monsters.values().collect();
-    monster_defs.sort_by(|a, b| a.id.cmp(&b.id));
-    if monster_defs.is_empty() {
+    if now_ms < state.next_boss_spawn_ms {
         return;
     }
-    let total_weight: u32 = monster_defs
-        .iter()
-        .map(|def| def.spawn_weight.max(1))
-        .sum();
-    if total_weight == 0 {
+    let first_check = state.next_boss_spawn_ms == 0;
+    state.next_boss_spawn_ms = now_ms + state.config.boss_spawn_interval_ms;
+    if first_check || state.monsters.values().any(|monster| monster.boss) {
         return;
     }
+    let night = state.night;
+    let mut bosses: Vec<&MonsterDef> = data
+        .monsters
+        .values()
+        .filter(|def| def.boss && def.active.allows(night))
+        .collect();
+    bosses.sort_by(|a, b| a.id.cmp(&b.id));
+    let players: Vec<(f32, f32)> = state.players.values().map(|p| (p.x, p.y)).collect();
+    let mut rng = rand::thread_rng();
+    let (def, (px, py)) = match (bosses.choose(&mut rng), players.choose(&mut rng)) {
+        (Some(def), Some(pos)) => (*def, *pos),
+        _ => return,
+    };
 
-    let chunk_size = world.chunk_size;
-    let base = hash_u64(seed ^ (coord.x as u64).wrapping_mul(0xD1B54A32) ^ coord.y as u64);
-    let count = (base % 3) as i32;
-    for i in 0..count {
-        let local_seed = hash_u64(base.wrapping_add(i as u64));
-        let mut roll = (local_seed % total_weight as u64) as u32;
-        let mut chosen = monster_defs[0];
-        for def in &monster_defs {
-            let weight = def.spawn_weight.max(1);
-            if roll < weight {
-                chosen = def;
-                break;
+    let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+    let (tile_x, tile_y) = entity_foot_tile(
+        px + angle.cos() * state.config.boss_spawn_distance,
+        py + angle.sin() * state.config.boss_spawn_distance,
+    );
+    let (x, y) = tile_anchor_position(tile_x, tile_y);
+    if !can_walk(&state.structure_tiles, noise, x, y) || in_spawn_safe_zone(state, world, x, y) {
+        // Try again soon rather than waiting a full interval.
+        state.next_boss_spawn_ms = now_ms + state.config.boss_spawn_interval_ms / 10;
+        return;
+    }
+    let home = chunk_coord_for_position(x, y, world.chunk_size);
+    spawn_monster(state, def, x, y, home);
+    broadcast_localized(state, |lang| {
+        message_boss_spawned(
+            &localize_monster_name(data, &def.id, lang),
+            tile_x,
+            tile_y,
+            lang,
+        )
+    });
+}
+
+fn in_spawn_safe_zone(state: &GameState, world: &WorldConfig, x: f32, y: f32) -> bool {
+    if distance(x, y, world.spawn_x, world.spawn_y) < state.config.spawn_safe_radius {
+        return true;
+    }
+    let (tile_x, tile_y) = entity_foot_tile(x, y);
+    for dy in -state.config.structure_safe_radius..=state.config.structure_safe_radius {
+        for dx in -state.config.structure_safe_radius..=state.config.structure_safe_radius {
+            if state.structure_tiles.contains_key(&TileCoord {
+                x: tile_x + dx,
+                y: tile_y + dy,
+            }) {
+                return true;
             }
-            roll -= weight;
         }
-        let lx = (local_seed % chunk_size as u64) as i32;
-        let ly = ((local_seed >> 8) % chunk_size as u64) as i32;
-        let wx = coord.x * chunk_size + lx;
-        let wy = coord.y * chunk_size + ly;
-        if tile_at(noise, wx, wy) == TILE_WATER {
-            continue;
-        }
-        let monster_id = state.next_id();
-        let (spawn_x, spawn_y) = tile_anchor_position(wx, wy);
-        state.monsters.insert(
-            monster_id,
-            Monster {
-                id: monster_id,
-                kind: chosen.id.clone(),
-                x: spawn_x,
-                y: spawn_y,
-                hp: chosen.hp,
-                target: None,
-                wander_dir: (0.0, 0.0),
-                wander_until_ms: 0,
-                last_attack_ms: 0,
-                flee_dir: None,
-                flee_next_sample_ms: 0,
-            },
-        );
+    }
+    false
+}
+
+fn tile_name(tile: u8) -> &'static str {
+    match tile {
+        TILE_WATER => "water",
+        TILE_SAND => "sand",
+        TILE_DIRT => "dirt",
+        TILE_FLOWER => "flowers",
+        _ => "grass",
     }
 }
 
@@ -3339,78 +8326,735 @@ fn tile_at(noise: &WorldNoise, x: i32, y: i32) -> u8 {
     }
 }
 
-fn is_adjacent_to_water(noise: &WorldNoise, x: i32, y: i32) -> bool {
-    for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
-        if tile_at(noise, x + dx, y + dy) == TILE_WATER {
-            return true;
+fn is_adjacent_to_water(noise: &WorldNoise, x: i32, y: i32) -> bool {
+    for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+        if tile_at(noise, x + dx, y + dy) == TILE_WATER {
+            return true;
+        }
+    }
+    false
+}
+
+/// Base colors of the tile art in `public/assets/tiles`, indexed by tile id.
+const TILE_COLORS: [[u8; 3]; 5] = [
+    [0x9f, 0xe8, 0x7a],
+    [0x4a, 0xa8, 0xff],
+    [0xf4, 0xd3, 0x8b],
+    [0xb6, 0x80, 0x4a],
+    [0xc2, 0xe6, 0x8c],
+];
+
+/// RGB pixels for a `width` by `height` grid sampled every `step` tiles from `x0,y0`,
+/// each sample drawn as `scale` by `scale` pixels.
+fn render_tiles(
+    noise: &WorldNoise,
+    x0: i32,
+    y0: i32,
+    width: u32,
+    height: u32,
+    step: i32,
+    scale: u32,
+) -> Vec<u8> {
+    let row_len = (width * scale * 3) as usize;
+    let mut pixels = Vec::with_capacity(row_len * (height * scale) as usize);
+    for ty in 0..height as i32 {
+        let mut row = Vec::with_capacity(row_len);
+        for tx in 0..width as i32 {
+            let color = TILE_COLORS
+                .get(tile_at(noise, x0 + tx * step, y0 + ty * step) as usize)
+                .unwrap_or(&[0, 0, 0]);
+            for _ in 0..scale {
+                row.extend_from_slice(color);
+            }
+        }
+        for _ in 0..scale {
+            pixels.extend_from_slice(&row);
+        }
+    }
+    pixels
+}
+
+/// Encodes 8-bit RGB pixels as a PNG.
+fn encode_png(width: u32, height: u32, rgb: &[u8]) -> Vec<u8> {
+    let row_len = width as usize * 3;
+    let mut raw = Vec::with_capacity((row_len + 1) * height as usize);
+    for row in rgb.chunks(row_len) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    // Writing to a Vec cannot fail.
+    let _ = encoder.write_all(&raw);
+    let zlib = encoder.finish().unwrap_or_default();
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&width.to_be_bytes());
+    header.extend_from_slice(&height.to_be_bytes());
+    header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    png_chunk(&mut png, b"IHDR", &header);
+    png_chunk(&mut png, b"IDAT", &zlib);
+    png_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn png_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    png.extend_from_slice(&crc32(kind.iter().chain(data)).to_be_bytes());
+}
+
+/// Pixels of a PNG written by `encode_png`; other color types and row filters are
+/// rejected.
+fn decode_png(png: &[u8]) -> Option<(u32, u32, Vec<u8>)> {
+    let (mut width, mut height) = (0, 0);
+    let mut zlib = Vec::new();
+    let mut pos = 8;
+    while pos + 12 <= png.len() {
+        let len = u32::from_be_bytes(png.get(pos..pos + 4)?.try_into().ok()?) as usize;
+        let data = png.get(pos + 8..pos + 8 + len)?;
+        match png.get(pos + 4..pos + 8)? {
+            b"IHDR" => {
+                if data.get(8..10)? != [8, 2] {
+                    return None;
+                }
+                width = u32::from_be_bytes(data.get(0..4)?.try_into().ok()?);
+                height = u32::from_be_bytes(data.get(4..8)?.try_into().ok()?);
+            }
+            b"IDAT" => zlib.extend_from_slice(data),
+            _ => {}
+        }
+        pos += 12 + len;
+    }
+
+    let mut raw = Vec::new();
+    ZlibDecoder::new(zlib.as_slice())
+        .read_to_end(&mut raw)
+        .ok()?;
+
+    let row_len = width as usize * 3;
+    if raw.len() != (row_len + 1) * height as usize {
+        return None;
+    }
+    if raw.chunks(row_len + 1).any(|row| row[0] != 0) {
+        return None;
+    }
+    let pixels = raw
+        .chunks(row_len + 1)
+        .flat_map(|row| row[1..].iter().copied())
+        .collect();
+    Some((width, height, pixels))
+}
+
+fn crc32<'a>(bytes: impl Iterator<Item = &'a u8>) -> u32 {
+    let mut crc = u32::MAX;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn find_nearest_land_anchor(
+    noise: &WorldNoise,
+    start_x: i32,
+    start_y: i32,
+    max_radius: i32,
+) -> Option<(f32, f32)> {
+    if tile_at(noise, start_x, start_y) != TILE_WATER {
+        return Some(tile_anchor_position(start_x, start_y));
+    }
+    for radius in 1..=max_radius {
+        for dx in -radius..=radius {
+            for dy in -radius..=radius {
+                if dx.abs() != radius && dy.abs() != radius {
+                    continue;
+                }
+                let x = start_x + dx;
+                let y = start_y + dy;
+                if tile_at(noise, x, y) != TILE_WATER {
+                    return Some(tile_anchor_position(x, y));
+                }
+            }
+        }
+    }
+    None
+}
+
+fn noise_hash01(seed: u64, x: i32, y: i32) -> f32 {
+    let value = hash_u64(
+        seed ^ (x as u64).wrapping_mul(0x9E3779B97F4A7C15)
+            ^ (y as u64).wrapping_mul(0xC2B2AE3D27D4EB4F),
+    );
+    (value % 10_000) as f32 / 10_000.0
+}
+
+fn hash_u64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9E3779B97F4A7C15);
+    let mut z = x;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+fn load_json<T: DeserializeOwned>(path: &str) -> AppResult<T> {
+    let data = std::fs::read_to_string(path).map_err(|err| format!("{}: {}", path, err))?;
+    Ok(serde_json::from_str(&data).map_err(|err| format!("{}: {}", path, err))?)
+}
+
+fn load_game_data() -> AppResult<GameData> {
+    let items: Vec<ItemDef> = load_json("data/items.json")?;
+    let resources: Vec<ResourceDef> = load_json("data/resources.json")?;
+    let monsters: Vec<MonsterDef> = load_json("data/monsters.json")?;
+    let quests: Vec<QuestDef> = load_json("data/quests.json")?;
+    let npcs: Vec<NpcDef> = load_json("data/npcs.json")?;
+    let npcs: Vec<NpcDef> = npcs
+        .into_iter()
+        .map(|mut npc| {
+            npc.x += ENTITY_FOOT_OFFSET_X;
+            npc.y += ENTITY_FOOT_OFFSET_Y;
+            npc
+        })
+        .collect();
+
+    let chat_filter: WordFilter = load_json("data/chat_filter.json")?;
+
+    let errors = validate_game_data(&items, &resources, &monsters, &quests, &npcs);
+    if !errors.is_empty() {
+        return Err(format!(
+            "invalid game data ({} errors):\n  {}",
+            errors.len(),
+            errors.join("\n  ")
+        )
+        .into());
+    }
+
+    Ok(GameData::new(
+        items,
+        resources,
+        monsters,
+        quests,
+        npcs,
+        chat_filter,
+    ))
+}
+
+/// Ids the server refers to by name: starting gear, build costs, recipes, fishing and the
+/// resource kinds world generation places.
+const REQUIRED_ITEMS: [&str; 9] = [
+    "wood",
+    "stone",
+    "fish",
+    "arrow",
+    "basic_axe",
+    "basic_pick",
+    "basic_shovel",
+    "rusty_sword",
+    "fishing_rod",
+];
+const REQUIRED_RESOURCES: [&str; 5] = ["tree", "apple_tree", "pine_tree", "palm_tree", "rock"];
+
+/// Checks ids and cross-references between the data files. Every problem is reported as
+/// `<file>[<index>].<field>: <message>` so all of them can be fixed in one go.
+fn validate_game_data(
+    items: &[ItemDef],
+    resources: &[ResourceDef],
+    monsters: &[MonsterDef],
+    quests: &[QuestDef],
+    npcs: &[NpcDef],
+) -> Vec<String> {
+    let mut errors = Vec::new();
+    let item_ids = collect_ids(
+        "data/items.json",
+        items.iter().map(|item| &item.id),
+        &mut errors,
+    );
+    let resource_ids = collect_ids(
+        "data/resources.json",
+        resources.iter().map(|res| &res.id),
+        &mut errors,
+    );
+    let monster_ids = collect_ids(
+        "data/monsters.json",
+        monsters.iter().map(|monster| &monster.id),
+        &mut errors,
+    );
+    collect_ids(
+        "data/quests.json",
+        quests.iter().map(|quest| &quest.id),
+        &mut errors,
+    );
+    let npc_ids = collect_ids(
+        "data/npcs.json",
+        npcs.iter().map(|npc| &npc.id),
+        &mut errors,
+    );
+
+    for (i, item) in items.iter().enumerate() {
+        if let Some(ammo) = &item.ammo_for {
+            let path = format!("data/items.json[{}].ammo_for", i);
+            check_id(&mut errors, &item_ids, "item", path, ammo);
+        }
+        if let Some(kind) = &item.plants {
+            let path = format!("data/items.json[{}].plants", i);
+            check_id(&mut errors, &resource_ids, "resource", path, kind);
+        }
+    }
+    for (i, res) in resources.iter().enumerate() {
+        for (j, drop) in res.drops.iter().enumerate() {
+            let path = format!("data/resources.json[{}].drops[{}].id", i, j);
+            check_id(&mut errors, &item_ids, "item", path, &drop.id);
+        }
+    }
+    for (i, monster) in monsters.iter().enumerate() {
+        if let Some(drop) = &monster.drop {
+            let path = format!("data/monsters.json[{}].drop.id", i);
+            check_id(&mut errors, &item_ids, "item", path, &drop.id);
+        }
+        for (j, loot) in monster.loot.iter().enumerate() {
+            let path = format!("data/monsters.json[{}].loot[{}].id", i, j);
+            check_id(&mut errors, &item_ids, "item", path, &loot.id);
+        }
+        if monster.night_hp_factor <= 0.0 || monster.night_damage_factor <= 0.0 {
+            errors.push(format!(
+                "data/monsters.json[{}]: night_hp_factor and night_damage_factor must be positive",
+                i
+            ));
+        }
+        for (j, phase) in monster.phases.iter().enumerate() {
+            let summon = phase
+                .special
+                .as_ref()
+                .filter(|special| special.kind == SpecialAttackKind::Summon);
+            if let Some(id) = summon.and_then(|special| special.id.as_ref()) {
+                let path = format!("data/monsters.json[{}].phases[{}].special.id", i, j);
+                check_id(&mut errors, &monster_ids, "monster", path, id);
+            }
+        }
+    }
+    // Quests are looked up by NPC, so a second quest for the same NPC would hide the first.
+    let mut quest_npcs = HashSet::new();
+    for (i, quest) in quests.iter().enumerate() {
+        let path = format!("data/quests.json[{}].npc_id", i);
+        if !quest_npcs.insert(quest.npc_id.as_str()) {
+            errors.push(format!(
+                "{}: npc \"{}\" already has a quest",
+                path, quest.npc_id
+            ));
+        }
+        check_id(&mut errors, &npc_ids, "npc", path, &quest.npc_id);
+        for (j, stack) in quest.requires.iter().enumerate() {
+            let path = format!("data/quests.json[{}].requires[{}].id", i, j);
+            check_id(&mut errors, &item_ids, "item", path, &stack.id);
+        }
+        for (j, kill) in quest.kills.iter().enumerate() {
+            let path = format!("data/quests.json[{}].kills[{}].monster", i, j);
+            check_id(&mut errors, &monster_ids, "monster", path, &kill.monster);
+        }
+        for (j, stack) in quest.rewards.iter().enumerate() {
+            let path = format!("data/quests.json[{}].rewards[{}].id", i, j);
+            check_id(&mut errors, &item_ids, "item", path, &stack.id);
+        }
+    }
+    for id in REQUIRED_ITEMS {
+        if !item_ids.contains(id) {
+            errors.push(format!(
+                "data/items.json: missing item \"{}\" used by the server",
+                id
+            ));
+        }
+    }
+    for id in REQUIRED_RESOURCES {
+        if !resource_ids.contains(id) {
+            errors.push(format!(
+                "data/resources.json: missing resource \"{}\" used by the server",
+                id
+            ));
+        }
+    }
+    errors
+}
+
+fn check_id(errors: &mut Vec<String>, known: &HashSet<&str>, what: &str, path: String, id: &str) {
+    if !known.contains(id) {
+        errors.push(format!("{}: unknown {} \"{}\"", path, what, id));
+    }
+}
+
+/// Collects the ids of one data file, reporting duplicates (the later entry would silently
+/// replace the earlier one).
+fn collect_ids<'a>(
+    file: &str,
+    ids: impl Iterator<Item = &'a String>,
+    errors: &mut Vec<String>,
+) -> HashSet<&'a str> {
+    let mut seen = HashSet::new();
+    for (i, id) in ids.enumerate() {
+        if !seen.insert(id.as_str()) {
+            errors.push(format!("{}[{}].id: duplicate id \"{}\"", file, i, id));
+        }
+    }
+    seen
+}
+
+/// `--check-data`: loads and validates the server config, world and game data, prints the
+/// result and exits without serving.
+fn check_data() -> ! {
+    let result = load_server_config()
+        .and_then(|_| load_json::<WorldConfig>("data/world.json"))
+        .and_then(|_| load_game_data());
+    match result {
+        Ok(data) => {
+            println!(
+                "game data ok: {} items, {} resources, {} monsters, {} quests, {} npcs",
+                data.items.len(),
+                data.resources.len(),
+                data.monsters.len(),
+                data.quests_by_npc.len(),
+                data.npcs.len()
+            );
+            std::process::exit(0);
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Value of `--name <value>` in CLI arguments.
+fn cli_option<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|i| args.get(i + 1))
+        .map(String::as_str)
+}
+
+fn cli_number<T: std::str::FromStr>(args: &[String], name: &str, default: T) -> AppResult<T> {
+    match cli_option(args, name) {
+        Some(value) => value
+            .parse()
+            .map_err(|_| format!("{} expects a number, got {:?}", name, value).into()),
+        None => Ok(default),
+    }
+}
+
+/// Mongo access for the offline commands, using the same config as the server.
+async fn open_store() -> AppResult<GameStore> {
+    let server_config = load_server_config()?;
+    GameStore::new(&server_config.mongodb_uri, Arc::new(Metrics::default())).await
+}
+
+/// `render-map`: draws a tile region of the configured world with `tile_at`.
+fn render_map_command(args: &[String]) -> AppResult<()> {
+    let world: WorldConfig = load_json("data/world.json")?;
+    let noise = WorldNoise::new(world.seed);
+    let width = cli_number(args, "--width", 256u32)?;
+    let height = cli_number(args, "--height", 256u32)?;
+    let scale = cli_number(args, "--scale", 1u32)?;
+    let too_large = width.saturating_mul(scale) > MAP_MAX_PIXELS
+        || height.saturating_mul(scale) > MAP_MAX_PIXELS;
+    if width == 0 || height == 0 || scale == 0 || too_large {
+        return Err(format!(
+            "width, height and scale must be positive and the image at most {} px per side",
+            MAP_MAX_PIXELS
+        )
+        .into());
+    }
+    let center_x = cli_number(args, "--x", world.spawn_x.round() as i32)?;
+    let center_y = cli_number(args, "--y", world.spawn_y.round() as i32)?;
+    let out = cli_option(args, "--out").unwrap_or("map.png");
+    let (x0, y0) = (center_x - width as i32 / 2, center_y - height as i32 / 2);
+    let pixels = render_tiles(&noise, x0, y0, width, height, 1, scale);
+    std::fs::write(out, encode_png(width * scale, height * scale, &pixels))?;
+    println!(
+        "wrote {} ({}x{} tiles from {},{}, seed {})",
+        out, width, height, x0, y0, world.seed
+    );
+    Ok(())
+}
+
+/// Persistent world state as written by `export-world`.
+#[derive(Serialize, Deserialize)]
+struct WorldExport {
+    seed: u64,
+    exported_ms: i64,
+    structures: Vec<StructureDoc>,
+    boats: Vec<BoatDoc>,
+    players: Vec<PlayerDoc>,
+}
+
+impl WorldExport {
+    /// Duplicate ids or tiles would leave the restored world inconsistent, so they are
+    /// rejected before anything is written.
+    fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        let mut structure_ids = HashSet::new();
+        let mut tiles = HashSet::new();
+        for (i, structure) in self.structures.iter().enumerate() {
+            if !structure_ids.insert(structure.id) {
+                errors.push(format!("structures[{}]: duplicate id {}", i, structure.id));
+            }
+            if !tiles.insert((structure.x, structure.y)) {
+                errors.push(format!(
+                    "structures[{}]: second structure on tile {},{}",
+                    i, structure.x, structure.y
+                ));
+            }
+        }
+        let mut boat_ids = HashSet::new();
+        for (i, boat) in self.boats.iter().enumerate() {
+            if !boat_ids.insert(boat.id) {
+                errors.push(format!("boats[{}]: duplicate id {}", i, boat.id));
+            }
+        }
+        let mut player_ids = HashSet::new();
+        for (i, player) in self.players.iter().enumerate() {
+            if player.id.is_empty() {
+                errors.push(format!("players[{}]: empty id", i));
+            } else if !player_ids.insert(player.id.as_str()) {
+                errors.push(format!("players[{}]: duplicate id {:?}", i, player.id));
+            }
+        }
+        errors
+    }
+}
+
+async fn export_world_command(args: &[String]) -> AppResult<()> {
+    let path = args.first().ok_or("usage: export-world FILE")?;
+    let world: WorldConfig = load_json("data/world.json")?;
+    let store = open_store().await?;
+    let export = WorldExport {
+        seed: world.seed,
+        exported_ms: now_millis(),
+        structures: store.load_structures().await?,
+        boats: store.load_boats().await?,
+        players: store.load_players().await?,
+    };
+    std::fs::write(path, serde_json::to_string_pretty(&export)?)?;
+    println!(
+        "exported {} structure tiles, {} boats and {} players to {}",
+        export.structures.len(),
+        export.boats.len(),
+        export.players.len(),
+        path
+    );
+    Ok(())
+}
+
+/// Structures and boats are replaced wholesale so the export is restored exactly; players
+/// are upserted. Run it with the server stopped, or it will save its own copy over it.
+async fn import_world_command(args: &[String]) -> AppResult<()> {
+    let path = args
+        .iter()
+        .find(|arg| !arg.starts_with("--"))
+        .ok_or("usage: import-world FILE [--force]")?;
+    let force = args.iter().any(|arg| arg == "--force");
+    let export: WorldExport = load_json(path)?;
+    let world: WorldConfig = load_json("data/world.json")?;
+    if export.seed != world.seed && !force {
+        return Err(format!(
+            "{} was exported from seed {} but data/world.json uses seed {}; pass --force to import anyway",
+            path, export.seed, world.seed
+        )
+        .into());
+    }
+    let errors = export.validate();
+    if !errors.is_empty() {
+        return Err(format!("{} is not a valid export: {}", path, errors.join("; ")).into());
+    }
+    let store = open_store().await?;
+    store.replace_structures(&export.structures).await?;
+    store.replace_boats(&export.boats).await?;
+    for player in &export.players {
+        store.save_player(player).await?;
+    }
+    println!(
+        "imported {} structure tiles, {} boats and {} players from {}",
+        export.structures.len(),
+        export.boats.len(),
+        export.players.len(),
+        path
+    );
+    Ok(())
+}
+
+async fn inspect_player_command(args: &[String]) -> AppResult<()> {
+    let id = args.first().ok_or("usage: inspect-player ID")?;
+    let store = open_store().await?;
+    let player = match store.load_player(id).await? {
+        Some(player) => player,
+        None => {
+            eprintln!("no player with id {}", id);
+            std::process::exit(1);
         }
-    }
-    false
+    };
+    let ban = store.find_active_ban(id, None, now_millis()).await?;
+    let report = serde_json::json!({ "player": player, "active_ban": ban });
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    Ok(())
 }
 
-fn find_nearest_land_anchor(
-    noise: &WorldNoise,
-    start_x: i32,
-    start_y: i32,
-    max_radius: i32,
-) -> Option<(f32, f32)> {
-    if tile_at(noise, start_x, start_y) != TILE_WATER {
-        return Some(tile_anchor_position(start_x, start_y));
-    }
-    for radius in 1..=max_radius {
-        for dx in -radius..=radius {
-            for dy in -radius..=radius {
-                if dx.abs() != radius && dy.abs() != radius {
-                    continue;
-                }
-                let x = start_x + dx;
-                let y = start_y + dy;
-                if tile_at(noise, x, y) != TILE_WATER {
-                    return Some(tile_anchor_position(x, y));
-                }
+/// Loads `data/*.json` again and swaps it in under the game state lock. A file that fails
+/// to load leaves the running data untouched.
+async fn reload_game_data(app_state: &AppState) -> AppResult<DataReloadReport> {
+    let data = Arc::new(load_game_data()?);
+    let mut state = app_state.state.write().await;
+    *app_state
+        .data
+        .write()
+        .unwrap_or_else(|err| err.into_inner()) = data.clone();
+    let report = migrate_to_game_data(&mut state, &data, app_state.world.chunk_size);
+    info!(
+        "game data reloaded: {} items, {} resources, {} monsters, {} quests, {} npcs; removed {} monsters, {} resource nodes, {} ground items, {} quest kill counts; {} unknown inventory items kept",
+        report.items,
+        report.resources,
+        report.monsters,
+        report.quests,
+        report.npcs,
+        report.removed_monsters,
+        report.removed_resources,
+        report.removed_ground_items,
+        report.removed_quest_kills,
+        report.unknown_inventory_items
+    );
+    Ok(report)
+}
+
+/// Drops monsters, resource nodes, ground items and quest kill counts whose definitions
+/// vanished in a reload, counts inventory entries left without an item, and resends every inventory and the NPC list so renamed, moved or removed entries show up.
+fn migrate_to_game_data(
+    state: &mut GameState,
+    data: &GameData,
+    chunk_size: i32,
+) -> DataReloadReport {
+    let monsters_before = state.monsters.len();
+    let chunk_monsters = &mut state.chunk_monsters;
+    state.monsters.retain(|_, monster| {
+        let known = data.monsters.contains_key(&monster.kind);
+        if !known {
+            release_monster_home(chunk_monsters, monster.home);
+        }
+        known
+    });
+    let removed_monsters = monsters_before - state.monsters.len();
+
+    let mut removed_nodes = Vec::new();
+    for nodes in state
+        .resources
+        .values_mut()
+        .chain(state.planted.values_mut())
+    {
+        nodes.retain(|node| {
+            let known = data.resources.contains_key(&node.kind);
+            if !known {
+                removed_nodes.push(node.clone());
             }
+            known
+        });
+    }
+    for node in &removed_nodes {
+        if node.owner_id.is_some() {
+            state
+                .planting_writes
+                .push(PlantingWrite::Delete(node.id as i64));
         }
+        send_to_players_in_chunk(
+            state,
+            chunk_size,
+            chunk_coord_for_tile(node.x, node.y, chunk_size),
+            ServerMessage::ResourceUpdate {
+                resource: ResourceNodePublic::from(node.clone()),
+                state: "removed".to_string(),
+            },
+        );
     }
-    None
-}
 
-fn noise_hash01(seed: u64, x: i32, y: i32) -> f32 {
-    let value = hash_u64(
-        seed ^ (x as u64).wrapping_mul(0x9E3779B97F4A7C15)
-            ^ (y as u64).wrapping_mul(0xC2B2AE3D27D4EB4F),
-    );
-    (value % 10_000) as f32 / 10_000.0
-}
+    let ground_before = state.ground_items.len();
+    state
+        .ground_items
+        .retain(|_, item| data.items.contains_key(&item.item));
+    let removed_ground_items = ground_before - state.ground_items.len();
+
+    // Kill counts for vanished monsters can never finish a quest again. Unknown inventory
+    // items are only counted: dropping them would destroy items over a typo in a reload.
+    let mut removed_quest_kills = 0;
+    let mut unknown_inventory_items = 0;
+    for player in state.players.values_mut() {
+        let kills_before = player.quest_kills.len();
+        player
+            .quest_kills
+            .retain(|kind, _| data.monsters.contains_key(kind));
+        removed_quest_kills += kills_before - player.quest_kills.len();
+        unknown_inventory_items += player
+            .inventory
+            .keys()
+            .filter(|id| !data.items.contains_key(*id))
+            .count();
+    }
+
+    for (id, player) in &state.players {
+        if let Some(sender) = state.clients.get(id) {
+            let lang = player_language(state, id);
+            let items = build_inventory_items(&player.inventory, data, lang);
+            let _ = sender.send(ServerMessage::Inventory { items });
+            let _ = sender.send(ServerMessage::Npcs {
+                npcs: npcs_public(data, lang),
+            });
+        }
+    }
 
-fn hash_u64(mut x: u64) -> u64 {
-    x = x.wrapping_add(0x9E3779B97F4A7C15);
-    let mut z = x;
-    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
-    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
-    z ^ (z >> 31)
+    DataReloadReport {
+        items: data.items.len(),
+        resources: data.resources.len(),
+        monsters: data.monsters.len(),
+        quests: data.quests_by_npc.len(),
+        npcs: data.npcs.len(),
+        removed_monsters,
+        removed_resources: removed_nodes.len(),
+        removed_ground_items,
+        removed_quest_kills,
+        unknown_inventory_items,
+    }
 }
 
-fn load_json<T: DeserializeOwned>(path: &str) -> AppResult<T> {
-    let data = std::fs::read_to_string(path)?;
-    Ok(serde_json::from_str(&data)?)
+/// Polls the game data files and reloads when one of them changes. Failed reloads are
+/// logged and retried on the next change.
+fn spawn_data_watcher(app_state: AppState) {
+    tokio::spawn(async move {
+        let mut last_modified = game_data_modified();
+        let mut interval = tokio::time::interval(Duration::from_millis(DATA_WATCH_INTERVAL_MS));
+        loop {
+            interval.tick().await;
+            let modified = game_data_modified();
+            if modified == last_modified {
+                continue;
+            }
+            last_modified = modified;
+            if let Err(err) = reload_game_data(&app_state).await {
+                warn!("game data reload failed: {}", err);
+            }
+        }
+    });
 }
 
-fn load_game_data() -> AppResult<GameData> {
-    let items: Vec<ItemDef> = load_json("data/items.json")?;
-    let resources: Vec<ResourceDef> = load_json("data/resources.json")?;
-    let monsters: Vec<MonsterDef> = load_json("data/monsters.json")?;
-    let quests: Vec<QuestDef> = load_json("data/quests.json")?;
-    let npcs: Vec<NpcDef> = load_json("data/npcs.json")?;
-    let npcs = npcs
-        .into_iter()
-        .map(|mut npc| {
-            npc.x += ENTITY_FOOT_OFFSET_X;
-            npc.y += ENTITY_FOOT_OFFSET_Y;
-            npc
+fn game_data_modified() -> Vec<Option<SystemTime>> {
+    GAME_DATA_FILES
+        .iter()
+        .map(|path| {
+            std::fs::metadata(path)
+                .and_then(|meta| meta.modified())
+                .ok()
         })
-        .collect();
-
-    Ok(GameData::new(items, resources, monsters, quests, npcs))
+        .collect()
 }
 
 fn ensure_session_cookie(jar: CookieJar) -> (String, CookieJar, bool) {
@@ -3443,7 +9087,12 @@ fn extract_session_id(headers: &HeaderMap) -> Option<String> {
         })
 }
 
-fn default_player_doc(id: &str, world: &WorldConfig, noise: &WorldNoise) -> PlayerDoc {
+fn default_player_doc(
+    id: &str,
+    world: &WorldConfig,
+    noise: &WorldNoise,
+    config: &GameplayConfig,
+) -> PlayerDoc {
     let mut inventory = HashMap::new();
     inventory.insert("basic_axe".to_string(), 1);
     inventory.insert("basic_pick".to_string(), 1);
@@ -3455,10 +9104,19 @@ fn default_player_doc(id: &str, world: &WorldConfig, noise: &WorldNoise) -> Play
         name: random_name(),
         x: spawn_x,
         y: spawn_y,
-        hp: MAX_HP,
+        hp: config.max_hp,
         inventory,
         completed_quests: Vec::new(),
+        active_quests: Vec::new(),
+        quest_kills: HashMap::new(),
+        muted_until_ms: 0,
+        chat_strikes: 0,
+        last_strike_ms: 0,
+        role: PlayerRole::Player,
         coord_version: PLAYER_COORD_VERSION,
+        pvp: false,
+        pvp_changed_ms: 0,
+        last_pvp_combat_ms: 0,
     }
 }
 
@@ -3467,7 +9125,11 @@ fn random_name() -> String {
     format!("Adventurer{}", rng.gen_range(1000..9999))
 }
 
-fn normalize_player_name(input: &str) -> Option<String> {
+fn normalize_player_name(
+    input: &str,
+    filter: &WordFilter,
+    config: &GameplayConfig,
+) -> Option<String> {
     let trimmed = input.trim();
     if trimmed.is_empty() {
         return None;
@@ -3477,7 +9139,11 @@ fn normalize_player_name(input: &str) -> Option<String> {
     if cleaned.is_empty() {
         return None;
     }
-    Some(cleaned.chars().take(MAX_NAME_CHARS).collect())
+    let name: String = cleaned.chars().take(config.max_name_chars).collect();
+    if filter.blocks(&name) {
+        return None;
+    }
+    Some(name)
 }
 
 fn now_millis() -> i64 {
@@ -3553,93 +9219,587 @@ fn broadcast_message_inline(state: &GameState, msg: ServerMessage) {
     }
 }
 
+fn broadcast_localized(state: &GameState, text_for: impl Fn(Language) -> String) {
+    for (id, sender) in &state.clients {
+        let _ = sender.send(ServerMessage::System {
+            text: text_for(player_language(state, id)),
+        });
+    }
+}
+
+/// Inputs for `/healthz` and `/readyz`. The game loop stamps `last_tick_ms`; the
+/// restore counts are filled in before the server starts listening.
+struct Health {
+    started_ms: i64,
+    last_tick_ms: AtomicI64,
+    restored: RestoreCounts,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+struct RestoreCounts {
+    structures: usize,
+    boats: usize,
+    plantings: usize,
+    guilds: usize,
+}
+
+/// Counters behind `/metrics`. Gauges that can be read off `GameState` are computed
+/// when scraped instead.
+#[derive(Default)]
+struct Metrics {
+    tick: Mutex<TickStats>,
+    store: Mutex<HashMap<&'static str, StoreStats>>,
+}
+
+#[derive(Default)]
+struct TickStats {
+    buckets: [u64; TICK_BUCKETS_SECS.len()],
+    count: u64,
+    sum_secs: f64,
+    overruns: u64,
+    errors: u64,
+}
+
+#[derive(Default, Clone, Copy)]
+struct StoreStats {
+    calls: u64,
+    errors: u64,
+    sum_secs: f64,
+}
+
+impl Metrics {
+    fn record_tick(&self, elapsed: Duration, ok: bool, budget_ms: u64) {
+        let secs = elapsed.as_secs_f64();
+        let mut tick = self.tick.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(bucket) = TICK_BUCKETS_SECS.iter().position(|bound| secs <= *bound) {
+            tick.buckets[bucket] += 1;
+        }
+        tick.count += 1;
+        tick.sum_secs += secs;
+        if elapsed > Duration::from_millis(budget_ms) {
+            tick.overruns += 1;
+        }
+        if !ok {
+            tick.errors += 1;
+        }
+    }
+
+    fn record_store(&self, method: &'static str, elapsed: Duration, ok: bool) {
+        let mut store = self.store.lock().unwrap_or_else(|err| err.into_inner());
+        let stats = store.entry(method).or_default();
+        stats.calls += 1;
+        stats.sum_secs += elapsed.as_secs_f64();
+        if !ok {
+            stats.errors += 1;
+        }
+    }
+
+    fn render(&self, state: &GameState) -> String {
+        use std::fmt::Write;
+        let mut out = String::new();
+        {
+            let tick = self.tick.lock().unwrap_or_else(|err| err.into_inner());
+            out.push_str("# HELP onlinerpg_tick_duration_seconds Time spent in one game tick.\n");
+            out.push_str("# TYPE onlinerpg_tick_duration_seconds histogram\n");
+            let mut cumulative = 0;
+            for (bound, count) in TICK_BUCKETS_SECS.iter().zip(tick.buckets.iter()) {
+                cumulative += count;
+                let _ = writeln!(
+                    out,
+                    "onlinerpg_tick_duration_seconds_bucket{{le=\"{}\"}} {}",
+                    bound, cumulative
+                );
+            }
+            let _ = writeln!(
+                out,
+                "onlinerpg_tick_duration_seconds_bucket{{le=\"+Inf\"}} {}",
+                tick.count
+            );
+            let _ = writeln!(out, "onlinerpg_tick_duration_seconds_sum {}", tick.sum_secs);
+            let _ = writeln!(out, "onlinerpg_tick_duration_seconds_count {}", tick.count);
+            write_metric(
+                &mut out,
+                "onlinerpg_tick_overruns_total",
+                "counter",
+                "Ticks that took longer than the tick interval.",
+                tick.overruns,
+            );
+            write_metric(
+                &mut out,
+                "onlinerpg_tick_errors_total",
+                "counter",
+                "Ticks that returned an error.",
+                tick.errors,
+            );
+        }
+
+        let queued: Vec<usize> = state
+            .clients
+            .values()
+            .map(|sender| sender.depth.load(Ordering::Relaxed))
+            .collect();
+        write_metric(
+            &mut out,
+            "onlinerpg_connected_clients",
+            "gauge",
+            "Open websocket connections.",
+            state.clients.len(),
+        );
+        write_metric(
+            &mut out,
+            "onlinerpg_players",
+            "gauge",
+            "Players in the world.",
+            state.players.len(),
+        );
+        write_metric(
+            &mut out,
+            "onlinerpg_monsters",
+            "gauge",
+            "Live monsters.",
+            state.monsters.len(),
+        );
+        write_metric(
+            &mut out,
+            "onlinerpg_projectiles",
+            "gauge",
+            "Projectiles in flight.",
+            state.projectiles.len(),
+        );
+        write_metric(
+            &mut out,
+            "onlinerpg_loaded_chunks",
+            "gauge",
+            "Chunks kept in memory.",
+            state.chunk_last_access.len(),
+        );
+        write_metric(
+            &mut out,
+            "onlinerpg_outbound_queue_messages",
+            "gauge",
+            "Messages waiting to be written to all sockets.",
+            queued.iter().sum::<usize>(),
+        );
+        write_metric(
+            &mut out,
+            "onlinerpg_outbound_queue_max",
+            "gauge",
+            "Longest outbound queue of a single socket.",
+            queued.iter().copied().max().unwrap_or(0),
+        );
+
+        let store = self.store.lock().unwrap_or_else(|err| err.into_inner());
+        let mut methods: Vec<(&&str, &StoreStats)> = store.iter().collect();
+        methods.sort_by_key(|(method, _)| **method);
+        out.push_str(
+            "# HELP onlinerpg_store_duration_seconds Latency of database calls by store method.\n",
+        );
+        out.push_str("# TYPE onlinerpg_store_duration_seconds summary\n");
+        for (method, stats) in &methods {
+            let _ = writeln!(
+                out,
+                "onlinerpg_store_duration_seconds_sum{{method=\"{}\"}} {}",
+                method, stats.sum_secs
+            );
+            let _ = writeln!(
+                out,
+                "onlinerpg_store_duration_seconds_count{{method=\"{}\"}} {}",
+                method, stats.calls
+            );
+        }
+        out.push_str(
+            "# HELP onlinerpg_store_errors_total Failed database calls by store method.\n",
+        );
+        out.push_str("# TYPE onlinerpg_store_errors_total counter\n");
+        for (method, stats) in &methods {
+            let _ = writeln!(
+                out,
+                "onlinerpg_store_errors_total{{method=\"{}\"}} {}",
+                method, stats.errors
+            );
+        }
+        out
+    }
+}
+
+fn write_metric(
+    out: &mut String,
+    name: &str,
+    kind: &str,
+    help: &str,
+    value: impl std::fmt::Display,
+) {
+    use std::fmt::Write;
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    let _ = writeln!(out, "{} {}", name, value);
+}
+
 #[derive(Clone)]
 struct GameStore {
     players: Collection<PlayerDoc>,
     structures: Collection<StructureDoc>,
     boats: Collection<BoatDoc>,
+    plantings: Collection<PlantingDoc>,
+    guilds: Collection<GuildDoc>,
+    reports: Collection<ReportDoc>,
+    bans: Collection<BanDoc>,
+    audit: Collection<AuditDoc>,
+    db: Database,
+    metrics: Arc<Metrics>,
 }
 
-impl GameStore {
-    async fn new(uri: &str) -> AppResult<Self> {
-        let client = Client::with_uri_str(uri).await?;
-        let db = client.database("onlinerpg");
-        Ok(Self {
-            players: db.collection::<PlayerDoc>("players"),
-            structures: db.collection::<StructureDoc>("structures"),
-            boats: db.collection::<BoatDoc>("boats"),
+impl GameStore {
+    async fn new(uri: &str, metrics: Arc<Metrics>) -> AppResult<Self> {
+        let client = Client::with_uri_str(uri).await?;
+        let db = client.database("onlinerpg");
+        Ok(Self {
+            players: db.collection::<PlayerDoc>("players"),
+            structures: db.collection::<StructureDoc>("structures"),
+            boats: db.collection::<BoatDoc>("boats"),
+            plantings: db.collection::<PlantingDoc>("plantings"),
+            guilds: db.collection::<GuildDoc>("guilds"),
+            reports: db.collection::<ReportDoc>("reports"),
+            bans: db.collection::<BanDoc>("bans"),
+            audit: db.collection::<AuditDoc>("audit"),
+            db,
+            metrics,
+        })
+    }
+
+    async fn ping(&self) -> AppResult<()> {
+        self.timed("ping", async {
+            self.db.run_command(doc! { "ping": 1 }, None).await?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Runs one store call and records its latency and outcome per method.
+    async fn timed<T>(
+        &self,
+        method: &'static str,
+        call: impl Future<Output = AppResult<T>>,
+    ) -> AppResult<T> {
+        let started = Instant::now();
+        let result = call.await;
+        self.metrics
+            .record_store(method, started.elapsed(), result.is_ok());
+        result
+    }
+
+    async fn load_player(&self, id: &str) -> AppResult<Option<PlayerDoc>> {
+        self.timed("load_player", async {
+            Ok(self.players.find_one(doc! { "_id": id }, None).await?)
+        })
+        .await
+    }
+
+    async fn load_or_create_player(
+        &self,
+        id: &str,
+        world: &WorldConfig,
+        noise: &WorldNoise,
+        config: &GameplayConfig,
+    ) -> AppResult<PlayerDoc> {
+        if let Some(doc) = self.load_player(id).await? {
+            Ok(doc)
+        } else {
+            let doc = default_player_doc(id, world, noise, config);
+            let _ = self.save_player(&doc).await;
+            Ok(doc)
+        }
+    }
+
+    async fn save_player(&self, doc: &PlayerDoc) -> AppResult<()> {
+        self.timed("save_player", async {
+            let opts = ReplaceOptions::builder().upsert(true).build();
+            self.players
+                .replace_one(doc! { "_id": &doc.id }, doc, opts)
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn load_structures(&self) -> AppResult<Vec<StructureDoc>> {
+        self.timed("load_structures", async {
+            let mut cursor = self.structures.find(doc! {}, None).await?;
+            let mut docs = Vec::new();
+            while let Some(result) = cursor.next().await {
+                docs.push(result?);
+            }
+            Ok(docs)
+        })
+        .await
+    }
+
+    async fn load_boats(&self) -> AppResult<Vec<BoatDoc>> {
+        self.timed("load_boats", async {
+            let mut cursor = self.boats.find(doc! {}, None).await?;
+            let mut docs = Vec::new();
+            while let Some(result) = cursor.next().await {
+                docs.push(result?);
+            }
+            Ok(docs)
+        })
+        .await
+    }
+
+    async fn load_players(&self) -> AppResult<Vec<PlayerDoc>> {
+        self.timed("load_players", async {
+            let mut cursor = self.players.find(doc! {}, None).await?;
+            let mut docs = Vec::new();
+            while let Some(result) = cursor.next().await {
+                docs.push(result?);
+            }
+            Ok(docs)
+        })
+        .await
+    }
+
+    async fn replace_structures(&self, structures: &[StructureDoc]) -> AppResult<()> {
+        self.timed(
+            "replace_structures",
+            self.replace_collection(&self.structures, structures),
+        )
+        .await
+    }
+
+    async fn replace_boats(&self, boats: &[BoatDoc]) -> AppResult<()> {
+        self.timed("replace_boats", self.replace_collection(&self.boats, boats))
+            .await
+    }
+
+    /// Writes `docs` to a staging collection and renames it over `target`, so a failed
+    /// insert leaves the existing documents untouched.
+    async fn replace_collection<T: Serialize + Send + Sync>(
+        &self,
+        target: &Collection<T>,
+        docs: &[T],
+    ) -> AppResult<()> {
+        let staging = self
+            .db
+            .collection::<T>(&format!("{}_import", target.name()));
+        staging.drop(None).await?;
+        self.db.create_collection(staging.name(), None).await?;
+        if !docs.is_empty() {
+            staging.insert_many(docs, None).await?;
+        }
+        let db_name = self.db.name();
+        target
+            .client()
+            .database("admin")
+            .run_command(
+                doc! {
+                    "renameCollection": format!("{}.{}", db_name, staging.name()),
+                    "to": format!("{}.{}", db_name, target.name()),
+                    "dropTarget": true,
+                },
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn insert_structures(&self, structures: &[StructureDoc]) -> AppResult<()> {
+        self.timed("insert_structures", async {
+            if structures.is_empty() {
+                return Ok(());
+            }
+            self.structures.insert_many(structures, None).await?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn insert_boat(&self, boat: &BoatDoc) -> AppResult<()> {
+        self.timed("insert_boat", async {
+            self.boats.insert_one(boat, None).await?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn update_boat(&self, boat: &BoatDoc) -> AppResult<()> {
+        self.timed("update_boat", async {
+            let opts = ReplaceOptions::builder().upsert(true).build();
+            self.boats
+                .replace_one(doc! { "id": boat.id }, boat, opts)
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn delete_structure_group(&self, id: i64) -> AppResult<()> {
+        self.timed("delete_structure_group", async {
+            self.structures.delete_many(doc! { "id": id }, None).await?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn load_plantings(&self) -> AppResult<Vec<PlantingDoc>> {
+        self.timed("load_plantings", async {
+            let mut cursor = self.plantings.find(doc! {}, None).await?;
+            let mut docs = Vec::new();
+            while let Some(result) = cursor.next().await {
+                docs.push(result?);
+            }
+            Ok(docs)
+        })
+        .await
+    }
+
+    async fn save_planting(&self, planting: &PlantingDoc) -> AppResult<()> {
+        self.timed("save_planting", async {
+            let opts = ReplaceOptions::builder().upsert(true).build();
+            self.plantings
+                .replace_one(doc! { "id": planting.id }, planting, opts)
+                .await?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn delete_planting(&self, id: i64) -> AppResult<()> {
+        self.timed("delete_planting", async {
+            self.plantings.delete_one(doc! { "id": id }, None).await?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn load_guilds(&self) -> AppResult<Vec<GuildDoc>> {
+        self.timed("load_guilds", async {
+            let mut cursor = self.guilds.find(doc! {}, None).await?;
+            let mut docs = Vec::new();
+            while let Some(result) = cursor.next().await {
+                docs.push(result?);
+            }
+            Ok(docs)
+        })
+        .await
+    }
+
+    async fn save_guild(&self, guild: &GuildDoc) -> AppResult<()> {
+        self.timed("save_guild", async {
+            let opts = ReplaceOptions::builder().upsert(true).build();
+            self.guilds
+                .replace_one(doc! { "_id": &guild.id }, guild, opts)
+                .await?;
+            Ok(())
         })
+        .await
     }
 
-    async fn load_player(&self, id: &str) -> AppResult<Option<PlayerDoc>> {
-        Ok(self.players.find_one(doc! { "_id": id }, None).await?)
+    async fn delete_guild(&self, id: &str) -> AppResult<()> {
+        self.timed("delete_guild", async {
+            self.guilds.delete_one(doc! { "_id": id }, None).await?;
+            Ok(())
+        })
+        .await
     }
 
-    async fn load_or_create_player(
-        &self,
-        id: &str,
-        world: &WorldConfig,
-        noise: &WorldNoise,
-    ) -> AppResult<PlayerDoc> {
-        if let Some(doc) = self.load_player(id).await? {
-            Ok(doc)
-        } else {
-            let doc = default_player_doc(id, world, noise);
-            let _ = self.save_player(&doc).await;
-            Ok(doc)
-        }
+    async fn insert_report(&self, report: &ReportDoc) -> AppResult<()> {
+        self.timed("insert_report", async {
+            self.reports.insert_one(report, None).await?;
+            Ok(())
+        })
+        .await
     }
 
-    async fn save_player(&self, doc: &PlayerDoc) -> AppResult<()> {
-        let opts = ReplaceOptions::builder().upsert(true).build();
-        self.players
-            .replace_one(doc! { "_id": &doc.id }, doc, opts)
-            .await?;
-        Ok(())
+    async fn insert_audit(&self, entry: &AuditDoc) -> AppResult<()> {
+        self.timed("insert_audit", async {
+            self.audit.insert_one(entry, None).await?;
+            Ok(())
+        })
+        .await
     }
 
-    async fn load_structures(&self) -> AppResult<Vec<StructureDoc>> {
-        let mut cursor = self.structures.find(doc! {}, None).await?;
-        let mut docs = Vec::new();
-        while let Some(result) = cursor.next().await {
-            docs.push(result?);
-        }
-        Ok(docs)
+    async fn insert_ban(&self, ban: &BanDoc) -> AppResult<()> {
+        self.timed("insert_ban", async {
+            self.bans.insert_one(ban, None).await?;
+            Ok(())
+        })
+        .await
     }
 
-    async fn load_boats(&self) -> AppResult<Vec<BoatDoc>> {
-        let mut cursor = self.boats.find(doc! {}, None).await?;
-        let mut docs = Vec::new();
-        while let Some(result) = cursor.next().await {
-            docs.push(result?);
-        }
-        Ok(docs)
+    /// Bans without an expiry never lapse.
+    async fn find_active_ban(
+        &self,
+        player_id: &str,
+        ip: Option<&str>,
+        now_ms: i64,
+    ) -> AppResult<Option<BanDoc>> {
+        self.timed("find_active_ban", async {
+            let mut targets = vec![doc! { "player_id": player_id }];
+            if let Some(ip) = ip {
+                targets.push(doc! { "ip": ip });
+            }
+            let filter = doc! {
+                "$and": [
+                    { "$or": targets },
+                    { "$or": [
+                        { "expires_ms": null },
+                        { "expires_ms": { "$gt": now_ms } },
+                    ] },
+                ],
+            };
+            Ok(self.bans.find_one(filter, None).await?)
+        })
+        .await
     }
 
-    async fn insert_structures(&self, structures: &[StructureDoc]) -> AppResult<()> {
-        if structures.is_empty() {
-            return Ok(());
-        }
-        self.structures.insert_many(structures, None).await?;
-        Ok(())
+    async fn load_active_bans(&self, now_ms: i64) -> AppResult<Vec<BanDoc>> {
+        self.timed("load_active_bans", async {
+            let filter = doc! {
+                "$or": [
+                    { "expires_ms": null },
+                    { "expires_ms": { "$gt": now_ms } },
+                ],
+            };
+            let mut cursor = self.bans.find(filter, None).await?;
+            let mut docs = Vec::new();
+            while let Some(result) = cursor.next().await {
+                docs.push(result?);
+            }
+            Ok(docs)
+        })
+        .await
     }
 
-    async fn insert_boat(&self, boat: &BoatDoc) -> AppResult<()> {
-        self.boats.insert_one(boat, None).await?;
-        Ok(())
+    async fn delete_ban(&self, id: &str) -> AppResult<bool> {
+        self.timed("delete_ban", async {
+            let result = self.bans.delete_one(doc! { "_id": id }, None).await?;
+            Ok(result.deleted_count > 0)
+        })
+        .await
     }
 
-    async fn update_boat(&self, boat: &BoatDoc) -> AppResult<()> {
-        let opts = ReplaceOptions::builder().upsert(true).build();
-        self.boats
-            .replace_one(doc! { "id": boat.id }, boat, opts)
-            .await?;
-        Ok(())
+    async fn delete_bans(&self, player_id: &str) -> AppResult<()> {
+        self.timed("delete_bans", async {
+            self.bans
+                .delete_many(doc! { "player_id": player_id }, None)
+                .await?;
+            Ok(())
+        })
+        .await
     }
 
-    async fn delete_structure_group(&self, id: i64) -> AppResult<()> {
-        self.structures.delete_many(doc! { "id": id }, None).await?;
-        Ok(())
+    async fn update_structure_guild(&self, id: i64, guild_id: Option<&str>) -> AppResult<()> {
+        self.timed("update_structure_guild", async {
+            let update = match guild_id {
+                Some(guild_id) => doc! { "$set": { "guild_id": guild_id } },
+                None => doc! { "$unset": { "guild_id": "" } },
+            };
+            self.structures
+                .update_many(doc! { "id": id }, update, None)
+                .await?;
+            Ok(())
+        })
+        .await
     }
 }
 
@@ -3653,45 +9813,149 @@ struct PlayerDoc {
     hp: i32,
     inventory: HashMap<String, i32>,
     completed_quests: Vec<String>,
+    /// Kill quests the player has been given but not yet handed in.
+    #[serde(default)]
+    active_quests: Vec<String>,
+    #[serde(default)]
+    quest_kills: HashMap<String, i32>,
+    #[serde(default)]
+    muted_until_ms: i64,
+    #[serde(default)]
+    chat_strikes: i32,
+    #[serde(default)]
+    last_strike_ms: i64,
+    #[serde(default)]
+    role: PlayerRole,
     #[serde(default)]
     coord_version: i32,
+    /// The PvP flag and its cooldown clocks survive a relog, so logging out is not a way
+    /// to drop the flag mid-fight.
+    #[serde(default)]
+    pvp: bool,
+    #[serde(default)]
+    pvp_changed_ms: i64,
+    #[serde(default)]
+    last_pvp_combat_ms: i64,
+}
+
+/// Stored on the player document; only set through the admin API.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum PlayerRole {
+    #[default]
+    Player,
+    Admin,
+}
+
+/// Outbound channel of one socket. `depth` counts messages from enqueue until the send
+/// task takes them, so a socket stuck on a slow write shows a growing backlog.
+#[derive(Clone)]
+struct ClientSender {
+    tx: mpsc::UnboundedSender<ServerMessage>,
+    depth: Arc<AtomicUsize>,
+}
+
+impl ClientSender {
+    /// Queues `msg`; false once the socket task has gone away.
+    fn send(&self, msg: ServerMessage) -> bool {
+        self.depth.fetch_add(1, Ordering::Relaxed);
+        let sent = self.tx.send(msg).is_ok();
+        if !sent {
+            self.depth.fetch_sub(1, Ordering::Relaxed);
+        }
+        sent
+    }
 }
 
 #[derive(Clone)]
 struct GameState {
+    config: Arc<GameplayConfig>,
     players: HashMap<String, Player>,
     inputs: HashMap<String, InputState>,
     monsters: HashMap<u64, Monster>,
     projectiles: HashMap<u64, Projectile>,
+    ground_items: HashMap<u64, GroundItem>,
     boats: HashMap<u64, Boat>,
     resources: HashMap<ChunkCoord, Vec<ResourceNode>>,
     structure_tiles: HashMap<TileCoord, StructureTile>,
+    planted: HashMap<ChunkCoord, Vec<ResourceNode>>,
+    planting_writes: Vec<PlantingWrite>,
     spawned_chunks: HashSet<ChunkCoord>,
+    chunk_respawn_at: HashMap<ChunkCoord, i64>,
+    /// Share of land tiles per spawned chunk, sampled once when the chunk first spawns.
+    chunk_land: HashMap<ChunkCoord, f32>,
+    /// Live monsters per home chunk, kept in step with `monsters`.
+    chunk_monsters: HashMap<ChunkCoord, i32>,
     chunk_last_access: HashMap<ChunkCoord, i64>,
-    clients: HashMap<String, mpsc::UnboundedSender<ServerMessage>>,
+    clients: HashMap<String, ClientSender>,
     typing: HashMap<String, i64>,
     visibility: HashMap<String, VisibilityState>,
     locales: HashMap<String, Language>,
+    client_ips: HashMap<String, String>,
+    parties: HashMap<u64, Party>,
+    party_of: HashMap<String, u64>,
+    party_invites: HashMap<String, PartyInvite>,
+    guilds: HashMap<String, GuildDoc>,
+    guild_of: HashMap<String, String>,
+    guild_invites: HashMap<String, GuildInvite>,
+    guild_writes: Vec<GuildWrite>,
+    chat_log: VecDeque<ChatLogEntry>,
+    report_writes: Vec<ReportDoc>,
+    audit_writes: Vec<AuditDoc>,
+    night: bool,
+    campfire_lit: bool,
+    time_offset_ms: i64,
+    last_world_time_ms: i64,
+    last_weather_ms: i64,
+    last_population_ms: i64,
+    last_party_update_ms: i64,
+    next_boss_spawn_ms: i64,
     next_entity_id: u64,
     next_structure_id: u64,
 }
 
 impl GameState {
-    fn new() -> Self {
+    fn new(config: Arc<GameplayConfig>) -> Self {
         Self {
+            config,
             players: HashMap::new(),
             inputs: HashMap::new(),
             monsters: HashMap::new(),
             projectiles: HashMap::new(),
+            ground_items: HashMap::new(),
             boats: HashMap::new(),
             resources: HashMap::new(),
             structure_tiles: HashMap::new(),
+            planted: HashMap::new(),
+            planting_writes: Vec::new(),
             spawned_chunks: HashSet::new(),
+            chunk_respawn_at: HashMap::new(),
+            chunk_land: HashMap::new(),
+            chunk_monsters: HashMap::new(),
             chunk_last_access: HashMap::new(),
             clients: HashMap::new(),
             typing: HashMap::new(),
             visibility: HashMap::new(),
             locales: HashMap::new(),
+            client_ips: HashMap::new(),
+            parties: HashMap::new(),
+            party_of: HashMap::new(),
+            party_invites: HashMap::new(),
+            guilds: HashMap::new(),
+            guild_of: HashMap::new(),
+            guild_invites: HashMap::new(),
+            guild_writes: Vec::new(),
+            chat_log: VecDeque::new(),
+            report_writes: Vec::new(),
+            audit_writes: Vec::new(),
+            night: false,
+            campfire_lit: true,
+            time_offset_ms: 0,
+            last_world_time_ms: 0,
+            last_weather_ms: 0,
+            last_population_ms: 0,
+            last_party_update_ms: 0,
+            next_boss_spawn_ms: 0,
             next_entity_id: 1,
             next_structure_id: 1,
         }
@@ -3716,6 +9980,8 @@ struct VisibilityState {
     monsters: HashSet<u64>,
     projectiles: HashSet<u64>,
     boats: HashSet<u64>,
+    ground_items: HashSet<u64>,
+    weather: HashMap<ChunkCoord, Weather>,
 }
 
 #[derive(Debug, Clone)]
@@ -3731,6 +9997,8 @@ struct Player {
     boat_id: Option<u64>,
     inventory: HashMap<String, i32>,
     completed_quests: HashSet<String>,
+    active_quests: HashSet<String>,
+    quest_kills: HashMap<String, i32>,
     last_attack_ms: i64,
     last_gather_ms: i64,
     last_interact_ms: i64,
@@ -3740,6 +10008,18 @@ struct Player {
     last_input_seq: u32,
     fishing_clicks: i32,
     fishing_target: i32,
+    pvp: bool,
+    pvp_changed_ms: i64,
+    last_pvp_combat_ms: i64,
+    pvp_zone: Option<String>,
+    guild_tag: Option<String>,
+    chat_times: VecDeque<i64>,
+    muted_until_ms: i64,
+    chat_strikes: i32,
+    last_strike_ms: i64,
+    last_report_ms: i64,
+    role: PlayerRole,
+    god_mode: bool,
 }
 
 impl Player {
@@ -3758,6 +10038,8 @@ impl Player {
             boat_id: None,
             inventory: doc.inventory,
             completed_quests: doc.completed_quests.into_iter().collect(),
+            active_quests: doc.active_quests.into_iter().collect(),
+            quest_kills: doc.quest_kills,
             last_attack_ms: 0,
             last_gather_ms: 0,
             last_interact_ms: 0,
@@ -3767,6 +10049,18 @@ impl Player {
             last_input_seq: 0,
             fishing_clicks: 0,
             fishing_target: 0,
+            pvp: doc.pvp,
+            pvp_changed_ms: doc.pvp_changed_ms,
+            last_pvp_combat_ms: doc.last_pvp_combat_ms,
+            pvp_zone: None,
+            guild_tag: None,
+            chat_times: VecDeque::new(),
+            muted_until_ms: doc.muted_until_ms,
+            chat_strikes: doc.chat_strikes,
+            last_strike_ms: doc.last_strike_ms,
+            last_report_ms: 0,
+            role: doc.role,
+            god_mode: false,
         }
     }
 
@@ -3782,6 +10076,15 @@ impl Player {
         self.fishing_target = 0;
         self.inventory = doc.inventory.clone();
         self.completed_quests = doc.completed_quests.iter().cloned().collect();
+        self.active_quests = doc.active_quests.iter().cloned().collect();
+        self.quest_kills = doc.quest_kills.clone();
+        self.muted_until_ms = doc.muted_until_ms;
+        self.chat_strikes = doc.chat_strikes;
+        self.last_strike_ms = doc.last_strike_ms;
+        self.role = doc.role;
+        self.pvp = doc.pvp;
+        self.pvp_changed_ms = doc.pvp_changed_ms;
+        self.last_pvp_combat_ms = doc.last_pvp_combat_ms;
         self.last_inventory_hash = inventory_hash(&self.inventory);
         self.last_input_seq = 0;
     }
@@ -3795,7 +10098,16 @@ impl Player {
             hp: self.hp,
             inventory: self.inventory.clone(),
             completed_quests: self.completed_quests.iter().cloned().collect(),
+            active_quests: self.active_quests.iter().cloned().collect(),
+            quest_kills: self.quest_kills.clone(),
+            muted_until_ms: self.muted_until_ms,
+            chat_strikes: self.chat_strikes,
+            last_strike_ms: self.last_strike_ms,
+            role: self.role,
             coord_version: PLAYER_COORD_VERSION,
+            pvp: self.pvp,
+            pvp_changed_ms: self.pvp_changed_ms,
+            last_pvp_combat_ms: self.last_pvp_combat_ms,
         }
     }
 
@@ -3809,6 +10121,7 @@ impl Player {
             in_boat: self.in_boat,
             boat_id: self.boat_id,
             inventory: self.inventory.clone(),
+            admin: self.role == PlayerRole::Admin,
         }
     }
 }
@@ -3821,7 +10134,7 @@ fn player_position_from_doc(doc: &PlayerDoc) -> (f32, f32) {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 struct InputState {
     dir_x: f32,
     dir_y: f32,
@@ -3831,6 +10144,11 @@ struct InputState {
     seq: u32,
     expected_x: Option<f32>,
     expected_y: Option<f32>,
+    /// Aim direction relative to the player, used by ranged attacks.
+    aim: Option<(f32, f32)>,
+    target_id: Option<u64>,
+    /// Player to shoot at when no monster is targeted; only taken if PvP allows the hit.
+    target_player: Option<String>,
 }
 
 impl Default for InputState {
@@ -3844,6 +10162,9 @@ impl Default for InputState {
             seq: 0,
             expected_x: None,
             expected_y: None,
+            aim: None,
+            target_id: None,
+            target_player: None,
         }
     }
 }
@@ -3855,12 +10176,25 @@ struct Monster {
     x: f32,
     y: f32,
     hp: i32,
+    boss: bool,
     target: Option<String>,
     wander_dir: (f32, f32),
     wander_until_ms: i64,
     last_attack_ms: i64,
     flee_dir: Option<(f32, f32)>,
     flee_next_sample_ms: i64,
+    home: ChunkCoord,
+    spawn_x: f32,
+    spawn_y: f32,
+    provoked_until_ms: i64,
+    returning: bool,
+    path: Vec<(i32, i32)>,
+    path_goal: Option<(i32, i32)>,
+    path_retry_ms: i64,
+    last_special_ms: i64,
+    phase: usize,
+    /// Damage dealt by each player, used to split loot.
+    damage_by: HashMap<String, i32>,
 }
 
 #[derive(Debug, Clone)]
@@ -3875,12 +10209,131 @@ struct Boat {
 #[derive(Debug, Clone)]
 struct Projectile {
     id: u64,
+    kind: String,
     x: f32,
     y: f32,
     vx: f32,
     vy: f32,
     ttl_ms: i64,
     damage: i32,
+    owner: ProjectileOwner,
+    hits: Faction,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ProjectileOwner {
+    Player(String),
+    Monster { id: u64, kind: String },
+}
+
+/// What stopped a projectile, reported to clients in `projectile_impact`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Impact {
+    Ground,
+    Structure,
+    Resource,
+    Monster,
+    Player,
+}
+
+#[derive(Debug, Clone)]
+struct GroundItem {
+    id: u64,
+    item: String,
+    count: i32,
+    x: f32,
+    y: f32,
+    expires_ms: i64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ChatChannel {
+    #[default]
+    Local,
+    Global,
+    Party,
+    Guild,
+    Whisper,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ChatLogEntry {
+    from_id: String,
+    from: String,
+    channel: ChatChannel,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    to: Option<String>,
+    text: String,
+    at_ms: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ReportDoc {
+    reporter_id: String,
+    reporter_name: String,
+    target_id: String,
+    target_name: String,
+    reason: String,
+    created_ms: i64,
+    context: Vec<ChatLogEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AuditDoc {
+    actor_id: String,
+    actor_name: String,
+    command: String,
+    args: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target_id: Option<String>,
+    created_ms: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BanDoc {
+    #[serde(rename = "_id")]
+    id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    player_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ip: Option<String>,
+    reason: String,
+    created_ms: i64,
+    expires_ms: Option<i64>,
+}
+
+#[derive(Debug, Clone)]
+struct Party {
+    id: u64,
+    leader: String,
+    members: Vec<String>,
+    loot: LootMode,
+    next_loot: usize,
+}
+
+/// How a party splits monster drops that land on one of its members.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum LootMode {
+    #[default]
+    Damage,
+    RoundRobin,
+    Random,
+}
+
+#[derive(Debug, Clone)]
+struct PartyInvite {
+    from: String,
+    expires_ms: i64,
+}
+
+/// Which side a projectile damages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Faction {
+    Players,
+    Monsters,
 }
 
 #[derive(Debug, Clone)]
@@ -3893,6 +10346,25 @@ struct ResourceNode {
     respawn_at_ms: Option<i64>,
     size: i32,
     next_growth_ms: Option<i64>,
+    owner_id: Option<String>,
+    /// Item a player planted this from; an unripe harvest gives it back.
+    seed_id: Option<String>,
+}
+
+impl ResourceNode {
+    fn to_planting_doc(&self) -> Option<PlantingDoc> {
+        let owner_id = self.owner_id.clone()?;
+        Some(PlantingDoc {
+            id: self.id as i64,
+            kind: self.kind.clone(),
+            x: self.x,
+            y: self.y,
+            size: self.size,
+            next_growth_ms: self.next_growth_ms,
+            owner_id,
+            seed_id: self.seed_id.clone(),
+        })
+    }
 }
 
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize)]
@@ -3914,6 +10386,7 @@ struct StructureTile {
     x: i32,
     y: i32,
     owner_id: String,
+    guild_id: Option<String>,
 }
 
 #[derive(Clone, Serialize)]
@@ -3931,6 +10404,80 @@ struct StructureDoc {
     x: i32,
     y: i32,
     owner_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    guild_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum GuildRank {
+    Member,
+    Officer,
+    Leader,
+}
+
+/// What officers and members may do; leaders can do everything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum GuildPermission {
+    Invite,
+    Kick,
+    Promote,
+    Demolish,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct GuildMember {
+    id: String,
+    name: String,
+    rank: GuildRank,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct GuildDoc {
+    #[serde(rename = "_id")]
+    id: String,
+    name: String,
+    tag: String,
+    members: Vec<GuildMember>,
+    #[serde(default = "default_officer_permissions")]
+    officer_permissions: Vec<GuildPermission>,
+    #[serde(default = "default_member_permissions")]
+    member_permissions: Vec<GuildPermission>,
+}
+
+impl GuildDoc {
+    fn rank_of(&self, player_id: &str) -> Option<GuildRank> {
+        self.members
+            .iter()
+            .find(|member| member.id == player_id)
+            .map(|member| member.rank)
+    }
+}
+
+fn default_officer_permissions() -> Vec<GuildPermission> {
+    vec![
+        GuildPermission::Invite,
+        GuildPermission::Kick,
+        GuildPermission::Demolish,
+    ]
+}
+
+fn default_member_permissions() -> Vec<GuildPermission> {
+    Vec::new()
+}
+
+#[derive(Debug, Clone)]
+struct GuildInvite {
+    guild_id: String,
+    expires_ms: i64,
+}
+
+#[derive(Debug, Clone)]
+enum GuildWrite {
+    Save(GuildDoc),
+    Delete(String),
+    StructureGuild { id: u64, guild_id: Option<String> },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -3941,6 +10488,25 @@ struct BoatDoc {
     owner_id: String,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PlantingDoc {
+    id: i64,
+    kind: String,
+    x: i32,
+    y: i32,
+    size: i32,
+    next_growth_ms: Option<i64>,
+    owner_id: String,
+    #[serde(default)]
+    seed_id: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+enum PlantingWrite {
+    Save(PlantingDoc),
+    Delete(i64),
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct WeaponStats {
     kind: String,
@@ -3960,6 +10526,11 @@ struct ItemDef {
     heal: Option<i32>,
     weapon: Option<WeaponStats>,
     ammo_for: Option<String>,
+    #[serde(default)]
+    plants: Option<String>,
+    /// Chance that a fired projectile of this item can be picked up again.
+    #[serde(default)]
+    recover_chance: Option<f32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -3970,37 +10541,179 @@ struct ResourceDef {
     hp: i32,
     respawn_ms: i64,
     drops: Vec<ItemStack>,
+    #[serde(default)]
+    plant_on: Vec<String>,
+    #[serde(default)]
+    max_size: Option<i32>,
+    #[serde(default)]
+    grow_ms: Option<i64>,
+    /// Nodes at or above this size stop projectiles.
+    #[serde(default)]
+    blocks_projectiles_at: Option<i32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum MonsterBehavior {
+    Aggressive,
+    Timid,
+    /// Ignores players until hit.
+    Neutral,
+    /// Aggressive, and pulls in nearby monsters of the same kind when it engages.
+    Pack,
+    /// Keeps its distance and attacks from `attack_range`.
+    Ranged,
+    /// Only attacks players who come within `territory_radius` of its spawn point.
+    Territorial,
+}
+
+impl Default for MonsterBehavior {
+    fn default() -> Self {
+        MonsterBehavior::Aggressive
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum ActiveTime {
+    #[default]
+    Always,
+    Day,
+    Night,
+}
+
+impl ActiveTime {
+    fn allows(self, night: bool) -> bool {
+        match self {
+            ActiveTime::Always => true,
+            ActiveTime::Day => !night,
+            ActiveTime::Night => night,
+        }
+    }
+}
+
+fn default_spawn_weight() -> u32 {
+    1
+}
+
+fn default_day_length_ms() -> i64 {
+    1_200_000
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct MonsterDef {
+    id: String,
+    name: String,
+    hp: i32,
+    speed: f32,
+    damage: i32,
+    drop: Option<ItemStack>,
+    #[serde(default)]
+    behavior: MonsterBehavior,
+    #[serde(default = "default_spawn_weight")]
+    spawn_weight: u32,
+    #[serde(default)]
+    active: ActiveTime,
+    #[serde(default)]
+    biomes: Vec<String>,
+    #[serde(default = "default_aggro_range")]
+    aggro_range: f32,
+    #[serde(default = "default_attack_range")]
+    attack_range: f32,
+    #[serde(default = "default_attack_cooldown_ms")]
+    attack_cooldown_ms: i64,
+    #[serde(default = "default_leash_distance")]
+    leash_distance: f32,
+    #[serde(default = "default_pack_radius")]
+    pack_radius: f32,
+    #[serde(default = "default_territory_radius")]
+    territory_radius: f32,
+    /// Timid monsters that sample escape directions instead of running straight away.
+    #[serde(default)]
+    evasive: bool,
+    /// Ranged monsters fire this projectile kind; without one their attacks land instantly.
+    #[serde(default)]
+    projectile: Option<String>,
+    #[serde(default)]
+    projectile_speed: Option<f32>,
+    /// Bosses never come from chunk population; they appear on their own timer.
+    #[serde(default)]
+    boss: bool,
+    #[serde(default)]
+    phases: Vec<BossPhase>,
+    /// Extra drops on top of `drop`, each with an optional `chance`.
+    #[serde(default)]
+    loot: Vec<ItemStack>,
+    /// HP and damage multipliers for monsters that spawn or fight at night.
+    #[serde(default = "default_factor")]
+    night_hp_factor: f32,
+    #[serde(default = "default_factor")]
+    night_damage_factor: f32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BossPhase {
+    /// The phase starts once HP falls to this fraction of the maximum.
+    hp_below: f32,
+    #[serde(default = "default_factor")]
+    speed_factor: f32,
+    #[serde(default = "default_factor")]
+    damage_factor: f32,
+    #[serde(default)]
+    special: Option<SpecialAttack>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SpecialAttack {
+    kind: SpecialAttackKind,
+    cooldown_ms: i64,
+    #[serde(default)]
+    damage: i32,
+    /// Slam radius in tiles.
+    #[serde(default)]
+    radius: f32,
+    /// Projectiles in a volley or monsters per summon.
+    #[serde(default)]
+    count: u32,
+    /// Projectile kind for volleys, monster id for summons.
+    #[serde(default)]
+    id: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum SpecialAttackKind {
+    Slam,
+    Volley,
+    Summon,
+}
+
+fn default_factor() -> f32 {
+    1.0
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
-#[serde(rename_all = "snake_case")]
-enum MonsterBehavior {
-    Aggressive,
-    Timid,
+fn default_aggro_range() -> f32 {
+    5.0
 }
 
-impl Default for MonsterBehavior {
-    fn default() -> Self {
-        MonsterBehavior::Aggressive
-    }
+fn default_attack_range() -> f32 {
+    0.8
 }
 
-fn default_spawn_weight() -> u32 {
-    1
+fn default_attack_cooldown_ms() -> i64 {
+    800
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct MonsterDef {
-    id: String,
-    name: String,
-    hp: i32,
-    speed: f32,
-    damage: i32,
-    drop: Option<ItemStack>,
-    #[serde(default)]
-    behavior: MonsterBehavior,
-    #[serde(default = "default_spawn_weight")]
-    spawn_weight: u32,
+fn default_leash_distance() -> f32 {
+    14.0
+}
+
+fn default_pack_radius() -> f32 {
+    6.0
+}
+
+fn default_territory_radius() -> f32 {
+    4.0
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -4010,9 +10723,17 @@ struct QuestDef {
     name: String,
     description: String,
     requires: Vec<ItemStack>,
+    #[serde(default)]
+    kills: Vec<KillRequirement>,
     rewards: Vec<ItemStack>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KillRequirement {
+    monster: String,
+    count: i32,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct NpcDef {
     id: String,
@@ -4022,6 +10743,404 @@ struct NpcDef {
     dialog: String,
 }
 
+/// Process settings from `data/server.json` (or `SERVER_CONFIG`) with environment
+/// overrides. Only `gameplay` is sent to clients.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ServerConfig {
+    port: u16,
+    mongodb_uri: String,
+    admin_token: Option<String>,
+    metrics_token: Option<String>,
+    /// Reload game data when a file in `data/` changes.
+    watch_data: bool,
+    /// Terrain tiles for `/api/map` are cached under `<dir>/<seed>/`; empty disables it.
+    map_cache_dir: String,
+    /// Oldest cached tiles are deleted once the cache grows past this size.
+    map_cache_max_mb: u64,
+    /// `/api/map` serves world tiles within this distance of the origin on each axis.
+    map_radius: i32,
+    /// Reverse proxies allowed to set `X-Real-IP`, as addresses or CIDR ranges.
+    trusted_proxies: Vec<String>,
+    gameplay: GameplayConfig,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            port: 3000,
+            mongodb_uri: "mongodb://localhost:27017".to_string(),
+            admin_token: None,
+            metrics_token: None,
+            watch_data: false,
+            map_cache_dir: "map-cache".to_string(),
+            map_cache_max_mb: 256,
+            map_radius: 4096,
+            trusted_proxies: Vec::new(),
+            gameplay: GameplayConfig::default(),
+        }
+    }
+}
+
+impl ServerConfig {
+    fn trusted_proxies(&self) -> Result<Vec<TrustedProxy>, String> {
+        self.trusted_proxies
+            .iter()
+            .map(|entry| {
+                TrustedProxy::parse(entry)
+                    .ok_or_else(|| format!("invalid trusted_proxies entry {:?}", entry))
+            })
+            .collect()
+    }
+}
+
+/// An address or CIDR range from `trusted_proxies`.
+#[derive(Debug, Clone, Copy)]
+struct TrustedProxy {
+    addr: IpAddr,
+    prefix: u32,
+}
+
+impl TrustedProxy {
+    fn parse(entry: &str) -> Option<Self> {
+        let (addr, prefix) = match entry.trim().split_once('/') {
+            Some((addr, prefix)) => (
+                addr.parse::<IpAddr>().ok()?,
+                Some(prefix.parse::<u32>().ok()?),
+            ),
+            None => (entry.trim().parse::<IpAddr>().ok()?, None),
+        };
+        let bits = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = prefix.unwrap_or(bits);
+        (prefix <= bits).then_some(Self { addr, prefix })
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        let ip = match ip {
+            IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(ip),
+            other => other,
+        };
+        let (addr, ip, bits) = match (self.addr, ip) {
+            (IpAddr::V4(a), IpAddr::V4(b)) => (u32::from(a) as u128, u32::from(b) as u128, 32),
+            (IpAddr::V6(a), IpAddr::V6(b)) => (u128::from(a), u128::from(b), 128),
+            _ => return false,
+        };
+        let shift = bits - self.prefix;
+        shift >= bits || (addr >> shift) == (ip >> shift)
+    }
+}
+
+/// Gameplay tuning. Every field can be overridden with `ONLINERPG_<FIELD>`, e.g.
+/// `ONLINERPG_TICK_MS=50`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct GameplayConfig {
+    /// Simulation step; the loop runs once per tick.
+    tick_ms: u64,
+    player_speed: f32,
+    gather_range: f32,
+    interact_range: f32,
+    plant_range: f32,
+    /// How often online players are written to the store.
+    save_interval_ms: i64,
+    max_hp: i32,
+    player_regen_interval_ms: i64,
+    /// Range of interact presses needed to land a fish.
+    fish_min_clicks: i32,
+    fish_max_clicks: i32,
+    typing_timeout_ms: i64,
+    /// Chunks around each player kept loaded, and visible for entity updates.
+    chunk_keep_radius: i32,
+    entity_visibility_radius: i32,
+    /// How far the client-predicted position may pull the server position, and how strongly.
+    expected_pos_correction_range: f32,
+    expected_pos_correction_weight: f32,
+    /// Idle chunks are dropped after this long.
+    chunk_ttl_ms: i64,
+    max_name_chars: usize,
+    tree_grow_interval_ms: i64,
+    /// Growth stages of trees, and the largest rock size rolled for new rocks.
+    tree_max_size: i32,
+    rock_max_size: i32,
+    /// Fractions of the day cycle where daylight starts and ends; the rest is night.
+    day_start: f32,
+    night_start: f32,
+    world_time_broadcast_ms: i64,
+    weather_update_ms: i64,
+    /// Boat speed multiplier during storms.
+    storm_boat_speed_factor: f32,
+    /// How far other entities stay visible in fog.
+    fog_view_distance: f32,
+    population_check_ms: i64,
+    monster_respawn_ms: i64,
+    /// Monster population target per fully walkable chunk by day and by night.
+    day_chunk_population: f32,
+    night_chunk_population: f32,
+    max_monsters_per_chunk: i32,
+    max_monsters: usize,
+    /// No monsters spawn this close to the world spawn or to structures.
+    spawn_safe_radius: f32,
+    structure_safe_radius: i32,
+    monster_spawn_min_player_distance: f32,
+    /// A* nodes all monsters may expand per tick, and per search.
+    path_search_budget: usize,
+    path_max_nodes: usize,
+    path_retry_ms: i64,
+    /// How long a hit monster keeps chasing its attacker.
+    monster_provoke_ms: i64,
+    boss_spawn_interval_ms: i64,
+    boss_spawn_distance: f32,
+    boss_phase_message_range: f32,
+    /// Dropped items vanish after this long.
+    ground_item_ttl_ms: i64,
+    pickup_range: f32,
+    /// No PvP damage this close to the spawn campfire.
+    pvp_safe_radius: f32,
+    pvp_toggle_cooldown_ms: i64,
+    max_party_size: usize,
+    party_invite_ttl_ms: i64,
+    party_update_ms: i64,
+    /// Party members within this range share kill credit.
+    party_share_range: f32,
+    guild_invite_ttl_ms: i64,
+    max_guild_members: usize,
+    max_guild_name_chars: usize,
+    /// More than `chat_rate_limit` messages within the window mutes the sender.
+    chat_rate_window_ms: i64,
+    chat_rate_limit: usize,
+    /// Mute lengths for the first and each repeated offence.
+    chat_mute_steps_ms: Vec<i64>,
+    chat_strike_reset_ms: i64,
+    /// Chat lines kept in memory for reports.
+    chat_log_size: usize,
+    report_context_lines: usize,
+    report_cooldown_ms: i64,
+    /// Upper limit for `/spawn monster`.
+    gm_max_spawn: i32,
+}
+
+impl Default for GameplayConfig {
+    fn default() -> Self {
+        Self {
+            tick_ms: 100,
+            player_speed: 3.4,
+            gather_range: 1.1,
+            interact_range: 1.2,
+            plant_range: 1.6,
+            save_interval_ms: 5_000,
+            max_hp: 10,
+            player_regen_interval_ms: 5_000,
+            fish_min_clicks: 1,
+            fish_max_clicks: 10,
+            typing_timeout_ms: 2500,
+            chunk_keep_radius: 3,
+            entity_visibility_radius: 2,
+            expected_pos_correction_range: 1.5,
+            expected_pos_correction_weight: 0.35,
+            chunk_ttl_ms: 60_000,
+            max_name_chars: 20,
+            tree_grow_interval_ms: 30_000,
+            tree_max_size: 3,
+            rock_max_size: 3,
+            day_start: 0.25,
+            night_start: 0.8,
+            world_time_broadcast_ms: 10_000,
+            weather_update_ms: 2_000,
+            storm_boat_speed_factor: 0.5,
+            fog_view_distance: 9.0,
+            population_check_ms: 1_000,
+            monster_respawn_ms: 20_000,
+            day_chunk_population: 2.0,
+            night_chunk_population: 4.0,
+            max_monsters_per_chunk: 6,
+            max_monsters: 400,
+            spawn_safe_radius: 10.0,
+            structure_safe_radius: 4,
+            monster_spawn_min_player_distance: 8.0,
+            path_search_budget: 2_000,
+            path_max_nodes: 400,
+            path_retry_ms: 1_000,
+            monster_provoke_ms: 15_000,
+            boss_spawn_interval_ms: 600_000,
+            boss_spawn_distance: 14.0,
+            boss_phase_message_range: 15.0,
+            ground_item_ttl_ms: 120_000,
+            pickup_range: 0.8,
+            pvp_safe_radius: 12.0,
+            pvp_toggle_cooldown_ms: 30_000,
+            max_party_size: 5,
+            party_invite_ttl_ms: 60_000,
+            party_update_ms: 1_000,
+            party_share_range: 24.0,
+            guild_invite_ttl_ms: 120_000,
+            max_guild_members: 50,
+            max_guild_name_chars: 24,
+            chat_rate_window_ms: 10_000,
+            chat_rate_limit: 6,
+            chat_mute_steps_ms: vec![30_000, 120_000, 600_000, 3_600_000],
+            chat_strike_reset_ms: 3_600_000,
+            chat_log_size: 500,
+            report_context_lines: 30,
+            report_cooldown_ms: 60_000,
+            gm_max_spawn: 10,
+        }
+    }
+}
+
+impl GameplayConfig {
+    fn validate(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        let mut check = |ok: bool, message: &str| {
+            if !ok {
+                errors.push(message.to_string());
+            }
+        };
+        check(self.tick_ms >= 10, "tick_ms must be at least 10");
+        check(self.player_speed > 0.0, "player_speed must be positive");
+        check(
+            self.gather_range > 0.0 && self.interact_range > 0.0 && self.plant_range > 0.0,
+            "gather_range, interact_range and plant_range must be positive",
+        );
+        check(self.pickup_range > 0.0, "pickup_range must be positive");
+        check(self.max_hp > 0, "max_hp must be positive");
+        check(
+            self.fish_min_clicks >= 1 && self.fish_min_clicks <= self.fish_max_clicks,
+            "fish_min_clicks must be at least 1 and not above fish_max_clicks",
+        );
+        check(
+            self.entity_visibility_radius >= 1,
+            "entity_visibility_radius must be at least 1",
+        );
+        check(
+            self.chunk_keep_radius >= self.entity_visibility_radius,
+            "chunk_keep_radius must not be below entity_visibility_radius",
+        );
+        check(
+            (0.0..=1.0).contains(&self.expected_pos_correction_weight),
+            "expected_pos_correction_weight must be between 0 and 1",
+        );
+        check(
+            self.max_name_chars >= 1,
+            "max_name_chars must be at least 1",
+        );
+        check(
+            self.tree_max_size >= 1 && self.rock_max_size >= 1,
+            "tree_max_size and rock_max_size must be at least 1",
+        );
+        check(
+            0.0 <= self.day_start && self.day_start < self.night_start && self.night_start <= 1.0,
+            "day_start and night_start must satisfy 0 <= day_start < night_start <= 1",
+        );
+        check(
+            self.max_guild_name_chars >= 1,
+            "max_guild_name_chars must be at least 1",
+        );
+        check(
+            self.max_party_size >= 2,
+            "max_party_size must be at least 2",
+        );
+        check(
+            self.max_guild_members >= 1,
+            "max_guild_members must be at least 1",
+        );
+        check(
+            self.chat_rate_limit >= 1,
+            "chat_rate_limit must be at least 1",
+        );
+        check(
+            !self.chat_mute_steps_ms.is_empty(),
+            "chat_mute_steps_ms must not be empty",
+        );
+        check(self.gm_max_spawn >= 1, "gm_max_spawn must be at least 1");
+        check(
+            self.max_monsters_per_chunk >= 0,
+            "max_monsters_per_chunk must not be negative",
+        );
+        for (name, value) in [
+            ("save_interval_ms", self.save_interval_ms),
+            ("player_regen_interval_ms", self.player_regen_interval_ms),
+            ("chunk_ttl_ms", self.chunk_ttl_ms),
+            ("tree_grow_interval_ms", self.tree_grow_interval_ms),
+            ("world_time_broadcast_ms", self.world_time_broadcast_ms),
+            ("weather_update_ms", self.weather_update_ms),
+            ("population_check_ms", self.population_check_ms),
+            ("boss_spawn_interval_ms", self.boss_spawn_interval_ms),
+            ("party_update_ms", self.party_update_ms),
+            ("chat_rate_window_ms", self.chat_rate_window_ms),
+        ] {
+            if value <= 0 {
+                errors.push(format!("{} must be positive", name));
+            }
+        }
+        errors
+    }
+}
+
+fn load_server_config() -> AppResult<ServerConfig> {
+    let path = std::env::var("SERVER_CONFIG").unwrap_or_else(|_| "data/server.json".to_string());
+    let mut config: ServerConfig = if std::path::Path::new(&path).exists() {
+        load_json(&path)?
+    } else {
+        info!("{} not found, using default server config", path);
+        ServerConfig::default()
+    };
+    if let Some(port) = std::env::var("PORT")
+        .ok()
+        .and_then(|value| value.parse::<u16>().ok())
+    {
+        config.port = port;
+    }
+    if let Ok(uri) = std::env::var("MONGODB_URI") {
+        config.mongodb_uri = uri;
+    }
+    if let Ok(token) = std::env::var("ADMIN_TOKEN") {
+        config.admin_token = Some(token);
+    }
+    if let Ok(token) = std::env::var("METRICS_TOKEN") {
+        config.metrics_token = Some(token);
+    }
+    if let Ok(dir) = std::env::var("MAP_CACHE_DIR") {
+        config.map_cache_dir = dir;
+    }
+    if let Ok(proxies) = std::env::var("TRUSTED_PROXIES") {
+        config.trusted_proxies = proxies
+            .split(',')
+            .map(|entry| entry.trim().to_string())
+            .filter(|entry| !entry.is_empty())
+            .collect();
+    }
+    if let Ok(watch) = std::env::var("WATCH_DATA") {
+        config.watch_data = matches!(watch.trim(), "1" | "true" | "yes");
+    }
+    config.admin_token = config.admin_token.filter(|token| !token.trim().is_empty());
+    config.metrics_token = config
+        .metrics_token
+        .filter(|token| !token.trim().is_empty());
+
+    let mut gameplay = serde_json::to_value(&config.gameplay)?;
+    if let Some(fields) = gameplay.as_object_mut() {
+        for (key, field) in fields.iter_mut() {
+            if let Ok(raw) = std::env::var(format!("ONLINERPG_{}", key.to_uppercase())) {
+                *field = serde_json::from_str(&raw).unwrap_or(serde_json::Value::String(raw));
+            }
+        }
+    }
+    config.gameplay = serde_json::from_value(gameplay)
+        .map_err(|err| format!("invalid ONLINERPG_* override: {}", err))?;
+
+    let mut errors = config.gameplay.validate();
+    if let Err(err) = config.trusted_proxies() {
+        errors.push(err);
+    }
+    if config.map_radius < 1 || config.map_cache_max_mb < 1 {
+        errors.push("map_radius and map_cache_max_mb must be at least 1".to_string());
+    }
+    if !errors.is_empty() {
+        return Err(format!("invalid server config: {}", errors.join("; ")).into());
+    }
+    Ok(config)
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct WorldConfig {
     seed: u64,
@@ -4029,6 +11148,40 @@ struct WorldConfig {
     tile_size: i32,
     spawn_x: f32,
     spawn_y: f32,
+    #[serde(default = "default_day_length_ms")]
+    day_length_ms: i64,
+    #[serde(default)]
+    pvp_zones: Vec<PvpZone>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PvpZone {
+    name: String,
+    x: f32,
+    y: f32,
+    radius: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Weather {
+    Clear,
+    Rain,
+    Storm,
+    Fog,
+}
+
+impl Weather {
+    fn is_wet(self) -> bool {
+        matches!(self, Weather::Rain | Weather::Storm)
+    }
+}
+
+#[derive(Clone, Serialize)]
+struct ChunkWeather {
+    chunk_x: i32,
+    chunk_y: i32,
+    weather: Weather,
 }
 
 struct WorldNoise {
@@ -4039,6 +11192,7 @@ struct WorldNoise {
     tree: Perlin,
     rock: Perlin,
     flowers: Perlin,
+    weather: Perlin,
 }
 
 impl WorldNoise {
@@ -4052,6 +11206,7 @@ impl WorldNoise {
             tree: Perlin::new(base.wrapping_add(59)),
             rock: Perlin::new(base.wrapping_add(71)),
             flowers: Perlin::new(base.wrapping_add(83)),
+            weather: Perlin::new(base.wrapping_add(97)),
         }
     }
 
@@ -4083,6 +11238,15 @@ impl WorldNoise {
         self.fbm(&self.flowers, x, y, 0.08, 3)
     }
 
+    fn weather(&self, x: f32, y: f32, now_ms: i64) -> f32 {
+        let secs = now_ms as f64 / 1000.0;
+        self.weather.get([
+            x as f64 * 0.004 + secs * 0.004,
+            y as f64 * 0.004 + secs * 0.0025,
+            secs * 0.0008,
+        ]) as f32
+    }
+
     fn fbm(&self, perlin: &Perlin, x: f32, y: f32, base_freq: f64, octaves: i32) -> f32 {
         let mut freq = base_freq;
         let mut amp = 0.5;
@@ -4107,6 +11271,8 @@ impl WorldNoise {
 struct ItemStack {
     id: String,
     count: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    chance: Option<f32>,
 }
 
 impl ItemStack {
@@ -4114,6 +11280,7 @@ impl ItemStack {
         Self {
             id: id.to_string(),
             count,
+            chance: None,
         }
     }
 }
@@ -4124,6 +11291,8 @@ struct InventoryItem {
     name: String,
     count: i32,
     heal: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    plants: Option<String>,
 }
 
 #[derive(Clone)]
@@ -4133,6 +11302,60 @@ struct GameData {
     monsters: HashMap<String, MonsterDef>,
     quests_by_npc: HashMap<String, QuestDef>,
     npcs: Vec<NpcDef>,
+    chat_filter: WordFilter,
+}
+
+/// Words masked in chat and refused in player names. `words` match whole words,
+/// `fragments` match anywhere inside a word; both ignore case.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct WordFilter {
+    #[serde(default)]
+    words: Vec<String>,
+    #[serde(default)]
+    fragments: Vec<String>,
+}
+
+impl WordFilter {
+    fn matches_word(&self, word: &str) -> bool {
+        let word = word.to_lowercase();
+        self.words
+            .iter()
+            .any(|blocked| blocked.to_lowercase() == word)
+            || self
+                .fragments
+                .iter()
+                .any(|fragment| word.contains(&fragment.to_lowercase()))
+    }
+
+    fn words_of(text: &str) -> impl Iterator<Item = &str> {
+        text.split(|ch: char| !ch.is_alphanumeric())
+            .filter(|word| !word.is_empty())
+    }
+
+    fn blocks(&self, text: &str) -> bool {
+        Self::words_of(text).any(|word| self.matches_word(word))
+    }
+
+    fn censor(&self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        let mut word = String::new();
+        for ch in text.chars().chain(std::iter::once('\0')) {
+            if ch.is_alphanumeric() {
+                word.push(ch);
+                continue;
+            }
+            if self.matches_word(&word) {
+                out.push_str(&"*".repeat(word.chars().count()));
+            } else {
+                out.push_str(&word);
+            }
+            word.clear();
+            if ch != '\0' {
+                out.push(ch);
+            }
+        }
+        out
+    }
 }
 
 impl GameData {
@@ -4142,6 +11365,7 @@ impl GameData {
         monsters: Vec<MonsterDef>,
         quests: Vec<QuestDef>,
         npcs: Vec<NpcDef>,
+        chat_filter: WordFilter,
     ) -> Self {
         let items_map = items.into_iter().map(|item| (item.id.clone(), item)).collect();
         let resources_map = resources
@@ -4162,10 +11386,46 @@ impl GameData {
             monsters: monsters_map,
             quests_by_npc,
             npcs,
+            chat_filter,
         }
     }
 }
 
+#[derive(Clone, Serialize)]
+struct PartyInfo {
+    id: u64,
+    leader: String,
+    loot: LootMode,
+    members: Vec<PartyMemberInfo>,
+}
+
+#[derive(Clone, Serialize)]
+struct PartyMemberInfo {
+    id: String,
+    name: String,
+    x: f32,
+    y: f32,
+    hp: i32,
+}
+
+#[derive(Clone, Serialize)]
+struct GuildInfo {
+    id: String,
+    name: String,
+    tag: String,
+    members: Vec<GuildMemberInfo>,
+    officer_permissions: Vec<GuildPermission>,
+    member_permissions: Vec<GuildPermission>,
+}
+
+#[derive(Clone, Serialize)]
+struct GuildMemberInfo {
+    id: String,
+    name: String,
+    rank: GuildRank,
+    online: bool,
+}
+
 #[derive(Clone, Serialize)]
 struct PlayerPublic {
     id: String,
@@ -4177,6 +11437,10 @@ struct PlayerPublic {
     boat_id: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     last_input_seq: Option<u32>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pvp: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    guild: Option<String>,
 }
 
 impl From<&Player> for PlayerPublic {
@@ -4190,6 +11454,8 @@ impl From<&Player> for PlayerPublic {
             in_boat: player.in_boat,
             boat_id: player.boat_id,
             last_input_seq: None,
+            pvp: player.pvp,
+            guild: player.guild_tag.clone(),
         }
     }
 }
@@ -4205,6 +11471,8 @@ struct PlayerSelf {
     in_boat: bool,
     boat_id: Option<u64>,
     inventory: HashMap<String, i32>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    admin: bool,
 }
 
 #[derive(Clone, Serialize)]
@@ -4214,6 +11482,8 @@ struct MonsterPublic {
     x: f32,
     y: f32,
     hp: i32,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    boss: bool,
 }
 
 impl From<&Monster> for MonsterPublic {
@@ -4224,6 +11494,7 @@ impl From<&Monster> for MonsterPublic {
             x: monster.x,
             y: monster.y,
             hp: monster.hp,
+            boss: monster.boss,
         }
     }
 }
@@ -4248,14 +11519,37 @@ impl From<&Boat> for BoatPublic {
 #[derive(Clone, Serialize)]
 struct ProjectilePublic {
     id: u64,
+    kind: String,
+    x: f32,
+    y: f32,
+}
+
+#[derive(Clone, Serialize)]
+struct GroundItemPublic {
+    id: u64,
+    item: String,
+    count: i32,
     x: f32,
     y: f32,
 }
 
+impl From<&GroundItem> for GroundItemPublic {
+    fn from(item: &GroundItem) -> Self {
+        Self {
+            id: item.id,
+            item: item.item.clone(),
+            count: item.count,
+            x: item.x,
+            y: item.y,
+        }
+    }
+}
+
 impl From<&Projectile> for ProjectilePublic {
     fn from(projectile: &Projectile) -> Self {
         Self {
             id: projectile.id,
+            kind: projectile.kind.clone(),
             x: projectile.x,
             y: projectile.y,
         }
@@ -4348,12 +11642,117 @@ struct SessionResponse {
     name: String,
 }
 
+#[derive(Serialize)]
+struct AdminPlayerSummary {
+    id: String,
+    name: String,
+    x: f32,
+    y: f32,
+    hp: i32,
+    in_boat: bool,
+    pvp: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    guild: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    party: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct AdminPlayerView {
+    online: bool,
+    player: PlayerDoc,
+}
+
+#[derive(Deserialize)]
+struct AdminPlayerPatch {
+    name: Option<String>,
+    hp: Option<i32>,
+    inventory: Option<HashMap<String, i32>>,
+    completed_quests: Option<Vec<String>>,
+    role: Option<PlayerRole>,
+}
+
+#[derive(Deserialize)]
+struct AdminTeleportRequest {
+    x: f32,
+    y: f32,
+}
+
+#[derive(Deserialize)]
+struct AdminItemRequest {
+    item: String,
+    count: i32,
+}
+
+#[derive(Deserialize)]
+struct AdminReasonRequest {
+    reason: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AdminBanRequest {
+    reason: Option<String>,
+    duration_ms: Option<i64>,
+    #[serde(default)]
+    ban_ip: bool,
+}
+
+#[derive(Deserialize)]
+struct AdminCreateBanRequest {
+    player_id: Option<String>,
+    ip: Option<String>,
+    reason: Option<String>,
+    duration_ms: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct BanRejection {
+    error: &'static str,
+    reason: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires_ms: Option<i64>,
+}
+
+#[derive(Deserialize)]
+struct AdminBroadcastRequest {
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct AdminStructureQuery {
+    owner: Option<String>,
+}
+
+#[derive(Serialize)]
+struct AdminDeleteResponse {
+    removed: usize,
+}
+
+/// What a game data reload loaded, and the live entities it dropped because their
+/// definitions no longer exist.
+#[derive(Debug, Serialize)]
+struct DataReloadReport {
+    items: usize,
+    resources: usize,
+    monsters: usize,
+    quests: usize,
+    npcs: usize,
+    removed_monsters: usize,
+    removed_resources: usize,
+    removed_ground_items: usize,
+    /// Quest kill counts of online players for monsters that no longer exist.
+    removed_quest_kills: usize,
+    /// Inventory entries of online players whose item no longer exists; they are kept.
+    unknown_inventory_items: usize,
+}
+
 #[derive(Clone, Serialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 enum ServerMessage {
     Welcome {
         player: PlayerSelf,
         world: WorldConfig,
+        config: Box<GameplayConfig>,
         npcs: Vec<NpcPublic>,
         inventory_items: Vec<InventoryItem>,
     },
@@ -4363,18 +11762,27 @@ enum ServerMessage {
         tiles: Vec<u8>,
         resources: Vec<ResourceNodePublic>,
         structures: Vec<StructurePublic>,
+        weather: Weather,
     },
     EntitiesUpdate {
         players: Vec<PlayerPublic>,
         monsters: Vec<MonsterPublic>,
         projectiles: Vec<ProjectilePublic>,
         boats: Vec<BoatPublic>,
+        ground_items: Vec<GroundItemPublic>,
     },
     EntitiesRemove {
         players: Vec<String>,
         monsters: Vec<u64>,
         projectiles: Vec<u64>,
         boats: Vec<u64>,
+        ground_items: Vec<u64>,
+    },
+    ProjectileImpact {
+        id: u64,
+        x: f32,
+        y: f32,
+        hit: Impact,
     },
     ResourceUpdate {
         resource: ResourceNodePublic,
@@ -4387,9 +11795,32 @@ enum ServerMessage {
     Inventory {
         items: Vec<InventoryItem>,
     },
+    /// The full NPC list, replacing the one from `welcome` after a data reload.
+    Npcs {
+        npcs: Vec<NpcPublic>,
+    },
     Chat {
         from: String,
         text: String,
+        channel: ChatChannel,
+        /// Set on the sender's copy of a whisper.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        to: Option<String>,
+        #[serde(skip_serializing_if = "std::ops::Not::not")]
+        emote: bool,
+    },
+    PartyInvite {
+        from: String,
+    },
+    Party {
+        party: Option<PartyInfo>,
+    },
+    GuildInvite {
+        from: String,
+        guild: String,
+    },
+    Guild {
+        guild: Option<GuildInfo>,
     },
     Dialog {
         title: String,
@@ -4402,6 +11833,25 @@ enum ServerMessage {
         id: String,
         typing: bool,
     },
+    WorldTime {
+        time: f32,
+        day_length_ms: i64,
+        night: bool,
+    },
+    Weather {
+        chunks: Vec<ChunkWeather>,
+    },
+    Campfire {
+        lit: bool,
+    },
+    Kicked {
+        reason: String,
+    },
+    Banned {
+        reason: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        expires_ms: Option<i64>,
+    },
 }
 
 #[derive(Debug, Deserialize)]
@@ -4416,9 +11866,51 @@ enum ClientMessage {
         seq: u32,
         expected_x: Option<f32>,
         expected_y: Option<f32>,
+        aim_x: Option<f32>,
+        aim_y: Option<f32>,
+        target_id: Option<u64>,
+        target_player: Option<String>,
     },
     Chat {
         text: String,
+        #[serde(default)]
+        channel: ChatChannel,
+    },
+    PartyInvite {
+        name: String,
+    },
+    PartyAccept,
+    PartyLeave,
+    PartyKick {
+        name: String,
+    },
+    PartyLoot {
+        mode: LootMode,
+    },
+    GuildCreate {
+        name: String,
+        tag: String,
+    },
+    GuildInvite {
+        name: String,
+    },
+    GuildAccept,
+    GuildLeave,
+    GuildKick {
+        name: String,
+    },
+    GuildRank {
+        name: String,
+        rank: GuildRank,
+    },
+    GuildPermission {
+        rank: GuildRank,
+        permission: GuildPermission,
+        allowed: bool,
+    },
+    GuildClaim {
+        x: i32,
+        y: i32,
     },
     SetName {
         name: String,
@@ -4435,6 +11927,14 @@ enum ClientMessage {
         x: i32,
         y: i32,
     },
+    Plant {
+        id: String,
+        x: i32,
+        y: i32,
+    },
+    Pvp {
+        enabled: bool,
+    },
     Typing {
         typing: bool,
     },